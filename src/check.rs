@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use pathdiff::diff_paths;
+use tower_lsp::lsp_types::ClientCapabilities;
+
+use crate::{
+    config::Settings,
+    diagnostics::{
+        duplicate_headings, suggested_targets, suggestion_candidates, vault_unresolved_references,
+    },
+    vault::{Reference, Vault},
+};
+
+/// Check the vault at `root_dir` outside of the language server, printing unresolved links and duplicate
+/// headings as `file:line:col: message`. Returns the process exit code: 1 if any problems were found.
+pub fn check(root_dir: &Path) -> i32 {
+    let Ok(root_dir) = root_dir.canonicalize() else {
+        eprintln!(
+            "markdown-oxide: could not find directory {}",
+            root_dir.display()
+        );
+        return 2;
+    };
+
+    let settings = match Settings::new(&root_dir, &ClientCapabilities::default()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("markdown-oxide: failed to read settings: {e}");
+            return 2;
+        }
+    };
+
+    let vault = match Vault::construct_vault(&settings, &root_dir, None) {
+        Ok(vault) => vault,
+        Err(e) => {
+            eprintln!("markdown-oxide: failed to read vault: {e}");
+            return 2;
+        }
+    };
+
+    let candidates = suggestion_candidates(&vault);
+
+    let unresolved = vault_unresolved_references(&vault)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(path, reference)| {
+            let message = match suggested_targets(&candidates, reference).first() {
+                Some(target) => format!(
+                    "{} \"{}\" (did you mean \"{}\"?)",
+                    description(reference),
+                    reference.data().reference_text,
+                    *target.refname
+                ),
+                None => format!(
+                    "{} \"{}\"",
+                    description(reference),
+                    reference.data().reference_text
+                ),
+            };
+
+            (path, reference.data().range.start, message)
+        });
+
+    let duplicates = vault.md_files.iter().flat_map(|(path, md_file)| {
+        duplicate_headings(&md_file.headings)
+            .into_iter()
+            .map(|(heading, first)| {
+                (
+                    path.as_path(),
+                    heading.range.start,
+                    format!(
+                        "duplicate heading \"{}\" (first at line {})",
+                        heading.heading_text,
+                        first.range.start.line + 1
+                    ),
+                )
+            })
+            .collect_vec()
+    });
+
+    let problems = unresolved
+        .chain(duplicates)
+        .sorted_by_key(|(path, position, _)| (*path, position.line, position.character))
+        .collect_vec();
+
+    for (path, position, message) in &problems {
+        let path = diff_paths(path, &root_dir).unwrap_or(path.to_path_buf());
+        println!(
+            "{}:{}:{}: {}",
+            path.display(),
+            position.line + 1,
+            position.character + 1,
+            message
+        );
+    }
+
+    match problems.len() {
+        0 => 0,
+        _ => 1,
+    }
+}
+
+fn description(reference: &Reference) -> &'static str {
+    match reference {
+        Reference::WikiHeadingLink(..) | Reference::MDHeadingLink(..) => "unresolved heading",
+        Reference::WikiIndexedBlockLink(..) | Reference::MDIndexedBlockLink(..) => {
+            "unresolved block"
+        }
+        Reference::Footnote(..) => "unresolved footnote",
+        _ => "unresolved link",
+    }
+}
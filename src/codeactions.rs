@@ -1,17 +1,22 @@
-use std::path::Path;
+use std::{
+    iter,
+    path::{Path, PathBuf},
+};
 
 use pathdiff::diff_paths;
+use ropey::Rope;
 use tower_lsp::lsp_types::{
-    CodeAction, CodeActionOrCommand, CodeActionParams, CreateFile, CreateFileOptions,
-    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
-    Position, Range, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CreateFile,
+    CreateFileOptions, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
 };
 
 use crate::{
     config::Settings,
     daily::filename_is_formatted,
     diagnostics::path_unresolved_references,
-    vault::{Reference, Vault},
+    vault::{get_obsidian_ref_path, Reference, Vault},
 };
 
 pub fn code_actions(
@@ -19,6 +24,111 @@ pub fn code_actions(
     params: &CodeActionParams,
     path: &Path,
     settings: &Settings,
+) -> Option<Vec<CodeActionOrCommand>> {
+    let unresolved_actions = unresolved_reference_code_actions(vault, params, path, settings);
+    let extract_action = extract_to_note_code_action(vault, params, path, settings);
+    let redundant_display_text_actions = redundant_display_text_code_actions(vault, params, path);
+
+    Some(
+        unresolved_actions
+            .into_iter()
+            .flatten()
+            .chain(extract_action)
+            .chain(redundant_display_text_actions.into_iter().flatten())
+            .collect(),
+    )
+}
+
+/// Remove the display text of wiki links that only repeat their target, as in `[[Note|Note]]`: for the link at the
+/// cursor, or for every such link in the file
+fn redundant_display_text_code_actions(
+    vault: &Vault,
+    params: &CodeActionParams,
+    path: &Path,
+) -> Option<Vec<CodeActionOrCommand>> {
+    let uri = Url::from_file_path(path).ok()?;
+    let rope = vault.ropes.get(path)?;
+
+    let redundant = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter(|(_, reference)| reference.has_redundant_display_text())
+        .filter_map(|(_, reference)| {
+            let range = *reference.data().range;
+            let start = rope.try_line_to_char(range.start.line as usize).ok()?
+                + range.start.character as usize;
+            let end =
+                rope.try_line_to_char(range.end.line as usize).ok()? + range.end.character as usize;
+            let link = rope.get_slice(start..end)?.to_string();
+            let (target, _display) = link.rsplit_once('|')?;
+
+            Some((
+                range,
+                TextEdit {
+                    range,
+                    new_text: format!("{target}]]"),
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let action = |title: &str, kind: CodeActionKind, edits: Vec<TextEdit>| {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_string(),
+            kind: Some(kind),
+            diagnostics: Some(
+                params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .filter(|diagnostic| edits.iter().any(|edit| edit.range == diagnostic.range))
+                    .cloned()
+                    .collect(),
+            )
+            .filter(|diagnostics: &Vec<_>| !diagnostics.is_empty()),
+            edit: Some(WorkspaceEdit {
+                document_changes: Some(DocumentChanges::Operations(vec![
+                    DocumentChangeOperation::Edit(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: uri.clone(),
+                            version: None,
+                        },
+                        edits: edits.into_iter().map(OneOf::Left).collect(),
+                    }),
+                ])),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    };
+
+    let at_cursor = redundant
+        .iter()
+        .filter(|(range, _)| range.start <= params.range.start && range.end >= params.range.end)
+        .map(|(_, edit)| {
+            action(
+                "Remove redundant display text",
+                CodeActionKind::QUICKFIX,
+                vec![edit.clone()],
+            )
+        });
+
+    let in_file = (redundant.len() > 1).then(|| {
+        action(
+            "Remove all redundant display text in this file",
+            CodeActionKind::SOURCE,
+            redundant.iter().map(|(_, edit)| edit.clone()).collect(),
+        )
+    });
+
+    Some(at_cursor.chain(in_file).collect())
+}
+
+fn unresolved_reference_code_actions(
+    vault: &Vault,
+    params: &CodeActionParams,
+    path: &Path,
+    settings: &Settings,
 ) -> Option<Vec<CodeActionOrCommand>> {
     // Diagnostics
     // get all links for changed file
@@ -28,58 +138,91 @@ pub fn code_actions(
     let unresolved_file_links = unresolved;
 
     let code_action_unresolved = unresolved_file_links.into_iter().filter(|(_, reference)| {
-        reference.data().range.start.line <= params.range.start.line
-            && reference.data().range.end.line >= params.range.end.line
-            && reference.data().range.start.character <= params.range.start.character
-            && reference.data().range.end.character >= params.range.end.character
+        let range = reference.data().range;
+
+        (range.start.line <= params.range.start.line
+            && range.end.line >= params.range.end.line
+            && range.start.character <= params.range.start.character
+            && range.end.character >= params.range.end.character)
+            || params
+                .context
+                .diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.range == *range)
     });
 
     Some(
         code_action_unresolved
             .flat_map(|(_path, reference)| {
+                // the diagnostic this action fixes, if the client sent it along
+                let diagnostics = params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.range == *reference.data().range)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let diagnostics = (!diagnostics.is_empty()).then_some(diagnostics);
+
                 match reference {
-                    Reference::WikiFileLink(_data) => {
+                    Reference::WikiFileLink(_data) | Reference::MDFileLink(_data) => {
                         let filename = &reference.data().reference_text;
 
-                        let mut new_path_buf = vault.root_dir().clone();
-                        if filename_is_formatted(settings, filename) {
-                            new_path_buf.push(&settings.daily_notes_folder);
-                        } else {
-                            new_path_buf.push(&settings.new_file_folder_path);
-                        }
-                        new_path_buf.push(filename);
-                        new_path_buf.set_extension("md");
+                        let new_path_buf =
+                            unresolved_file_path(vault, settings, path, reference, filename);
 
                         let new_path = Url::from_file_path(&new_path_buf).ok()?;
 
+                        let create_op =
+                            DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                                uri: new_path.clone(),
+                                options: None,
+                                annotation_id: None,
+                            }));
+
+                        // seed the new note with frontmatter and a title heading
+                        let title = new_path_buf.file_stem()?.to_string_lossy();
+                        let seed = settings
+                            .frontmatter_scaffold(&title)
+                            .into_iter()
+                            .chain(settings.title_headings.then(|| format!("# {}\n", title)))
+                            .collect::<String>();
+
+                        let title_op = match seed.is_empty() {
+                            false => Some(DocumentChangeOperation::Edit(TextDocumentEdit {
+                                text_document: OptionalVersionedTextDocumentIdentifier {
+                                    uri: new_path,
+                                    version: None,
+                                },
+                                edits: vec![OneOf::Left(TextEdit {
+                                    new_text: seed,
+                                    range: Range::default(),
+                                })],
+                            })),
+                            true => None,
+                        };
+
                         Some(CodeActionOrCommand::CodeAction(CodeAction {
                             title: format!(
                                 "Create File: {:?}",
-                                diff_paths(new_path_buf, vault.root_dir())?
+                                diff_paths(&new_path_buf, vault.root_dir())?
                             ),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            is_preferred: Some(true),
+                            diagnostics,
                             edit: Some(WorkspaceEdit {
-                                document_changes: Some(DocumentChanges::Operations(vec![
-                                    DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
-                                        uri: new_path,
-                                        options: None,
-                                        annotation_id: None,
-                                    })),
-                                ])),
+                                document_changes: Some(DocumentChanges::Operations(
+                                    iter::once(create_op).chain(title_op).collect(),
+                                )),
                                 ..Default::default()
                             }),
                             ..Default::default()
                         }))
                     }
-                    Reference::WikiHeadingLink(_data, link_path, heading) => {
-
-                        let mut new_path_buf = vault.root_dir().clone();
-                        if filename_is_formatted(settings, link_path) {
-                            new_path_buf.push(&settings.daily_notes_folder);
-                        } else {
-                            new_path_buf.push(&settings.new_file_folder_path);
-                        }
-                        new_path_buf.push(link_path);
-                        new_path_buf.set_extension("md");
+                    Reference::WikiHeadingLink(_data, link_path, heading)
+                    | Reference::MDHeadingLink(_data, link_path, heading) => {
+                        let new_path_buf =
+                            unresolved_file_path(vault, settings, path, reference, link_path);
 
                         let new_path = Url::from_file_path(&new_path_buf).ok()?;
 
@@ -93,7 +236,16 @@ pub fn code_actions(
 
                         let new_text = match file {
                             Some(..) => format!("\n\n# {}", heading),
-                            None => format!("# {}", heading)
+                            None => format!(
+                                "{}# {}",
+                                new_path_buf
+                                    .file_stem()
+                                    .and_then(|title| {
+                                        settings.frontmatter_scaffold(&title.to_string_lossy())
+                                    })
+                                    .unwrap_or_default(),
+                                heading
+                            )
                         }; // move this calculation to the vault somehow
 
 
@@ -103,6 +255,8 @@ pub fn code_actions(
                                 heading,
                                 link_path
                             ),
+                            kind: Some(CodeActionKind::QUICKFIX),
+                            diagnostics,
                             edit: Some(WorkspaceEdit{
                                 document_changes: Some(DocumentChanges::Operations(vec![
                                     DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
@@ -147,3 +301,295 @@ pub fn code_actions(
             .collect(),
     )
 }
+
+/// The path a new note must have to resolve the given link text of the note at `path`. Mirrors the matching in the
+/// vault: markdown links with a `/` are relative to the folder of the note, and wiki links with one to the vault root;
+/// bare names go in the daily notes or new file folder.
+fn unresolved_file_path(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    reference: &Reference,
+    link_path: &str,
+) -> PathBuf {
+    let link_path = link_path.replace(r"%20", " ").replace(r"\ ", " ");
+
+    // Html links are resolved from the folder of the note as they are parsed
+    let is_html = vault
+        .select_line(path, reference.data().range.start.line as isize)
+        .is_some_and(|line| {
+            String::from_iter(
+                line.iter()
+                    .skip(reference.data().range.start.character as usize),
+            )
+            .starts_with("<a")
+        });
+    let relative_to_note = matches!(
+        reference,
+        Reference::MDFileLink(..) | Reference::MDHeadingLink(..)
+    ) && !is_html
+        && !link_path.starts_with('/');
+
+    let mut new_path_buf = vault.root_dir().clone();
+    if link_path.contains('/') {
+        if let Some(folder) = path.parent().filter(|_| relative_to_note) {
+            new_path_buf = folder.to_path_buf();
+        }
+        new_path_buf.push(link_path.trim_start_matches("./").trim_start_matches('/'));
+    } else {
+        if filename_is_formatted(settings, &link_path) {
+            new_path_buf.push(&settings.daily_notes_folder);
+        } else {
+            new_path_buf.push(&settings.new_file_folder_path);
+        }
+        new_path_buf.push(&link_path);
+    }
+    new_path_buf.set_extension("md");
+
+    new_path_buf
+}
+
+/// Move a multi-line selection into a new note and replace the selection with an embed of that note.
+fn extract_to_note_code_action(
+    vault: &Vault,
+    params: &CodeActionParams,
+    path: &Path,
+    settings: &Settings,
+) -> Option<CodeActionOrCommand> {
+    let range = params.range;
+    if range.start.line == range.end.line {
+        return None;
+    }
+
+    let rope = vault.ropes.get(path)?;
+    let start = position_char_index(rope, range.start)?;
+    let end = position_char_index(rope, range.end)?;
+    let selected_text = rope.get_slice(start..end)?.to_string();
+
+    if selected_text.trim().is_empty() {
+        return None;
+    }
+
+    // Name the note after the first heading in the selection
+    let note_name = vault
+        .select_headings(path)
+        .and_then(|headings| {
+            headings.iter().find(|heading| {
+                heading.range.start.line >= range.start.line
+                    && heading.range.end.line <= range.end.line
+            })
+        })
+        .map(|heading| {
+            heading
+                .heading_text
+                .chars()
+                .filter(|c| !r#"\/:*?"<>|#^[]"#.contains(*c))
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or("New Note".to_string());
+
+    // Append a numeric suffix until the path is free so that an existing note is never overwritten
+    let folder = vault.root_dir().join(&settings.new_file_folder_path);
+    let (note_name, new_path_buf) = iter::once(note_name.clone())
+        .chain((1..).map(|i| format!("{} {}", note_name, i)))
+        .map(|name| {
+            let path = folder.join(format!("{}.md", name));
+            (name, path)
+        })
+        .find(|(_, path)| !path.exists() && !vault.md_files.contains_key(path))?;
+
+    let note_refname = get_obsidian_ref_path(vault.root_dir(), &new_path_buf)?;
+    let new_uri = Url::from_file_path(&new_path_buf).ok()?;
+    let current_uri = Url::from_file_path(path).ok()?;
+
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Extract selection to new note \"{}\"", note_name),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: new_uri.clone(),
+                    options: Some(CreateFileOptions {
+                        ignore_if_exists: Some(false),
+                        overwrite: Some(false),
+                    }),
+                    annotation_id: None,
+                })),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: new_uri,
+                        version: None,
+                    },
+                    edits: vec![OneOf::Left(TextEdit {
+                        range: Range::default(),
+                        new_text: format!(
+                            "{}{}",
+                            settings
+                                .frontmatter_scaffold(&note_name)
+                                .unwrap_or_default(),
+                            selected_text
+                        ),
+                    })],
+                }),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: current_uri,
+                        version: None,
+                    },
+                    edits: vec![OneOf::Left(TextEdit {
+                        range,
+                        new_text: format!("!{}", settings.note_link(&note_refname)),
+                    })],
+                }),
+            ])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// The char index in `rope` of an LSP position from the client, whose character offset counts UTF-16 code units
+fn position_char_index(rope: &Rope, position: Position) -> Option<usize> {
+    let line_start = rope.try_line_to_char(position.line as usize).ok()?;
+    let line_start_utf16 = rope.try_char_to_utf16_cu(line_start).ok()?;
+
+    rope.try_utf16_cu_to_char(line_start_utf16 + position.character as usize)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{
+        CodeAction, CodeActionOrCommand, CodeActionParams, DocumentChangeOperation,
+        DocumentChanges, OneOf, Position, Range, TextDocumentIdentifier, Url,
+    };
+
+    use crate::config::LinkStyle;
+    use crate::vault::{test_settings, Referenceable, Vault};
+
+    use super::{extract_to_note_code_action, unresolved_file_path};
+
+    #[test]
+    fn created_notes_resolve_links() {
+        let (root_dir, mut settings) = test_settings();
+        settings.new_file_folder_path = "new".to_string();
+
+        let path = root_dir.join("a/b.md");
+        let text = "[[sub/z]] [md](sub/y.md) [bare](x.md) <a href=\"sub/h.md\">html</a>";
+        let vault = Vault::of_notes(&settings, &root_dir, [(path.clone(), text)]);
+        let references = &vault.md_files[&path].references;
+
+        let new_paths = references
+            .iter()
+            .map(|reference| {
+                unresolved_file_path(
+                    &vault,
+                    &settings,
+                    &path,
+                    reference,
+                    &reference.data().reference_text,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            new_paths,
+            ["sub/z.md", "a/sub/y.md", "new/x.md", "a/sub/h.md"].map(|new| root_dir.join(new))
+        );
+
+        // Once created, the notes resolve the links
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            std::iter::once((path.clone(), text))
+                .chain(new_paths.iter().map(|new_path| (new_path.clone(), ""))),
+        );
+        for reference in &vault.md_files[&path].references {
+            let resolved = vault.select_referenceables_for_reference(reference, &path);
+            assert!(
+                matches!(resolved[..], [Referenceable::File(..)]),
+                "{reference:?} resolves to {resolved:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn extract_selection_across_emoji() {
+        let (root_dir, mut settings) = test_settings();
+        settings.new_file_folder_path = "extracted".to_string();
+        settings.new_note_frontmatter = None;
+        settings.default_link_style = LinkStyle::Wiki;
+        settings.include_md_extension_wikilink = false;
+
+        let path = root_dir.join("a.md");
+        let text = "# Top 😀\n\nkeep 😀 then\n## Moved\nbody 🎉 text\nend 😀 tail\n";
+        let vault = Vault::of_notes(&settings, &root_dir, [(path.clone(), text)]);
+
+        // Character offsets are in UTF-16 code units, in which each emoji is two long
+        let range = Range {
+            start: Position {
+                line: 2,
+                character: 8,
+            },
+            end: Position {
+                line: 5,
+                character: 6,
+            },
+        };
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&path).unwrap(),
+            },
+            range,
+            context: Default::default(),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let Some(CodeActionOrCommand::CodeAction(CodeAction {
+            title,
+            edit: Some(edit),
+            ..
+        })) = extract_to_note_code_action(&vault, &params, &path, &settings)
+        else {
+            panic!("no extract action")
+        };
+        assert_eq!(title, "Extract selection to new note \"Moved\"");
+
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("no document changes")
+        };
+        let edits = operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some((edit.text_document.uri, edit.edits)),
+                _ => None,
+            })
+            .flat_map(|(uri, edits)| {
+                edits.into_iter().map(move |edit| match edit {
+                    OneOf::Left(edit) => (uri.to_file_path().unwrap(), edit.range, edit.new_text),
+                    OneOf::Right(edit) => (
+                        uri.to_file_path().unwrap(),
+                        edit.text_edit.range,
+                        edit.text_edit.new_text,
+                    ),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            edits,
+            vec![
+                (
+                    root_dir.join("extracted/Moved.md"),
+                    Range::default(),
+                    "then\n## Moved\nbody 🎉 text\nend 😀".to_string()
+                ),
+                (path, range, "![[extracted/Moved]]".to_string()),
+            ]
+        );
+    }
+}
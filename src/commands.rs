@@ -1,20 +1,39 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::File;
+use std::io::Write;
 use std::path::Path;
 
-use crate::config::Settings;
+use crate::completion::matcher::{fuzzy_match, Matchable};
+use crate::config::{Case, Settings};
+use crate::daily::{canonical_daily_note_name, format_is_valid, try_format};
+use crate::diagnostics::vault_unresolved_references;
+use crate::rename::rename_referenceable;
+use crate::tasks::open_tasks;
+use crate::vault::{
+    encode_link_path, get_obsidian_ref_path, MDHeading, Rangeable, Reference, Referenceable, Vault,
+};
 use chrono::offset::Local;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use fuzzydate::parse;
+use itertools::Itertools;
+use nanoid::nanoid;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::Serialize;
 use serde_json::Value;
 use tower_lsp::jsonrpc::{Error, Result};
-use tower_lsp::lsp_types::{MessageType, ShowDocumentParams, Url};
+use tower_lsp::lsp_types::{
+    CreateFile, DocumentChangeOperation, DocumentChanges, Location, MessageType, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, ShowDocumentParams,
+    TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
 
 fn datetime_to_file(
     datetime: NaiveDateTime,
     dailynote_format: &str,
     root_dir: &Path,
 ) -> Option<Url> {
-    let filename = datetime.format(dailynote_format).to_string();
+    let filename = try_format(datetime.format(dailynote_format))?;
     let path = root_dir.join(&filename);
 
     Url::from_file_path(path.with_extension("md")).ok()
@@ -31,35 +50,21 @@ pub async fn jump(
     let daily_note_format = &settings.dailynote;
     let daily_note_path = root_dir.join(&settings.daily_notes_folder);
     let note_file = match jump_to {
-        Some(jmp_str) => parse(jmp_str)
-            .ok()
-            .and_then(|dt| datetime_to_file(dt, &daily_note_format, &daily_note_path)),
-        None => datetime_to_file(
-            Local::now().naive_local(),
-            &daily_note_format,
-            &daily_note_path,
-        ),
+        Some(jmp_str) => parse(jmp_str).ok().and_then(|dt| {
+            Some((
+                datetime_to_file(dt, &daily_note_format, &daily_note_path)?,
+                dt.date(),
+            ))
+        }),
+        None => {
+            let now = Local::now().naive_local();
+            datetime_to_file(now, &daily_note_format, &daily_note_path).map(|uri| (uri, now.date()))
+        }
     };
 
-    if let Some(uri) = note_file {
-        // file creation can fail and return an Err, ignore this and try
-        // to open the file on the off chance the client knows what to do
-        // TODO: log failure to create file
-        let _ = uri.to_file_path().map(|path| {
-            path.parent().map(|parent| std::fs::create_dir_all(parent));
-
-            let _ = File::create_new(path.as_path().to_owned());
-        });
-
-        client
-            .show_document(ShowDocumentParams {
-                uri,
-                external: Some(false),
-                take_focus: Some(true),
-                selection: None,
-            })
-            .await
-            .map(|success| Some(success.into()))
+    if let Some((uri, date)) = note_file {
+        create_daily_note(client, root_dir, settings, &uri, date).await;
+        show_note(client, settings, uri).await
     } else {
         client
             .log_message(
@@ -74,12 +79,1110 @@ pub async fn jump(
     }
 }
 
+/// Open today's daily note, creating it with the content of the daily note template if it does not exist yet; the
+/// daily notes folder is created if needed
+pub async fn daily(
+    client: &tower_lsp::Client,
+    root_dir: &Path,
+    settings: &Settings,
+) -> Result<Option<Value>> {
+    let today = Local::now().naive_local();
+    let daily_note_path = root_dir.join(&settings.daily_notes_folder);
+
+    let Some(uri) = datetime_to_file(today, &settings.dailynote, &daily_note_path) else {
+        return Err(Error::invalid_params(
+            "Could not create a uri for today's daily note",
+        ));
+    };
+
+    create_daily_note(client, root_dir, settings, &uri, today.date()).await;
+    show_note(client, settings, uri).await
+}
+
+/// Create the daily note of `date` with the content of the daily note template, if one is configured and the note
+/// does not exist yet. A template that cannot be read is logged; the note is then created empty when it is shown.
+async fn create_daily_note(
+    client: &tower_lsp::Client,
+    root_dir: &Path,
+    settings: &Settings,
+    uri: &Url,
+    date: NaiveDate,
+) {
+    let (Some(template_path), Ok(path)) = (&settings.daily_note_template, uri.to_file_path())
+    else {
+        return;
+    };
+
+    if path.exists() {
+        return;
+    }
+
+    let Some(template) = daily_note_template(&root_dir.join(template_path)) else {
+        client
+            .log_message(
+                MessageType::WARNING,
+                format!("Could not read the daily note template {template_path}"),
+            )
+            .await;
+        return;
+    };
+
+    // the daily notes folder may not exist yet
+    path.parent().map(std::fs::create_dir_all);
+
+    if let Ok(mut file) = File::create_new(&path) {
+        let title = path.file_stem().unwrap_or_default().to_string_lossy();
+        let _ = file.write_all(filled_template(&template, &title, date).as_bytes());
+    }
+}
+
+/// The content of the template note; its extension may be left out, as in the Obsidian setting
+fn daily_note_template(template: &Path) -> Option<String> {
+    std::fs::read_to_string(template)
+        .or_else(|_| std::fs::read_to_string(template.with_extension("md")))
+        .ok()
+}
+
+/// The template with `{{title}}` replaced by the note name, `{{date}}` by the date, and `{{date:format}}` by the date
+/// in a format such as the daily note format. Placeholders with invalid formats are left as they are.
+fn filled_template(template: &str, title: &str, date: NaiveDate) -> String {
+    static DATE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"\{\{date:(?<format>[^}]+)\}\}").unwrap());
+
+    let template = DATE_RE.replace_all(template, |captures: &Captures| {
+        let format = &captures["format"];
+
+        format_is_valid(format)
+            .then(|| try_format(date.format(format)))
+            .flatten()
+            .unwrap_or_else(|| captures[0].to_string())
+    });
+
+    template
+        .replace("{{title}}", title)
+        .replace("{{date}}", &date.format("%Y-%m-%d").to_string())
+}
+
+/// Jump to the periodic note of a date, such as one named by a relative daily note command; the note is named by `format`
+/// in `folder`
+pub async fn jump_to_date(
+    client: &tower_lsp::Client,
+    root_dir: &Path,
+    settings: &Settings,
+    (format, folder): (&str, &str),
+    date: NaiveDate,
+) -> Result<Option<Value>> {
+    let note_path = root_dir.join(folder);
+
+    match datetime_to_file(date.and_time(NaiveTime::MIN), format, &note_path) {
+        Some(uri) => {
+            // Weekly and monthly notes are not created from the daily note template
+            if (format, folder) == (&settings.dailynote, &settings.daily_notes_folder) {
+                create_daily_note(client, root_dir, settings, &uri, date).await;
+            }
+
+            show_note(client, settings, uri).await
+        }
+        None => Err(Error::invalid_params(format!(
+            "Could not create a note uri for {date}"
+        ))),
+    }
+}
+
+/// Rename daily notes named in other date formats, such as `2024_3_5`, to the configured format, updating the links
+/// to them. Returns the number of renamed notes with the edit doing so.
+pub fn normalize_daily_notes(vault: &Vault, settings: &Settings) -> (usize, WorkspaceEdit) {
+    let mut taken = HashSet::new();
+
+    let renames = vault
+        .md_files
+        .iter()
+        .sorted_by_key(|(path, _)| *path)
+        .filter_map(|(path, md_file)| {
+            let canonical = canonical_daily_note_name(settings, md_file.file_name()?)?;
+            let new_path = path.with_file_name(format!("{canonical}.md"));
+
+            // Never overwrite a note, including one that another misnamed note is renamed to
+            if new_path.exists()
+                || vault.md_files.contains_key(&new_path)
+                || !taken.insert(new_path)
+            {
+                return None;
+            }
+
+            rename_referenceable(
+                vault,
+                Referenceable::File(path, md_file),
+                &canonical,
+                settings,
+            )
+        })
+        .collect_vec();
+
+    // Links are updated before any note is renamed, as the edits address notes by their old paths
+    let (edits, renames): (Vec<_>, Vec<_>) = renames
+        .iter()
+        .flat_map(|edit| match &edit.document_changes {
+            Some(DocumentChanges::Operations(operations)) => operations.clone(),
+            _ => vec![],
+        })
+        .partition(|operation| matches!(operation, DocumentChangeOperation::Edit(..)));
+
+    (
+        renames.len(),
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(
+                edits.into_iter().chain(renames).collect(),
+            )),
+            ..Default::default()
+        },
+    )
+}
+
+/// A heading link whose heading no longer exists, with the closest existing heading of the linked note
+pub struct AnchorFix {
+    pub range: Range,
+    pub reference_text: String,
+    pub heading: String,
+    pub new_text: String,
+}
+
+impl Matchable for &MDHeading {
+    fn match_string(&self) -> &str {
+        &self.heading_text
+    }
+}
+
+/// A note linking to the current note, with the edit inserting a link back to it
+#[derive(Debug, Serialize)]
+pub struct BacklinkSource {
+    pub note: String,
+    pub edit: WorkspaceEdit,
+}
+
+/// The notes other than itself that link to the note at `path` or its headings and blocks, each with the edit
+/// inserting a link to it at `position`; clients let the user pick one and apply its edit with `apply_edits`
+pub fn backlink_sources(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    position: Position,
+) -> Option<Vec<BacklinkSource>> {
+    let uri = Url::from_file_path(path).ok()?;
+
+    let sources = vault
+        .select_referenceable_nodes(Some(path))
+        .into_iter()
+        .filter(|referenceable| {
+            matches!(
+                referenceable,
+                Referenceable::File(..)
+                    | Referenceable::Heading(..)
+                    | Referenceable::IndexedBlock(..)
+            )
+        })
+        .flat_map(|referenceable| vault.select_references_for_referenceable(&referenceable))
+        .flatten()
+        .map(|(source, _)| source)
+        .filter(|source| *source != path)
+        .unique()
+        .sorted()
+        .filter_map(|source| {
+            let note = get_obsidian_ref_path(vault.root_dir(), source)?;
+            let edit = WorkspaceEdit {
+                changes: Some(
+                    [(
+                        uri.clone(),
+                        vec![TextEdit {
+                            range: Range::new(position, position),
+                            new_text: settings.note_link(&note),
+                        }],
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+                ..Default::default()
+            };
+
+            Some(BacklinkSource { note, edit })
+        })
+        .collect();
+
+    Some(sources)
+}
+
+/// The locations of the unresolved links of the whole vault, including links to missing headings and blocks of notes
+/// that exist, in order of file and position; clients can fill a quickfix list with these. Diagnostics are only
+/// published for open files.
+pub fn broken_links(vault: &Vault) -> Vec<Location> {
+    vault_unresolved_references(vault)
+        .unwrap_or_default()
+        .into_iter()
+        .sorted_by_key(|(path, reference)| {
+            let start = reference.data().range.start;
+            (*path, start.line, start.character)
+        })
+        .filter_map(|(path, reference)| {
+            Some(Location {
+                uri: Url::from_file_path(path).ok()?,
+                range: *reference.data().range,
+            })
+        })
+        .collect()
+}
+
+/// Find the heading links in the file at `path` whose headings no longer exist, such as after a heading was renamed
+/// outside of the server, and the closest existing heading of the linked note to fix each to.
+pub fn broken_anchor_fixes(vault: &Vault, path: &Path) -> Vec<AnchorFix> {
+    let Some(references) = vault.select_references(Some(path)) else {
+        return vec![];
+    };
+
+    references
+        .into_iter()
+        .filter_map(|(path, reference)| {
+            let (file_ref, heading) = match reference {
+                Reference::WikiHeadingLink(_, file_ref, heading)
+                | Reference::MDHeadingLink(_, file_ref, heading) => (file_ref, heading),
+                _ => return None,
+            };
+
+            let resolved = vault
+                .select_referenceables_for_reference(reference, path)
+                .iter()
+                .any(|referenceable| !referenceable.is_unresolved());
+            if resolved {
+                return None;
+            }
+
+            // Only the target of a chained heading link such as `[[Note#H1#H2]]` is fixed
+            let target = heading.rsplit('#').next()?;
+
+            let headings = vault
+                .select_file_referenceables(file_ref)
+                .into_iter()
+                .find_map(|referenceable| match referenceable {
+                    Referenceable::File(_, md_file) => Some(&md_file.headings),
+                    _ => None,
+                })?;
+
+            let (closest, _) = fuzzy_match(target, headings, &Case::Ignore)
+                .into_iter()
+                .max_by_key(|(_, score)| *score)?;
+
+            let range = *reference.data().range;
+            let link = vault
+                .select_line(path, range.start.line as isize)?
+                .get(range.start.character as usize..range.end.character as usize)?
+                .iter()
+                .collect::<String>();
+
+            let new_text = match reference {
+                Reference::MDHeadingLink(..) => {
+                    fixed_link_text(&link, target, &encode_link_path(&closest.heading_text))
+                }
+                _ => {
+                    // The display text of a wiki link follows the heading and may repeat it
+                    let (link, display) = link.split_at(link.find('|').unwrap_or(link.len()));
+                    fixed_link_text(link, target, &closest.heading_text)
+                        .map(|link| format!("{link}{display}"))
+                }
+            }?;
+
+            Some(AnchorFix {
+                range,
+                reference_text: reference.data().reference_text.clone(),
+                heading: closest.heading_text.clone(),
+                new_text,
+            })
+        })
+        .collect()
+}
+
+/// The link with its last heading, `old`, replaced by `new`
+fn fixed_link_text(link: &str, old: &str, new: &str) -> Option<String> {
+    let start = link.rfind(&format!("#{old}"))? + 1;
+
+    Some(format!(
+        "{}{}{}",
+        &link[..start],
+        new,
+        &link[start + old.len()..]
+    ))
+}
+
+/// Renumber the numbered footnotes of the file at `path` in the order they are first referenced and move the footnote
+/// definitions, with the indented lines continuing them, to the bottom of the file in that order. Footnotes in code
+/// are left as they are, as are the line endings of the file.
+pub fn renumber_footnotes(vault: &Vault, path: &Path) -> Option<WorkspaceEdit> {
+    let md_file = vault.md_files.get(path)?;
+    let rope = vault.ropes.get(path)?;
+
+    let references = md_file
+        .references
+        .iter()
+        .filter(|reference| {
+            !md_file
+                .codeblocks
+                .iter()
+                .any(|codeblock| codeblock.includes(*reference))
+        })
+        .filter_map(|reference| match reference {
+            Reference::Footnote(data) => Some((*data.range, data.reference_text.as_str())),
+            _ => None,
+        })
+        .sorted_by_key(|(range, _)| range.start)
+        .collect_vec();
+
+    let definitions = md_file
+        .footnotes
+        .iter()
+        .sorted_by_key(|footnote| footnote.range.start)
+        .map(|footnote| (*footnote.range, footnote.index.as_str()))
+        .collect_vec();
+
+    if references.is_empty() && definitions.is_empty() {
+        return None;
+    }
+
+    let lines = rope
+        .lines()
+        .map(|line| line.to_string().trim_end_matches(['\n', '\r']).to_string())
+        .collect_vec();
+    let eol = match rope.to_string().contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+
+    Some(WorkspaceEdit {
+        changes: Some(
+            [(
+                Url::from_file_path(path).ok()?,
+                footnote_edits(&lines, eol, &references, &definitions),
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        ..Default::default()
+    })
+}
+
+/// The edits to the `lines` of a note numbering its numbered footnotes, as in `[^1]`, in the order they are first
+/// referenced, followed by those that are only defined, and moving the definitions at the start of a line to the bottom
+/// in that order. Named footnotes, as in `[^note]`, keep their names; a footnote defined more than once keeps its first
+/// definition. The text of definitions is kept as it is.
+fn footnote_edits(
+    lines: &[String],
+    eol: &str,
+    references: &[(Range, &str)],
+    definitions: &[(Range, &str)],
+) -> Vec<TextEdit> {
+    // Definitions after other text on their line are renumbered in place, as moving the line would move that text
+    let moved = definitions
+        .iter()
+        .filter(|(range, _)| range.start.character == 0)
+        .collect_vec();
+
+    let order = references
+        .iter()
+        .map(|(_, index)| index)
+        .chain(moved.iter().map(|(_, index)| index))
+        .unique()
+        .collect_vec();
+
+    let numbers: HashMap<&str, String> = order
+        .iter()
+        .filter(|index| {
+            index
+                .strip_prefix('^')
+                .is_some_and(|number| number.parse::<u32>().is_ok())
+        })
+        .enumerate()
+        .map(|(number, index)| (**index, format!("^{}", number + 1)))
+        .collect();
+
+    let blank = |line: usize| lines[line].trim().is_empty();
+
+    // The first and last lines of each moved definition; indented lines continue it, even after blank lines
+    let blocks = moved
+        .iter()
+        .map(|(range, index)| {
+            let start = range.start.line as usize;
+            let end = (start + 1..lines.len())
+                .take_while(|line| blank(*line) || lines[*line].starts_with([' ', '\t']))
+                .filter(|line| !blank(*line))
+                .last()
+                .unwrap_or(start);
+
+            (start, end, *index)
+        })
+        .collect_vec();
+    let in_block = |line: usize| {
+        blocks
+            .iter()
+            .any(|(start, end, _)| (*start..=*end).contains(&line))
+    };
+
+    // The footnote references and definition labels that are renumbered
+    let replacements = references
+        .iter()
+        .copied()
+        .chain(definitions.iter().map(|(range, index)| {
+            let end = Position::new(
+                range.start.line,
+                range.start.character + index.chars().count() as u32 + 2,
+            );
+
+            (Range::new(range.start, end), *index)
+        }))
+        .filter_map(|(range, index)| {
+            let number = numbers.get(index).filter(|number| *number != index)?;
+            Some((range, format!("[{number}]")))
+        })
+        .sorted_by_key(|(range, _)| range.start)
+        .collect_vec();
+
+    let renumbered_line = |line: usize| {
+        let mut text = lines[line].chars().collect_vec();
+        for (range, new_text) in replacements
+            .iter()
+            .filter(|(range, _)| range.start.line as usize == line)
+            .rev()
+        {
+            text.splice(
+                range.start.character as usize..range.end.character as usize,
+                new_text.chars(),
+            );
+        }
+
+        text.into_iter().collect::<String>()
+    };
+
+    let moved_text = order
+        .iter()
+        .filter_map(|index| blocks.iter().find(|(_, _, block)| block == *index))
+        .map(|(start, end, _)| (*start..=*end).map(renumbered_line).join(eol))
+        .collect_vec();
+
+    let last_kept = (0..lines.len())
+        .rev()
+        .find(|line| !in_block(*line) && !blank(*line));
+
+    let renumbered = replacements
+        .iter()
+        .filter(|(range, _)| !in_block(range.start.line as usize))
+        .map(|(range, new_text)| TextEdit {
+            range: *range,
+            new_text: new_text.clone(),
+        });
+
+    // A blank line after a moved definition is removed with it when the kept line before it is blank too, so that
+    // blank lines are not doubled
+    let removed = blocks
+        .iter()
+        .filter(|(start, _, _)| last_kept.is_some_and(|last_kept| *start < last_kept))
+        .map(|(start, end, _)| {
+            let blank_before = match (0..*start).rev().find(|line| !in_block(*line)) {
+                Some(line) => blank(line),
+                None => true,
+            };
+            let end = match blank_before && end + 1 < lines.len() && blank(end + 1) {
+                true => end + 2,
+                false => end + 1,
+            };
+
+            TextEdit {
+                range: Range::new(
+                    Position::new(*start as u32, 0),
+                    Position::new(end as u32, 0),
+                ),
+                new_text: String::new(),
+            }
+        });
+
+    // Everything after the last kept line is blank lines and moved definitions, which are replaced with the moved
+    // definitions in order
+    let end = Position::new(
+        lines.len().saturating_sub(1) as u32,
+        lines.last().map_or(0, |line| line.chars().count()) as u32,
+    );
+    let bottom = match last_kept {
+        Some(last_kept) => {
+            let new_text = format!("{eol}{eol}{}{eol}", moved_text.join(eol));
+            let current = lines[last_kept + 1..]
+                .iter()
+                .map(|line| format!("{eol}{line}"))
+                .collect::<String>();
+
+            (!moved_text.is_empty() && new_text != current).then(|| TextEdit {
+                range: Range::new(
+                    Position::new(last_kept as u32, lines[last_kept].chars().count() as u32),
+                    end,
+                ),
+                new_text,
+            })
+        }
+        None => (!moved_text.is_empty()).then(|| TextEdit {
+            range: Range::new(Position::new(0, 0), end),
+            new_text: format!("{}{eol}", moved_text.join(eol)),
+        }),
+    };
+
+    renumbered.chain(removed).chain(bottom).collect()
+}
+
+/// Cycle the checkbox of the list item on the line of `position` to the next of the configured task states, as from
+/// `[ ]` to `[x]`; list items without a checkbox are given one, and other lines are left as they are
+pub fn toggle_task(
+    vault: &Vault,
+    settings: &Settings,
+    path: &Path,
+    position: Position,
+) -> Option<WorkspaceEdit> {
+    let rope = vault.ropes.get(path)?;
+    let line = rope.get_line(position.line as usize)?.to_string();
+
+    let (start, end, new_text) =
+        toggled_task(line.trim_end_matches(['\n', '\r']), &settings.task_states)?;
+
+    Some(WorkspaceEdit {
+        changes: Some(
+            [(
+                Url::from_file_path(path).ok()?,
+                vec![TextEdit {
+                    range: Range::new(
+                        Position::new(position.line, start),
+                        Position::new(position.line, end),
+                    ),
+                    new_text,
+                }],
+            )]
+            .into_iter()
+            .collect(),
+        ),
+        ..Default::default()
+    })
+}
+
+/// The characters of `line` to replace, and their replacement, to move its checkbox to the state after its current one
+/// in `states`, or to the first state if it is in none or has no checkbox
+fn toggled_task(line: &str, states: &[String]) -> Option<(u32, u32, String)> {
+    static LIST_ITEM_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^(?<bullet>\s*(?:[-*+]|\d+[.)])(?: |$))(?:\[(?<state>[^\]])\](?: |$))?")
+            .unwrap()
+    });
+
+    let captures = LIST_ITEM_RE.captures(line)?;
+    let character = |byte: usize| line[..byte].chars().count() as u32;
+    let first = states.first()?;
+
+    match captures.name("state") {
+        Some(state) => {
+            let next = states
+                .iter()
+                .position(|other| other == state.as_str())
+                .and_then(|current| states.get(current + 1))
+                .unwrap_or(first);
+
+            Some((
+                character(state.start()),
+                character(state.end()),
+                next.clone(),
+            ))
+        }
+        None => {
+            let bullet = captures.name("bullet")?;
+            let separator = match bullet.as_str().ends_with(' ') {
+                true => "",
+                false => " ",
+            };
+            let end = character(bullet.end());
+
+            Some((end, end, format!("{separator}[{first}] ")))
+        }
+    }
+}
+
+/// Collect the open tasks of the vault in the agenda note, grouped by note and then by tag, each with a link to its
+/// block; tasks without an index are given one. Tasks in code blocks are left out. The content of the agenda note is
+/// replaced. Returns the number of tasks with the edit doing so.
+pub fn build_agenda(vault: &Vault, settings: &Settings) -> Option<(usize, WorkspaceEdit)> {
+    let root_dir = vault.root_dir();
+    let agenda_path = root_dir.join(format!("{}.md", settings.agenda_note));
+    let agenda_uri = Url::from_file_path(&agenda_path).ok()?;
+
+    let notes = open_tasks(vault)
+        .into_iter()
+        .filter_map(|task| Some((task.uri.to_file_path().ok()?, task)))
+        .filter(|(path, _)| *path != agenda_path)
+        .into_group_map()
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .collect_vec();
+
+    let count = notes.iter().map(|(_, tasks)| tasks.len()).sum();
+
+    let mut agenda = format!("# {}\n", agenda_path.file_stem()?.to_string_lossy());
+    let mut index_edits = vec![];
+    let mut tagged: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (path, tasks) in notes {
+        let refname = get_obsidian_ref_path(root_dir, &path)?;
+        let rope = vault.ropes.get(&path)?;
+        let mut edits = vec![];
+
+        agenda.push_str(&format!("\n## [[{refname}]]\n\n"));
+
+        for task in tasks {
+            // An index is added at the end of the line of a task without one
+            let line = rope.get_line(task.line as usize)?.to_string();
+            let end = Position::new(
+                task.line,
+                line.trim_end_matches(['\n', '\r']).chars().count() as u32,
+            );
+
+            let index = task.index.unwrap_or_else(|| {
+                let index = nanoid!(
+                    5,
+                    &[
+                        'a', 'b', 'c', 'd', 'e', 'f', 'g', '1', '2', '3', '4', '5', '6', '7', '8',
+                        '9'
+                    ]
+                );
+
+                edits.push(OneOf::Left(TextEdit {
+                    range: Range::new(end, end),
+                    new_text: format!(" ^{index}"),
+                }));
+
+                index
+            });
+
+            let entry = format!("- {} [[{refname}#^{index}]]\n", task.text);
+            for tag in task.tags {
+                tagged.entry(tag).or_default().push(entry.clone());
+            }
+
+            agenda.push_str(&entry);
+        }
+
+        if !edits.is_empty() {
+            index_edits.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: Url::from_file_path(&path).ok()?,
+                    version: None,
+                },
+                edits,
+            }));
+        }
+    }
+
+    for (tag, entries) in tagged {
+        agenda.push_str(&format!("\n## {tag}\n\n{}", entries.concat()));
+    }
+
+    // The agenda is rewritten as a whole, so the tasks completed since it was last built are left out
+    let (create, replaced) = match vault.ropes.get(&agenda_path) {
+        Some(rope) => (
+            None,
+            Range::new(
+                Position::new(0, 0),
+                Position::new(rope.len_lines() as u32, 0),
+            ),
+        ),
+        None => (
+            Some(DocumentChangeOperation::Op(ResourceOp::Create(
+                CreateFile {
+                    uri: agenda_uri.clone(),
+                    options: None,
+                    annotation_id: None,
+                },
+            ))),
+            Range::default(),
+        ),
+    };
+
+    let agenda_edit = DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: agenda_uri,
+            version: None,
+        },
+        edits: vec![OneOf::Left(TextEdit {
+            range: replaced,
+            new_text: agenda,
+        })],
+    });
+
+    Some((
+        count,
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(
+                create
+                    .into_iter()
+                    .chain([agenda_edit])
+                    .chain(index_edits)
+                    .collect(),
+            )),
+            ..Default::default()
+        },
+    ))
+}
+
+async fn show_note(
+    client: &tower_lsp::Client,
+    settings: &Settings,
+    uri: Url,
+) -> Result<Option<Value>> {
+    // file creation can fail and return an Err, ignore this and try
+    // to open the file on the off chance the client knows what to do
+    // TODO: log failure to create file
+    let _ = uri.to_file_path().map(|path| {
+        path.parent().map(|parent| std::fs::create_dir_all(parent));
+
+        // only a newly created note is seeded with frontmatter
+        if let Ok(mut file) = File::create_new(path.as_path().to_owned()) {
+            let title = path.file_stem().unwrap_or_default().to_string_lossy();
+
+            if let Some(frontmatter) = settings.frontmatter_scaffold(&title) {
+                let _ = file.write_all(frontmatter.as_bytes());
+            }
+        }
+    });
+
+    client
+        .show_document(ShowDocumentParams {
+            uri,
+            external: Some(false),
+            take_focus: Some(true),
+            selection: None,
+        })
+        .await
+        .map(|success| Some(success.into()))
+}
+
 // tests
 #[cfg(test)]
 mod tests {
+    use std::path::PathBuf;
+
     use fuzzydate::parse;
 
-    use super::datetime_to_file;
+    use chrono::NaiveDate;
+    use itertools::Itertools;
+    use ropey::Rope;
+    use tower_lsp::lsp_types::{
+        DocumentChangeOperation, DocumentChanges, OneOf, Position, Range, ResourceOp, WorkspaceEdit,
+    };
+
+    use crate::config::{LinkStyle, Settings};
+    use crate::vault::{test_settings, Vault};
+
+    use super::{
+        backlink_sources, broken_anchor_fixes, broken_links, build_agenda, datetime_to_file,
+        filled_template, fixed_link_text, normalize_daily_notes, renumber_footnotes, toggle_task,
+        toggled_task,
+    };
+
+    fn root_dir() -> PathBuf {
+        test_settings().0
+    }
+
+    fn settings() -> Settings {
+        let (_, mut settings) = test_settings();
+        settings.default_link_style = LinkStyle::Wiki;
+        settings.include_md_extension_wikilink = false;
+        settings
+    }
+
+    fn vault_of(settings: &Settings, notes: &[(&str, &str)]) -> Vault {
+        Vault::of_notes(
+            settings,
+            &root_dir(),
+            notes
+                .iter()
+                .map(|(name, text)| (root_dir().join(format!("{name}.md")), *text)),
+        )
+    }
+
+    /// The text edits of a workspace edit by the name of their note, in the order they are applied
+    fn text_edits(edit: WorkspaceEdit) -> Vec<(String, Range, String)> {
+        let name = |uri: &tower_lsp::lsp_types::Url| {
+            let path = uri.to_file_path().unwrap();
+            path.file_stem().unwrap().to_string_lossy().to_string()
+        };
+
+        let changes = edit.changes.into_iter().flatten().flat_map(|(uri, edits)| {
+            edits
+                .into_iter()
+                .map(move |edit| (name(&uri), edit.range, edit.new_text))
+        });
+
+        let operations = match edit.document_changes {
+            Some(DocumentChanges::Operations(operations)) => operations,
+            _ => vec![],
+        };
+        let document_changes = operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some(edit),
+                _ => None,
+            })
+            .flat_map(|edit| {
+                let name = name(&edit.text_document.uri);
+                edit.edits.into_iter().map(move |edit| match edit {
+                    OneOf::Left(edit) => (name.clone(), edit.range, edit.new_text),
+                    OneOf::Right(edit) => {
+                        (name.clone(), edit.text_edit.range, edit.text_edit.new_text)
+                    }
+                })
+            });
+
+        changes.chain(document_changes).collect()
+    }
+
+    #[test]
+    fn broken_links_of_vault() {
+        let settings = settings();
+        let vault = vault_of(
+            &settings,
+            &[
+                ("b", "# Yes\n[[a]] [[missing]]"),
+                ("a", "[[b#Yes]]\n[[b#No]]"),
+            ],
+        );
+
+        let locations = broken_links(&vault)
+            .into_iter()
+            .map(|location| (location.uri.to_file_path().unwrap(), location.range.start))
+            .collect_vec();
+
+        assert_eq!(
+            locations,
+            vec![
+                (root_dir().join("a.md"), Position::new(1, 0)),
+                (root_dir().join("b.md"), Position::new(1, 6)),
+            ]
+        );
+    }
+
+    #[test]
+    fn backlink_sources_of_note() {
+        let settings = settings();
+        let vault = vault_of(
+            &settings,
+            &[
+                ("a", "# A\n[[a]]"),
+                ("c", "[[a#A]]"),
+                ("b", "[[a]]"),
+                ("d", "[[other]]"),
+            ],
+        );
+        let position = Position::new(1, 2);
+
+        let sources = backlink_sources(&vault, &settings, &root_dir().join("a.md"), position)
+            .unwrap()
+            .into_iter()
+            .map(|source| (source.note, text_edits(source.edit)))
+            .collect_vec();
+
+        let insert = |link: &str| {
+            vec![(
+                "a".to_string(),
+                Range::new(position, position),
+                link.to_string(),
+            )]
+        };
+        assert_eq!(
+            sources,
+            vec![
+                ("b".to_string(), insert("[[b]]")),
+                ("c".to_string(), insert("[[c]]")),
+            ]
+        );
+    }
+
+    #[test]
+    fn renumber_footnotes_of_note() {
+        let settings = settings();
+        let vault = vault_of(&settings, &[("a", "B[^2] a[^1]\n\n[^1]: One\n[^2]: Two\n")]);
+
+        let edits = text_edits(renumber_footnotes(&vault, &root_dir().join("a.md")).unwrap());
+
+        let edit = |start: (u32, u32), end: (u32, u32), new_text: &str| {
+            (
+                "a".to_string(),
+                Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+                new_text.to_string(),
+            )
+        };
+        assert_eq!(
+            edits,
+            vec![
+                edit((0, 1), (0, 5), "[^1]"),
+                edit((0, 7), (0, 11), "[^2]"),
+                edit((0, 11), (4, 0), "\n\n[^1]: Two\n[^2]: One\n"),
+            ]
+        );
+    }
+
+    /// The text of a note after renumbering its footnotes
+    fn renumbered_footnotes(text: &str) -> String {
+        let settings = settings();
+        let vault = vault_of(&settings, &[("a", text)]);
+        let edits = text_edits(renumber_footnotes(&vault, &root_dir().join("a.md")).unwrap());
+
+        let mut rope = Rope::from_str(text);
+        for (_, range, new_text) in edits
+            .into_iter()
+            .sorted_by_key(|(_, range, _)| range.start)
+            .rev()
+        {
+            let char = |position: Position| {
+                rope.line_to_char(position.line as usize) + position.character as usize
+            };
+            let (start, end) = (char(range.start), char(range.end));
+
+            rope.remove(start..end);
+            rope.insert(start, &new_text);
+        }
+
+        rope.to_string()
+    }
+
+    #[test]
+    fn toggle_task_on_line() {
+        let settings = settings();
+        let vault = vault_of(&settings, &[("a", "- [ ] Call\n- Plain\nText")]);
+        let toggle = |line| {
+            toggle_task(
+                &vault,
+                &settings,
+                &root_dir().join("a.md"),
+                Position::new(line, 4),
+            )
+            .map(text_edits)
+        };
+
+        assert_eq!(
+            toggle(0),
+            Some(vec![(
+                "a".to_string(),
+                Range::new(Position::new(0, 3), Position::new(0, 4)),
+                "x".to_string()
+            )])
+        );
+        assert_eq!(
+            toggle(1),
+            Some(vec![(
+                "a".to_string(),
+                Range::new(Position::new(1, 2), Position::new(1, 2)),
+                "[ ] ".to_string()
+            )])
+        );
+        assert_eq!(toggle(2), None);
+    }
+
+    #[test]
+    fn agenda_of_open_tasks() {
+        let mut settings = settings();
+        settings.agenda_note = "Agenda".to_string();
+        let vault = vault_of(
+            &settings,
+            &[
+                (
+                    "b",
+                    "- [ ] Write #work ^abc12\n- [x] Done\n```\n- [ ] Code\n```",
+                ),
+                ("a", "Text\n- [ ] Call #work #home\n1. [ ] Ordered ^d4e5f"),
+                ("Agenda", "# Agenda\n- [ ] Old"),
+            ],
+        );
+
+        let (count, edit) = build_agenda(&vault, &settings).unwrap();
+        assert_eq!(count, 3);
+
+        let edits = text_edits(edit);
+        let [(_, _, index_text)] = &edits[1..] else {
+            panic!("expected one index edit: {edits:?}")
+        };
+        let index = index_text.trim_start_matches(" ^");
+
+        assert_eq!(
+            edits,
+            vec![
+                (
+                    "Agenda".to_string(),
+                    Range::new(Position::new(0, 0), Position::new(2, 0)),
+                    format!(
+                        "# Agenda\n\n## [[a]]\n\n- {call}\n- Ordered [[a#^d4e5f]]\n\n## [[b]]\n\n- {write}\n\n## #home\n\n- {call}\n\n## #work\n\n- {call}\n- {write}\n",
+                        call = format!("Call #work #home [[a#^{index}]]"),
+                        write = "Write #work [[b#^abc12]]",
+                    )
+                ),
+                (
+                    "a".to_string(),
+                    Range::new(Position::new(1, 22), Position::new(1, 22)),
+                    format!(" ^{index}")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_anchor_fixes_of_note() {
+        let settings = settings();
+        let vault = vault_of(
+            &settings,
+            &[
+                ("a", "[[b#Instal|install]] [[b#Setup Guide]]"),
+                ("b", "# Installation\n# Setup Guide"),
+            ],
+        );
+
+        let fixes = broken_anchor_fixes(&vault, &root_dir().join("a.md"))
+            .into_iter()
+            .map(|fix| (fix.range, fix.reference_text, fix.heading, fix.new_text))
+            .collect_vec();
+
+        assert_eq!(
+            fixes,
+            vec![(
+                Range::new(Position::new(0, 0), Position::new(0, 20)),
+                "b#Instal".to_string(),
+                "Installation".to_string(),
+                "[[b#Installation|install]]".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn normalize_daily_note_names() {
+        let mut settings = settings();
+        settings.dailynote = "%Y-%m-%d".to_string();
+        let vault = vault_of(
+            &settings,
+            &[("2023_1_2", ""), ("2023-01-03", ""), ("a", "[[2023_1_2]]")],
+        );
+
+        let (renamed, edit) = normalize_daily_notes(&vault, &settings);
+        assert_eq!(renamed, 1);
+
+        let Some(DocumentChanges::Operations(operations)) = &edit.document_changes else {
+            panic!("no document changes")
+        };
+        assert!(matches!(
+            operations.last(),
+            Some(DocumentChangeOperation::Op(ResourceOp::Rename(rename)))
+                if rename.new_uri.to_file_path().unwrap() == root_dir().join("2023-01-02.md")
+        ));
+        assert_eq!(
+            text_edits(edit)
+                .into_iter()
+                .map(|(name, _, new_text)| (name, new_text))
+                .collect_vec(),
+            vec![("a".to_string(), "[[2023-01-02]]".to_string())]
+        );
+    }
 
     #[test]
     fn test_string_to_file() {
@@ -94,4 +1197,100 @@ mod tests {
         )
         .unwrap();
     }
+
+    #[test]
+    fn fixed_anchor_link_text() {
+        assert_eq!(
+            fixed_link_text("[[Note#Instal]]", "Instal", "Installation"),
+            Some("[[Note#Installation]]".to_string())
+        );
+        assert_eq!(
+            fixed_link_text("[setup](Note#Old%20Setup)", "Old%20Setup", "Setup%20Guide"),
+            Some("[setup](Note#Setup%20Guide)".to_string())
+        );
+        assert_eq!(
+            fixed_link_text("[[Note#Usage#Opts]]", "Opts", "Options"),
+            Some("[[Note#Usage#Options]]".to_string())
+        );
+    }
+
+    #[test]
+    fn renumber_footnotes_in_reference_order() {
+        let text = "Second[^2] and first[^1], again[^2].\n\n[^1]: One  \n[^note]: Named[^2]\n\nMore[^note]\n[^2]: Two\n[^2]: Duplicate\n";
+
+        assert_eq!(
+            renumbered_footnotes(text),
+            "Second[^1] and first[^2], again[^1].\n\nMore[^note]\n\n[^1]: Two\n[^2]: One  \n[^note]: Named[^1]\n"
+        );
+    }
+
+    #[test]
+    fn renumber_footnotes_keeping_line_endings() {
+        assert_eq!(
+            renumbered_footnotes("B[^2] a[^1]\r\n\r\n[^1]: One\r\n[^2]: Two\r\n"),
+            "B[^1] a[^2]\r\n\r\n[^1]: Two\r\n[^2]: One\r\n"
+        );
+    }
+
+    #[test]
+    fn renumber_footnotes_outside_code() {
+        assert_eq!(
+            renumbered_footnotes(
+                "A[^2] `x[^2]`\n\n```\nB[^2] and C[^1]\n[^1]: Code\n```\n\n[^2]: Two\n"
+            ),
+            "A[^1] `x[^2]`\n\n```\nB[^2] and C[^1]\n[^1]: Code\n```\n\n[^1]: Two\n"
+        );
+    }
+
+    #[test]
+    fn renumber_multi_line_footnotes() {
+        assert_eq!(
+            renumbered_footnotes(
+                "[^1]: First\n    continued\n\n    more\n\nText[^2] and[^1]\n\n[^2]: Two\n"
+            ),
+            "Text[^1] and[^2]\n\n[^1]: Two\n[^2]: First\n    continued\n\n    more\n"
+        );
+    }
+
+    #[test]
+    fn toggle_task_states() {
+        let states = [" ", "/", "x"].map(String::from);
+
+        assert_eq!(
+            toggled_task("- [ ] Call Alice", &states),
+            Some((3, 4, "/".to_string()))
+        );
+        assert_eq!(
+            toggled_task("  1. [x] Done", &states),
+            Some((6, 7, " ".to_string()))
+        );
+        assert_eq!(
+            toggled_task("* [?] Unknown state", &states),
+            Some((3, 4, " ".to_string()))
+        );
+        assert_eq!(
+            toggled_task("- Plain item", &states),
+            Some((2, 2, "[ ] ".to_string()))
+        );
+        assert_eq!(
+            toggled_task("-", &states),
+            Some((1, 1, " [ ] ".to_string()))
+        );
+        assert_eq!(toggled_task("Not a list item", &states), None);
+        assert_eq!(toggled_task("-not a bullet", &states), None);
+    }
+
+    #[test]
+    fn daily_note_template() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+
+        assert_eq!(
+            filled_template("# {{title}}\n\nCreated {{date}}\n", "2024-03-05", date),
+            "# 2024-03-05\n\nCreated 2024-03-05\n"
+        );
+        assert_eq!(
+            filled_template("{{date:%A, %B %-d}} {{date:%Q}}", "2024-03-05", date),
+            "Tuesday, March 5 {{date:%Q}}"
+        );
+    }
 }
@@ -3,6 +3,7 @@ use std::path::Path;
 use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation};
 
 use crate::{
+    config::Settings,
     ui::preview_referenceable,
     vault::{MDFootnote, Preview, Referenceable, Vault},
 };
@@ -14,6 +15,7 @@ use rayon::prelude::*;
 pub struct FootnoteCompleter<'a> {
     vault: &'a Vault,
     path: &'a Path,
+    settings: &'a Settings,
 }
 
 impl<'a> Completer<'a> for FootnoteCompleter<'a> {
@@ -29,6 +31,7 @@ impl<'a> Completer<'a> for FootnoteCompleter<'a> {
             == Some(&['['])
         {
             Some(FootnoteCompleter {
+                settings: context.settings,
                 path: context.path,
                 vault: context.vault,
             })
@@ -90,8 +93,12 @@ impl<'a> Completable<'a, FootnoteCompleter<'a>> for FootnoteCompletion<'a> {
         Some(CompletionItem {
             label: refname.to_string(),
             kind: Some(CompletionItemKind::REFERENCE),
-            documentation: preview_referenceable(completer.vault, &self_referenceable)
-                .map(Documentation::MarkupContent),
+            documentation: preview_referenceable(
+                completer.vault,
+                &self_referenceable,
+                completer.settings,
+            )
+            .map(Documentation::MarkupContent),
             filter_text: Some(completer.completion_filter_text((refname, self_referenceable))),
             ..Default::default()
         })
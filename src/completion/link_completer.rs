@@ -18,8 +18,11 @@ use tower_lsp::lsp_types::{
 use crate::{
     completion::util::check_in_code_block,
     config::Settings,
-    ui::preview_referenceable,
-    vault::{MDFile, MDHeading, Reference, Referenceable, Vault},
+    daily::{relative_date_name, try_format, RELATIVE_PERIODS},
+    ui::{preview_referenceable, truncated},
+    vault::{
+        encode_link_path, heading_parents, MDFile, MDHeading, Reference, Referenceable, Vault,
+    },
 };
 
 use super::{
@@ -91,6 +94,11 @@ pub trait LinkCompleter<'a>: Completer<'a> {
         let completions = referenceables
             .into_par_iter()
             .filter(|referenceable| Some(referenceable) != single_unresolved_under_cursor.as_ref())
+            .filter(|referenceable| {
+                !self
+                    .settings()
+                    .excluded_from_completion(self.vault().root_dir(), referenceable.get_path())
+            })
             .filter(|referenceable| {
                 heading_completions
                     || !matches!(
@@ -114,16 +122,100 @@ pub trait LinkCompleter<'a>: Completer<'a> {
 
         // Get daily notes for convienience
         let today = chrono::Local::now().date_naive();
-        let days = (-7..=7)
+        let days = (-self.settings().daily_note_lookback..=self.settings().daily_note_lookahead)
             .flat_map(|i| Some(today + Duration::try_days(i)?))
             .flat_map(|date| MDDailyNote::from_date(date, self))
             .filter(|date| !refnames.contains(&date.ref_name))
-            .map(LinkCompletion::DailyNote);
+            .collect_vec();
+
+        // A date typed in the daily note format may be outside of the week of relative daily notes
+        let typed_day =
+            NaiveDate::parse_from_str(&self.entered_refname(), &self.settings().dailynote)
+                .ok()
+                .and_then(|date| MDDailyNote::from_typed_date(date, self))
+                .filter(|typed| {
+                    !refnames.contains(&typed.ref_name)
+                        && !days.iter().any(|day| day.ref_name == typed.ref_name)
+                });
+
+        // Weekly and monthly notes around the current ones
+        let periods = RELATIVE_PERIODS
+            .iter()
+            .flat_map(|(name, period, offset)| {
+                let ref_name = period.note_name(self.settings(), today, *offset)?;
+
+                Some(MDDailyNote {
+                    match_string: format!("{}: {}", name, ref_name),
+                    ref_name,
+                    real_referenceaable: None,
+                })
+            })
+            .filter(|period| !refnames.contains(&period.ref_name));
+
+        completions
+            .into_iter()
+            .chain(
+                days.into_iter()
+                    .chain(typed_day)
+                    .chain(periods)
+                    .map(LinkCompletion::DailyNote),
+            )
+            .collect::<Vec<_>>()
+    }
+
+    /// Files whose first lines contain the entered text, so that a note can be found by a phrase in it rather than by its name.
+    /// Files in `excluded` (by refname) are skipped.
+    fn content_completions(
+        &self,
+        phrase: &str,
+        excluded: &HashSet<String>,
+    ) -> Vec<LinkCompletion<'a>>
+    where
+        Self: Sync,
+    {
+        if !self.settings().content_completions || phrase.chars().count() < 3 {
+            return vec![];
+        }
+
+        let vault = self.vault();
+        let lowercase_phrase = phrase.to_lowercase();
+
+        vault
+            .md_files
+            .par_iter()
+            .filter(|(path, _)| {
+                path.as_path() != self.path()
+                    && !self
+                        .settings()
+                        .excluded_from_completion(vault.root_dir(), path)
+            })
+            .flat_map(|(path, mdfile)| {
+                if excluded.contains(mdfile.file_name()?) {
+                    return None;
+                }
 
-        completions.into_iter().chain(days).collect::<Vec<_>>()
+                let excerpt = vault
+                    .ropes
+                    .get(path)?
+                    .lines()
+                    .take(CONTENT_COMPLETION_LINES)
+                    .map(|line| line.to_string())
+                    .find(|line| line.to_lowercase().contains(&lowercase_phrase))?;
+
+                Some(Content {
+                    mdfile,
+                    match_string: phrase.to_string(),
+                    excerpt: truncated(excerpt.trim(), 80),
+                    referenceable: Referenceable::File(path, mdfile),
+                })
+            })
+            .collect::<Vec<_>>()
     }
 }
 
+/// Number of lines at the start of each note searched for content completions
+const CONTENT_COMPLETION_LINES: usize = 50;
+
 impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
     fn settings(&self) -> &'a Settings {
         self.settings
@@ -151,7 +243,7 @@ impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
         )
     }
 
-    /// Will add <$1> to the refname if it contains spaces
+    /// Will percent-encode spaces and other characters not allowed in a link destination; the display text is left as is
     fn completion_text_edit(&self, display: Option<&str>, refname: &str) -> CompletionTextEdit {
         let ext = if self.settings().include_md_extension_md_link {
             ".md"
@@ -159,9 +251,14 @@ impl<'a> LinkCompleter<'a> for MarkdownLinkCompleter<'a> {
             ""
         };
 
-        let link_ref_text = match refname.contains(' ') {
-            true => format!("<{}{}>", refname, ext),
-            false => format!("{}{}", refname, ext),
+        let link_ref_text = match refname.split_once('#') {
+            Some((path, infile_ref)) => format!(
+                "{}{}#{}",
+                encode_link_path(path),
+                ext,
+                encode_link_path(infile_ref)
+            ),
+            None => format!("{}{}", encode_link_path(refname), ext),
         };
 
         CompletionTextEdit::Edit(TextEdit {
@@ -277,10 +374,21 @@ impl<'a> Completer<'a> for MarkdownLinkCompleter<'a> {
 
         let link_completions = self.link_completions();
 
-        let matches =
-            fuzzy_match_completions(&filter_text, link_completions, &self.settings.case_matching);
+        let matches = fuzzy_match_completions(&filter_text, link_completions, self.settings);
+
+        let content_matches = match self.infile_ref {
+            None => self.content_completions(&filter_text, &matched_refnames(&matches)),
+            Some(_) => vec![],
+        };
 
         matches
+            .into_iter()
+            .chain(
+                content_matches
+                    .into_iter()
+                    .map(|completion| OrderedCompletion::new(completion, "0".to_string())),
+            )
+            .collect()
     }
 
     /// The completions refname
@@ -327,7 +435,8 @@ pub struct WikiLinkCompleter<'a> {
     line: u32,
     context_path: &'a Path,
     settings: &'a Settings,
-    chars_in_line: u32,
+    /// The number of `]` closing the link right after the cursor, which the completion replaces
+    closing_brackets: u32,
 }
 
 impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
@@ -368,7 +477,7 @@ impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
                 },
                 end: Position {
                     line: self.line,
-                    character: (self.chars_in_line - 1).min(self.character + 2_u32), // TODO: in zed, you cannot zed end to be out of the line count index
+                    character: self.character + self.closing_brackets,
                 },
             },
 
@@ -384,6 +493,19 @@ impl<'a> LinkCompleter<'a> for WikiLinkCompleter<'a> {
     }
 }
 
+/// The number of `]` right after the cursor, up to the two closing a wiki link. These are there if the editor paired
+/// the typed `[[`, but not if completion was triggered before it did; the completion adds its own, so only those
+/// present are replaced, and text after the cursor is kept.
+fn closing_brackets(line_chars: &[char], character: usize) -> usize {
+    line_chars
+        .get(character..)
+        .unwrap_or_default()
+        .iter()
+        .take(2)
+        .take_while(|c| **c == ']')
+        .count()
+}
+
 impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
     fn construct(context: Context<'a>, line: usize, character: usize) -> Option<Self>
     where
@@ -426,6 +548,11 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
         index.and_then(|index| {
             let cmp_text = line_chars.get(index + 1..character)?;
 
+            // There are no link targets to complete in the display text: `[[Note#Heading|display`
+            if WikiLinkSegment::of(cmp_text) == WikiLinkSegment::Display {
+                return None;
+            }
+
             Some(WikiLinkCompleter {
                 vault,
                 cmp_text: cmp_text.to_vec(),
@@ -435,7 +562,7 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
                 line: line as u32,
                 context_path: context.path,
                 settings: context.settings,
-                chars_in_line: line_chars.len() as u32,
+                closing_brackets: closing_brackets(&line_chars, character) as u32,
             })
         })
     }
@@ -498,13 +625,56 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
 
                 let link_completions = self.link_completions();
 
+                // Only infile references of the entered file are candidates once the anchor has been started
+                let link_completions = match WikiLinkSegment::of(filter_text) {
+                    WikiLinkSegment::Anchor => {
+                        let anchor_files = self.anchor_files();
+
+                        link_completions
+                            .into_iter()
+                            .filter(|completion| match completion {
+                                Heading { referenceable, .. }
+                                | Block { referenceable, .. }
+                                | Unresolved {
+                                    infile_ref: Some(_),
+                                    referenceable,
+                                    ..
+                                } => anchor_files
+                                    .iter()
+                                    .any(|path| path == referenceable.get_path()),
+                                Unresolved { .. }
+                                | File { .. }
+                                | Alias { .. }
+                                | Content { .. }
+                                | DailyNote(_) => false,
+                            })
+                            .collect()
+                    }
+                    WikiLinkSegment::Target | WikiLinkSegment::Display => link_completions,
+                };
+
                 let matches = fuzzy_match_completions(
                     &String::from_iter(filter_text),
                     link_completions,
-                    &self.settings.case_matching,
+                    self.settings,
                 );
 
+                let content_matches = match WikiLinkSegment::of(filter_text) {
+                    WikiLinkSegment::Target => self.content_completions(
+                        &String::from_iter(filter_text),
+                        &matched_refnames(&matches),
+                    ),
+                    WikiLinkSegment::Anchor | WikiLinkSegment::Display => vec![],
+                };
+
                 matches
+                    .into_iter()
+                    .chain(
+                        content_matches
+                            .into_iter()
+                            .map(|completion| OrderedCompletion::new(completion, "0".to_string())),
+                    )
+                    .collect()
             }
             _ => vec![],
         }
@@ -516,6 +686,47 @@ impl<'a> Completer<'a> for WikiLinkCompleter<'a> {
     }
 }
 
+impl WikiLinkCompleter<'_> {
+    /// The files whose headings and blocks complete the anchor of `[[file#anchor`: every file the entered name may
+    /// refer to, or the current file for `[[#anchor`. Unresolved files are included by the path of their links.
+    fn anchor_files(&self) -> Vec<PathBuf> {
+        let entered_refname = self.entered_refname();
+        let file = entered_refname.split('#').next().unwrap_or_default();
+
+        if file.is_empty() {
+            return vec![self.context_path.to_path_buf()];
+        }
+
+        self.vault
+            .select_file_referenceables(file)
+            .into_iter()
+            .map(|referenceable| referenceable.get_path().to_path_buf())
+            .chain(std::iter::once(self.vault.root_dir().join(file)))
+            .collect()
+    }
+}
+
+/// The segment of a wiki link that the cursor is in: `[[target#anchor|display]]`
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum WikiLinkSegment {
+    Target,
+    Anchor,
+    Display,
+}
+
+impl WikiLinkSegment {
+    /// Determine the segment from the link text between the `[[` and the cursor
+    pub(super) fn of(entered_text: &[char]) -> WikiLinkSegment {
+        if entered_text.contains(&'|') {
+            WikiLinkSegment::Display
+        } else if entered_text.contains(&'#') {
+            WikiLinkSegment::Anchor
+        } else {
+            WikiLinkSegment::Target
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum LinkCompletion<'a> {
     File {
@@ -543,11 +754,33 @@ pub enum LinkCompletion<'a> {
         infile_ref: Option<String>,
         referenceable: Referenceable<'a>,
     },
+    /// A file whose content, rather than its name, contains the entered text
+    Content {
+        mdfile: &'a MDFile,
+        /// The entered text found in the file
+        match_string: String,
+        /// The line of the file containing the match
+        excerpt: String,
+        referenceable: Referenceable<'a>,
+    },
     DailyNote(MDDailyNote<'a>),
 }
 
 use LinkCompletion::*;
 
+/// The refnames of files already offered in `completions`
+fn matched_refnames<'a, C: Completer<'a>>(
+    completions: &[OrderedCompletion<'a, C, LinkCompletion<'a>>],
+) -> HashSet<String>
+where
+    LinkCompletion<'a>: Completable<'a, C>,
+{
+    completions
+        .iter()
+        .map(|completion| completion.completable().refname())
+        .collect()
+}
+
 impl LinkCompletion<'_> {
     fn new<'a>(
         referenceable: Referenceable<'a>,
@@ -576,21 +809,17 @@ impl LinkCompletion<'_> {
                         .collect(),
                     )
                 }
-                Referenceable::Heading(path, mdheading) => Some(
+                Referenceable::Heading(_, mdheading) => Some(
                     once(Heading {
                         heading: mdheading,
-                        match_string: format!(
-                            "{}#{}",
-                            path.file_stem()?.to_str()?,
-                            mdheading.heading_text
-                        ),
+                        match_string: referenceable.display_name()?,
                         referenceable,
                     })
                     .collect(),
                 ),
-                Referenceable::IndexedBlock(path, indexed) => Some(
+                Referenceable::IndexedBlock(..) => Some(
                     once(Block {
-                        match_string: format!("{}#^{}", path.file_stem()?.to_str()?, indexed.index),
+                        match_string: referenceable.display_name()?,
                         referenceable,
                     })
                     .collect(),
@@ -636,16 +865,20 @@ impl LinkCompletion<'_> {
             | Self::Heading { referenceable, .. }
             | Self::Block { referenceable, .. }
             | Self::Unresolved { referenceable, .. }
-            | Self::Alias { referenceable, .. } => referenceable.to_owned(),
+            | Self::Alias { referenceable, .. }
+            | Self::Content { referenceable, .. } => referenceable.to_owned(),
             Self::DailyNote(daily) => daily.referenceable(completer),
         };
 
-        let label = self.match_string();
+        let label = match self {
+            Self::Content { .. } => self.refname(),
+            _ => self.match_string().to_string(),
+        };
 
         CompletionItem {
-            label: label.to_string(),
+            label,
             kind: Some(match self {
-                Self::File { .. } => CompletionItemKind::FILE,
+                Self::File { .. } | Self::Content { .. } => CompletionItemKind::FILE,
                 Self::Heading { .. } | Self::Block { .. } => CompletionItemKind::REFERENCE,
                 Self::Unresolved {
                     match_string: _,
@@ -668,7 +901,23 @@ impl LinkCompletion<'_> {
                     detail: Some(format!("Alias: {}.md", filename)),
                     description: None,
                 }),
+                Content { excerpt, .. } => Some(CompletionItemLabelDetails {
+                    detail: None,
+                    description: Some(excerpt.clone()),
+                }),
                 File { .. } => None,
+                Heading {
+                    heading,
+                    referenceable: Referenceable::Heading(path, _),
+                    ..
+                } if completer.settings().heading_completion_breadcrumbs => {
+                    heading_breadcrumb(vault, path, heading).map(|breadcrumb| {
+                        CompletionItemLabelDetails {
+                            detail: Some(format!(" {}", breadcrumb)),
+                            description: None,
+                        }
+                    })
+                }
                 Heading { .. } => None,
                 Block { .. } => None,
                 DailyNote(_) => None,
@@ -677,16 +926,26 @@ impl LinkCompletion<'_> {
             preselect: Some(match self {
                 Self::DailyNote(daily) => {
                     daily.relative_name(completer) == Some(completer.entered_refname())
+                        || daily.ref_name == completer.entered_refname()
                 }
                 link_completion => link_completion.refname() == completer.entered_refname(),
             }),
             filter_text: Some(filter_text.to_string()),
-            documentation: preview_referenceable(vault, &referenceable)
+            documentation: preview_referenceable(vault, &referenceable, completer.settings())
                 .map(Documentation::MarkupContent),
             ..Default::default()
         }
     }
 
+    /// The text clients filter the completion by; it includes the title of notes, so that those found by their title
+    /// are not filtered out
+    fn filter_string(&self) -> String {
+        match self.alternate_match_string() {
+            Some(alternate) => format!("{} {}", self.match_string(), alternate),
+            None => self.match_string().to_string(),
+        }
+    }
+
     /// Refname to be inserted into the document
     fn refname(&self) -> String {
         match self {
@@ -696,17 +955,30 @@ impl LinkCompletion<'_> {
             | Block { match_string, .. }
             | Unresolved { match_string, .. } => match_string.to_string(),
             Alias { filename, .. } => filename.to_string(),
+            Content { mdfile, .. } => mdfile.file_name().unwrap_or_default().to_string(),
         }
     }
 }
 
+/// The note and the headings containing `heading`, down to the heading itself: `Note > H1 > H2`
+fn heading_breadcrumb(vault: &Vault, path: &Path, heading: &MDHeading) -> Option<String> {
+    let parents = heading_parents(vault.select_headings(path)?, heading)?;
+    let note = path.file_stem()?.to_str()?;
+
+    Some(
+        once(note)
+            .chain(parents.iter().map(|parent| parent.heading_text.as_str()))
+            .chain(once(heading.heading_text.as_str()))
+            .join(" > "),
+    )
+}
+
 impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
     fn completions(
         &self,
         markdown_link_completer: &MarkdownLinkCompleter<'a>,
     ) -> Option<CompletionItem> {
         let refname = self.refname();
-        let match_string = self.match_string();
 
         let display = &markdown_link_completer.display;
 
@@ -718,9 +990,13 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
             }
             | Self::Block {
                 match_string: _, ..
-            } => None,
+            }
+            | Self::Content { .. } => None,
             Self::Alias { match_string, .. } => Some(match_string.to_string()),
-            Self::DailyNote(daily) => daily.relative_name(markdown_link_completer),
+            Self::DailyNote(daily) => daily
+                .title(markdown_link_completer)
+                .map(String::from)
+                .or_else(|| daily.relative_name(markdown_link_completer)),
             Self::Heading {
                 heading,
                 match_string: _,
@@ -738,11 +1014,9 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
             ("", Some(ref infile)) => infile,
             // Get the first heading of the file, if possible.
             ("", None) if markdown_link_completer.settings().title_headings => match self {
-                Self::File { mdfile, .. } => mdfile
-                    .headings
-                    .first()
-                    .map(|heading| heading.heading_text.as_str())
-                    .unwrap_or(""),
+                Self::File { mdfile, .. } | Self::Content { mdfile, .. } => {
+                    mdfile.title().unwrap_or("")
+                }
                 Self::Alias {
                     match_string: alias,
                     ..
@@ -757,7 +1031,7 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
         let text_edit =
             markdown_link_completer.completion_text_edit(Some(&link_display_text), &refname);
 
-        let filter_text = markdown_link_completer.completion_filter_text(match_string); // TODO: abstract into default_completion
+        let filter_text = markdown_link_completer.completion_filter_text(&self.filter_string()); // TODO: abstract into default_completion
 
         Some(CompletionItem {
             insert_text_format: Some(InsertTextFormat::SNIPPET),
@@ -769,20 +1043,32 @@ impl<'a> Completable<'a, MarkdownLinkCompleter<'a>> for LinkCompletion<'a> {
 impl<'a> Completable<'a, WikiLinkCompleter<'a>> for LinkCompletion<'a> {
     fn completions(&self, completer: &WikiLinkCompleter<'a>) -> Option<CompletionItem> {
         let refname = self.refname();
-        let match_text = self.match_string();
+
+        let title = match self {
+            File { mdfile, .. } | Content { mdfile, .. }
+                if completer.settings().title_display_text =>
+            {
+                mdfile.title()
+            }
+            DailyNote(daily) => daily.title(completer),
+            _ => None,
+        };
 
         let wikilink_display_text = match self {
             File { .. } => None,
-            Alias { match_string, .. } => Some(format!("${{1:{}}}", match_string)),
+            Alias { match_string, .. } => Some(*match_string),
             Heading { .. } => None,
             Block { .. } => None,
             Unresolved { .. } => None,
+            Content { .. } => None,
             DailyNote(_) => None,
-        };
+        }
+        .or(title)
+        .map(|display| format!("${{1:{}}}", display));
 
         let text_edit = completer.completion_text_edit(wikilink_display_text.as_deref(), &refname);
 
-        let filter_text = completer.completion_filter_text(match_text);
+        let filter_text = completer.completion_filter_text(&self.filter_string());
 
         Some(CompletionItem {
             insert_text_format: Some(InsertTextFormat::SNIPPET),
@@ -807,10 +1093,19 @@ impl Matchable for LinkCompletion<'_> {
             }
             | Block { match_string, .. }
             | Unresolved { match_string, .. }
+            | Content { match_string, .. }
             | DailyNote(MDDailyNote { match_string, .. }) => match_string,
             Alias { match_string, .. } => match_string,
         }
     }
+
+    /// Notes are also found by their title
+    fn alternate_match_string(&self) -> Option<&str> {
+        match self {
+            File { mdfile, .. } => mdfile.title(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -821,10 +1116,20 @@ pub struct MDDailyNote<'a> {
 }
 
 impl MDDailyNote<'_> {
+    /// The title of the daily note, if it exists and titles are used as display text
+    fn title<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<&str> {
+        match &self.real_referenceaable {
+            Some(Referenceable::File(_, mdfile)) if completer.settings().title_display_text => {
+                mdfile.title()
+            }
+            _ => None,
+        }
+    }
+
     pub fn relative_name<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<String> {
         let self_date = self.get_self_date(completer)?;
 
-        Self::relative_date_string(self_date)
+        relative_date_name(completer.settings(), self_date)
     }
 
     pub fn get_self_date<'a>(&self, completer: &impl LinkCompleter<'a>) -> Option<NaiveDate> {
@@ -833,22 +1138,6 @@ impl MDDailyNote<'_> {
         chrono::NaiveDate::parse_from_str(&self.ref_name, dailynote_format).ok()
     }
 
-    fn relative_date_string(date: NaiveDate) -> Option<String> {
-        let today = chrono::Local::now().date_naive();
-
-        if today == date {
-            Some("today".to_string())
-        } else {
-            match (date - today).num_days() {
-                1 => Some("tomorrow".to_string()),
-                2..=7 => Some(format!("next {}", date.format("%A"))),
-                -1 => Some("yesterday".to_string()),
-                -7..=-1 => Some(format!("last {}", date.format("%A"))),
-                _ => None,
-            }
-        }
-    }
-
     /// The refname used for fuzzy matching a completion - not the actual inserted text
     fn from_referenceable<'a>(
         referenceable: Referenceable<'a>,
@@ -867,7 +1156,7 @@ impl MDDailyNote<'_> {
                     ))
                 })?;
 
-                date.and_then(Self::relative_date_string)
+                date.and_then(|date| relative_date_name(completer.settings(), date))
                     .map(|thing| (filename.clone(), format!("{}: {}", thing, filename)))
             }
             _ => None,
@@ -886,8 +1175,12 @@ impl MDDailyNote<'_> {
         date: NaiveDate,
         completer: &impl LinkCompleter<'a>,
     ) -> Option<MDDailyNote<'a>> {
-        let filerefname = date.format(&completer.settings().dailynote).to_string();
-        let match_string = format!("{}: {}", Self::relative_date_string(date)?, filerefname);
+        let filerefname = try_format(date.format(&completer.settings().dailynote))?;
+        let match_string = format!(
+            "{}: {}",
+            relative_date_name(completer.settings(), date)?,
+            filerefname
+        );
 
         // path on unresolved file is useless
         Some(MDDailyNote {
@@ -897,6 +1190,23 @@ impl MDDailyNote<'_> {
         })
     }
 
+    /// A daily note for a date typed out in full, which need not have a relative name
+    fn from_typed_date<'a>(
+        date: NaiveDate,
+        completer: &impl LinkCompleter<'a>,
+    ) -> Option<MDDailyNote<'a>> {
+        let filerefname = try_format(date.format(&completer.settings().dailynote))?;
+
+        Some(MDDailyNote {
+            match_string: match relative_date_name(completer.settings(), date) {
+                Some(relative) => format!("{}: {}", relative, filerefname),
+                None => filerefname.clone(),
+            },
+            ref_name: filerefname,
+            real_referenceaable: None,
+        })
+    }
+
     /// mock referenceable for kicks
     fn referenceable<'a, 'b>(&'b self, completer: &impl LinkCompleter<'a>) -> Referenceable<'b> {
         if let Some(referencaable) = &self.real_referenceaable {
@@ -911,3 +1221,34 @@ impl MDDailyNote<'_> {
         unresolved_file
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::{closing_brackets, WikiLinkSegment};
+
+    #[test]
+    fn test_wiki_link_segment() {
+        let segment = |text: &str| WikiLinkSegment::of(&text.chars().collect_vec());
+
+        assert_eq!(segment(""), WikiLinkSegment::Target);
+        assert_eq!(segment("Note"), WikiLinkSegment::Target);
+        assert_eq!(segment("Note#Hea"), WikiLinkSegment::Anchor);
+        assert_eq!(segment("Note#^blo"), WikiLinkSegment::Anchor);
+        assert_eq!(segment("Note#Heading|"), WikiLinkSegment::Display);
+        assert_eq!(segment("Note|display #tag"), WikiLinkSegment::Display);
+    }
+
+    #[test]
+    fn test_closing_brackets() {
+        let closing =
+            |line: &str, character| closing_brackets(&line.chars().collect_vec(), character);
+
+        assert_eq!(closing("[[No]]\n", 4), 2);
+        assert_eq!(closing("[[No] and more\n", 4), 1);
+        assert_eq!(closing("[[No more text\n", 4), 0);
+        assert_eq!(closing("[[No", 4), 0);
+        assert_eq!(closing("[[No]]]", 4), 2);
+    }
+}
@@ -1,30 +1,22 @@
-use std::ops::Deref;
+use std::cmp::Reverse;
 
+use itertools::Itertools;
 use nucleo_matcher::{
     pattern::{self, Normalization},
-    Matcher,
+    Config, Matcher, Utf32Str,
 };
 use tower_lsp::lsp_types::CompletionItem;
 
-use crate::config::Case;
+use crate::config::{Case, Settings};
 
 use super::{Completable, Completer};
 
 pub trait Matchable {
     fn match_string(&self) -> &str;
-}
 
-struct NucleoMatchable<T: Matchable>(T);
-impl<T: Matchable> Deref for NucleoMatchable<T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl<T: Matchable> AsRef<str> for NucleoMatchable<T> {
-    fn as_ref(&self) -> &str {
-        self.match_string()
+    /// Another string the item is found by, such as the title of a note; the better match of the two is its score
+    fn alternate_match_string(&self) -> Option<&str> {
+        None
     }
 }
 
@@ -50,6 +42,12 @@ impl<'a, C: Completer<'a>, T: Completable<'a, C>> OrderedCompletion<'a, C, T> {
     }
 }
 
+impl<'a, C: Completer<'a>, T: Completable<'a, C>> OrderedCompletion<'a, C, T> {
+    pub fn completable(&self) -> &T {
+        &self.completable
+    }
+}
+
 impl<'a, C: Completer<'a>, T: Completable<'a, C>> Completable<'a, C>
     for OrderedCompletion<'a, C, T>
 {
@@ -63,12 +61,19 @@ impl<'a, C: Completer<'a>, T: Completable<'a, C>> Completable<'a, C>
     }
 }
 
+/// Fuzzy match completions with the matcher options of the settings; they are read for each completion, so changed
+/// settings apply to the next one
 pub fn fuzzy_match_completions<'a, 'b, C: Completer<'a>, T: Matchable + Completable<'a, C>>(
     filter_text: &'b str,
     items: impl IntoIterator<Item = T>,
-    case: &Case,
+    settings: &Settings,
 ) -> Vec<OrderedCompletion<'a, C, T>> {
-    let normal_fuzzy_match = fuzzy_match(filter_text, items, case);
+    let normal_fuzzy_match = fuzzy_match_with(
+        filter_text,
+        items,
+        &settings.case_matching,
+        completion_matcher_config(settings),
+    );
 
     normal_fuzzy_match
         .into_iter()
@@ -76,15 +81,35 @@ pub fn fuzzy_match_completions<'a, 'b, C: Completer<'a>, T: Matchable + Completa
         .collect::<Vec<_>>()
 }
 
-pub fn fuzzy_match<'a, T: Matchable>(
+/// The matcher configuration of completions: `completion_prefer_prefix` favors matches near the start of the name, and
+/// `completion_match_paths` scores folder boundaries as fzf does for file paths
+fn completion_matcher_config(settings: &Settings) -> Config {
+    let mut config = Config::DEFAULT;
+
+    if settings.completion_match_paths {
+        config.set_match_paths();
+    }
+    config.prefer_prefix = settings.completion_prefer_prefix;
+
+    config
+}
+
+pub fn fuzzy_match<T: Matchable>(
     filter_text: &str,
     items: impl IntoIterator<Item = T>,
     case: &Case,
 ) -> Vec<(T, u32)> {
-    let items = items.into_iter().map(NucleoMatchable);
+    fuzzy_match_with(filter_text, items, case, Config::DEFAULT)
+}
 
-    let mut matcher = Matcher::new(nucleo_matcher::Config::DEFAULT);
-    let matches = pattern::Pattern::parse(
+fn fuzzy_match_with<T: Matchable>(
+    filter_text: &str,
+    items: impl IntoIterator<Item = T>,
+    case: &Case,
+    config: Config,
+) -> Vec<(T, u32)> {
+    let mut matcher = Matcher::new(config);
+    let pattern = pattern::Pattern::parse(
         filter_text,
         match case {
             Case::Smart => pattern::CaseMatching::Smart,
@@ -92,11 +117,77 @@ pub fn fuzzy_match<'a, T: Matchable>(
             Case::Respect => pattern::CaseMatching::Respect,
         },
         Normalization::Smart,
-    )
-    .match_list(items, &mut matcher);
+    );
 
-    matches
+    let mut buf = Vec::new();
+
+    items
         .into_iter()
-        .map(|(item, score)| (item.0, score))
+        .filter_map(|item| {
+            let score = std::iter::once(item.match_string())
+                .chain(item.alternate_match_string())
+                .filter_map(|string| pattern.score(Utf32Str::new(string, &mut buf), &mut matcher))
+                .max()?;
+
+            Some((item, score))
+        })
+        .sorted_by_key(|(_, score)| Reverse(*score))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Case;
+
+    use super::{fuzzy_match, Matchable};
+
+    struct Note(&'static str, Option<&'static str>);
+
+    impl Matchable for &Note {
+        fn match_string(&self) -> &str {
+            self.0
+        }
+
+        fn alternate_match_string(&self) -> Option<&str> {
+            self.1
+        }
+    }
+
+    #[test]
+    fn matches_name_or_title() {
+        let notes = [
+            Note("2024-03-05", Some("Quarterly planning")),
+            Note("quarterly-review", None),
+            Note("Groceries", Some("Shopping list")),
+        ];
+
+        let matched = |filter_text| {
+            fuzzy_match(filter_text, &notes, &Case::Smart)
+                .into_iter()
+                .map(|(note, _)| note.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(matched("planning"), vec!["2024-03-05"]);
+        assert_eq!(matched("groceries"), vec!["Groceries"]);
+        assert_eq!(matched("quarterly").len(), 2);
+    }
+
+    #[test]
+    fn matches_partial_relative_dates() {
+        // Daily note completions are matched by their relative name and inserted by their date
+        let notes = [
+            Note("next friday: 2024-05-17", None),
+            Note("next saturday: 2024-05-18", None),
+            Note("last friday: 2024-05-10", None),
+            Note("2024-05-17", None),
+        ];
+
+        let matched = fuzzy_match("next fr", &notes, &Case::Smart)
+            .into_iter()
+            .map(|(note, _)| note.0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(matched, vec!["next friday: 2024-05-17"]);
+    }
+}
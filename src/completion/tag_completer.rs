@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{iter, path::Path};
 
 use itertools::Itertools;
 use once_cell::sync::Lazy;
@@ -15,6 +15,7 @@ use crate::{
 };
 
 use super::{
+    link_completer::WikiLinkSegment,
     matcher::{fuzzy_match_completions, Matchable},
     Completable, Completer, Context, LineRange,
 };
@@ -25,6 +26,8 @@ pub struct TagCompleter<'a> {
     full_range: LineRange<usize>,
     /// Tag name and range not including the '#'
     inputted_tag: (String, LineRange<usize>),
+    /// Whether the tag is already followed by a space, so that completing it should not add another
+    followed_by_space: bool,
     vault: &'a Vault,
     line: usize,
     character: usize,
@@ -54,6 +57,10 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
             .flat_map(|captures| {
                 let (full, tag_text) = (captures.get(0)?, captures.name("text")?);
 
+                if !is_bare_tag(&line_string, full.start()) {
+                    return None;
+                }
+
                 // check if the cursor is in the tag
                 let preceding_character = character - 1; // User is inserting into the position after the character they are looking at; "#tag|"  cursor is a position 4; I want pos 3; the end of the tag
                 if preceding_character >= full.range().start
@@ -63,6 +70,7 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
                     Some(TagCompleter {
                         full_range: full.range(),
                         inputted_tag: (tag_text.as_str().to_string(), tag_text.range()),
+                        followed_by_space: line_string[full.end()..].starts_with([' ', '\t']),
                         vault: context.vault,
                         line,
                         character,
@@ -85,10 +93,10 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
             .into_par_iter()
             .flat_map(TagCompletable::from_referenceable)
             .filter(|tag| {
-                !(tag.tag.1.range.start.line <= self.line as u32
-                    && tag.tag.1.range.start.character <= self.character as u32
-                    && tag.tag.1.range.end.line >= self.line as u32
-                    && tag.tag.1.range.end.character >= self.character as u32)
+                !(tag.tag.range.start.line <= self.line as u32
+                    && tag.tag.range.start.character <= self.character as u32
+                    && tag.tag.range.end.line >= self.line as u32
+                    && tag.tag.range.end.character >= self.character as u32)
             })
             .collect::<Vec<_>>();
 
@@ -100,11 +108,8 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
 
         let filter_text = &self.inputted_tag.0;
 
-        let filtered = fuzzy_match_completions(
-            filter_text,
-            tag_referenceables,
-            &self.context.settings.case_matching,
-        );
+        let filtered =
+            fuzzy_match_completions(filter_text, tag_referenceables, self.context.settings);
 
         filtered
     }
@@ -116,29 +121,68 @@ impl<'a> Completer<'a> for TagCompleter<'a> {
     }
 }
 
+/// Whether the `#` at `start` in `line` begins a tag: it must start a word, and not be in a link, where it begins a
+/// heading as in `[[file#heading]]`
+fn is_bare_tag(line: &str, start: usize) -> bool {
+    let before = &line[..start];
+
+    let starts_word = before.is_empty() || before.ends_with(char::is_whitespace);
+    // Tags may be written in the display text of a wiki link, as in `[[Note|some #tag]]`, but not in its target
+    let in_wiki_link_target = before
+        .rfind("[[")
+        .filter(|&link_start| before.rfind("]]") < Some(link_start))
+        .is_some_and(|link_start| {
+            WikiLinkSegment::of(&before[link_start + 2..].chars().collect_vec())
+                != WikiLinkSegment::Display
+        });
+    let in_md_link = before.rfind("](") > before.rfind(')');
+
+    starts_word && !in_wiki_link_target && !in_md_link
+}
+
 struct TagCompletable<'a> {
-    tag: (&'a Path, &'a MDTag),
+    path: &'a Path,
+    /// The tag, or one of its parents; a parent has the range of the tag it is part of
+    tag: MDTag,
 }
 
 impl TagCompletable<'_> {
-    fn from_referenceable(referenceable: Referenceable<'_>) -> Option<TagCompletable<'_>> {
+    /// The tag and each of its parent tags, as in `a` and `a/b` for `a/b/c`
+    fn from_referenceable(referenceable: Referenceable<'_>) -> Vec<TagCompletable<'_>> {
         match referenceable {
-            Referenceable::Tag(path, tag) => Some(TagCompletable { tag: (path, tag) }),
-            _ => None,
+            Referenceable::Tag(path, tag) => tag
+                .tag_ref
+                .match_indices('/')
+                .map(|(i, _)| &tag.tag_ref[..i])
+                .chain(iter::once(tag.tag_ref.as_str()))
+                .map(|tag_ref| TagCompletable {
+                    path,
+                    tag: MDTag {
+                        tag_ref: tag_ref.to_string(),
+                        range: tag.range,
+                    },
+                })
+                .collect(),
+            _ => vec![],
         }
     }
 }
 
 impl Matchable for TagCompletable<'_> {
     fn match_string(&self) -> &str {
-        &self.tag.1.tag_ref
+        &self.tag.tag_ref
     }
 }
 
 impl<'a> Completable<'a, TagCompleter<'a>> for TagCompletable<'a> {
     fn completions(&self, completer: &TagCompleter<'a>) -> Option<CompletionItem> {
+        let trailing_space = match completer.context.settings.tag_completion_trailing_space {
+            true if !completer.followed_by_space => " ",
+            _ => "",
+        };
+
         let text_edit = CompletionTextEdit::Edit(TextEdit {
-            new_text: format!("#{}", self.tag.1.tag_ref),
+            new_text: format!("#{}{}", self.tag.tag_ref, trailing_space),
             range: Range {
                 start: Position {
                     line: completer.line as u32,
@@ -151,9 +195,8 @@ impl<'a> Completable<'a, TagCompleter<'a>> for TagCompletable<'a> {
             },
         });
 
-        let path = self.tag.0;
-        let path_buf = path.to_path_buf();
-        let self_as_referenceable = Referenceable::Tag(&path_buf, self.tag.1);
+        let path_buf = self.path.to_path_buf();
+        let self_as_referenceable = Referenceable::Tag(&path_buf, &self.tag);
 
         let num_references = completer
             .vault
@@ -162,11 +205,15 @@ impl<'a> Completable<'a, TagCompleter<'a>> for TagCompletable<'a> {
             .unwrap_or(0);
 
         Some(CompletionItem {
-            label: self.tag.1.tag_ref.clone(),
+            label: self.tag.tag_ref.clone(),
             kind: Some(CompletionItemKind::KEYWORD),
-            filter_text: Some(completer.completion_filter_text(&self.tag.1.tag_ref.clone())),
-            documentation: ui::preview_referenceable(completer.vault, &self_as_referenceable)
-                .map(Documentation::MarkupContent),
+            filter_text: Some(completer.completion_filter_text(&self.tag.tag_ref.clone())),
+            documentation: ui::preview_referenceable(
+                completer.vault,
+                &self_as_referenceable,
+                completer.context.settings,
+            )
+            .map(Documentation::MarkupContent),
             label_details: Some(CompletionItemLabelDetails {
                 detail: Some(match num_references {
                     1 => "1 reference".to_string(),
@@ -179,3 +226,22 @@ impl<'a> Completable<'a, TagCompleter<'a>> for TagCompletable<'a> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_bare_tag;
+
+    #[test]
+    fn bare_tag_context() {
+        assert!(is_bare_tag("#tag", 0));
+        assert!(is_bare_tag("text #tag", 5));
+        assert!(is_bare_tag("[[link]] #tag", 9));
+        assert!(!is_bare_tag("issue#3", 5));
+        assert!(!is_bare_tag("[[file#", 6));
+        assert!(!is_bare_tag("[[my file #", 10));
+        assert!(!is_bare_tag("[text](file #", 12));
+        assert!(is_bare_tag("[[Note|some #", 12));
+        assert!(is_bare_tag("[[Note#Heading|some #", 20));
+        assert!(is_bare_tag("[some #", 6));
+    }
+}
@@ -89,7 +89,7 @@ impl<'a> Completer<'a> for UnindexedBlockCompleter<'a, MarkdownLinkCompleter<'a>
         let matches = fuzzy_match_completions(
             &grep_match_text,
             completables,
-            &self.link_completer.settings().case_matching,
+            self.link_completer.settings(),
         );
 
         matches
@@ -117,11 +117,8 @@ impl<'a> Completer<'a> for UnindexedBlockCompleter<'a, WikiLinkCompleter<'a>> {
     {
         let completables = self.completables();
         let filter_text = self.grep_match_text();
-        let matches = fuzzy_match_completions(
-            &filter_text,
-            completables,
-            &self.link_completer.settings().case_matching,
-        );
+        let matches =
+            fuzzy_match_completions(&filter_text, completables, self.link_completer.settings());
 
         matches
     }
@@ -167,8 +164,12 @@ impl<'a> UnindexedBlock<'a> {
                 _ => false,
             }) {
             Some(ref referenceable @ Referenceable::IndexedBlock(_, indexed_block)) => (
-                preview_referenceable(completer.link_completer.vault(), referenceable)
-                    .map(Documentation::MarkupContent),
+                preview_referenceable(
+                    completer.link_completer.vault(),
+                    referenceable,
+                    completer.link_completer.settings(),
+                )
+                .map(Documentation::MarkupContent),
                 None,
                 CompletionItemKind::REFERENCE,
                 Some(CompletionItemLabelDetails {
@@ -5,7 +5,10 @@ use config::{Config, File};
 use indexmap::IndexMap;
 use serde::Deserialize;
 use serde_json::Value;
-use tower_lsp::lsp_types::ClientCapabilities;
+use tower_lsp::lsp_types::{ClientCapabilities, DiagnosticSeverity};
+
+use crate::daily::format_is_valid;
+use crate::vault::encode_link_path;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct Settings {
@@ -13,20 +16,102 @@ pub struct Settings {
     pub dailynote: String,
     /// Diffrent pages path than default
     pub new_file_folder_path: String,
+    /// Folder, relative to the vault, that links to attachments may be relative to
+    pub attachments_folder: String,
+    /// Resolve links to notes and headings whatever their case; links with the exact case are still preferred
+    pub case_insensitive_links: bool,
+    /// Compare links and the names of notes and headings after Unicode NFC normalization, so that accented names
+    /// resolve whether their accents are composed or decomposed
+    pub unicode_normalize_links: bool,
+    /// Note, relative to the vault and without extension, that the open tasks of the vault are collected in
+    pub agenda_note: String,
+    /// The states the `toggle_task` command cycles checkboxes through, as the character between the brackets
+    pub task_states: Vec<String>,
     pub daily_notes_folder: String,
+    /// Note, relative to the vault, whose content new daily notes created by the `daily` command are seeded with
+    pub daily_note_template: Option<String>,
+    /// Days before today given relative daily note names, such as "last friday"
+    pub daily_note_lookback: i64,
+    /// Days after today given relative daily note names
+    pub daily_note_lookahead: i64,
+    /// Days both before and after today given relative daily note names; this overrides the lookback and lookahead
+    pub daily_note_completion_window: Option<i64>,
+    /// Language of relative daily note names
+    pub daily_note_locale: String,
+    /// Format of weekly notes
+    pub weekly_note: String,
+    pub weekly_notes_folder: String,
+    /// Format of monthly notes
+    pub monthly_note: String,
+    pub monthly_notes_folder: String,
     pub heading_completions: bool,
+    /// Show the note and parent headings of completed headings, such as `Note > H1 > H2`
+    pub heading_completion_breadcrumbs: bool,
+    /// Rank completions matching near the start of their name higher
+    pub completion_prefer_prefix: bool,
+    /// Match completions as file paths, favoring matches after folder separators
+    pub completion_match_paths: bool,
+    /// Also complete links to notes whose content contains the entered text
+    pub content_completions: bool,
+    /// Globs, relative to the vault root, of notes that are indexed but not offered in link completions
+    pub completion_exclude_paths: Vec<String>,
     pub title_headings: bool,
+    /// Use the title heading of the linked note as the display text of completed wiki links and daily note links
+    pub title_display_text: bool,
     pub unresolved_diagnostics: bool,
+    pub diagnostics_scope: DiagnosticsScope,
+    /// Also publish diagnostics for the files of the vault that are not opened, once the vault is read
+    pub background_diagnostics: bool,
+    /// The most files diagnosed by the background pass
+    pub background_diagnostics_max_files: usize,
+    /// Show one diagnostic for identical unresolved references in a file instead of one for each
+    pub collapse_duplicate_diagnostics: bool,
+    /// Hint at wiki links whose display text only repeats the target, as in `[[Note|Note]]`
+    pub redundant_display_text_diagnostics: bool,
+    /// Severity of the diagnostics on headings with the same text as an earlier heading of the file
+    pub duplicate_heading_diagnostics: DiagnosticLevel,
     pub semantic_tokens: bool,
     pub tags_in_codeblocks: bool,
+    /// Insert a space after completed tags, ready for the next word
+    pub tag_completion_trailing_space: bool,
     pub references_in_codeblocks: bool,
     pub include_md_extension_md_link: bool,
     pub include_md_extension_wikilink: bool,
+    pub html_links: bool,
+    /// Which styles of links to a heading are updated when it is renamed
+    pub rename_link_styles: LinkStyles,
+    /// Style of the links generated where the style is not otherwise determined, such as by `insert_backlink` and the
+    /// extract to note code action; completions follow the link being typed
+    pub default_link_style: LinkStyle,
+    /// How markdown links name headings: by their GitHub style slug, as in `#initial-setup`, or only by their text
+    pub heading_slug_style: HeadingSlugStyle,
+    /// Resolve links to anchors set with `{#id}` attributes on headings and blocks
+    pub attribute_anchors: bool,
     pub hover: bool,
+    /// Include tags and indexed blocks in the document outline, not only headings
+    pub tag_and_block_symbols: bool,
+    /// Include callouts, as in `> [!note] Title`, in the document outline
+    pub callout_symbols: bool,
+    /// The most backlinks listed in hover and completion previews
+    pub hover_backlink_limit: usize,
+    /// Show fields of the frontmatter of linked notes in hover previews
+    pub hover_frontmatter: bool,
+    /// The frontmatter fields shown in hover previews, in order
+    pub hover_frontmatter_keys: Vec<String>,
+    /// The most lines of notes and heading sections shown in previews
+    pub preview_max_lines: usize,
     pub case_matching: Case,
     pub inlay_hints: bool,
+    /// Show the number of links to each heading at its end
+    pub backlink_count_hints: bool,
+    /// Also show the count for headings without links
+    pub backlink_count_hints_zero: bool,
     pub block_transclusion: bool,
     pub block_transclusion_length: EmbeddedBlockTransclusionLength,
+    /// Note that tags navigate to, such as an `index` note listing all tags
+    pub tag_index_note: Option<String>,
+    /// Frontmatter template for new notes; `{{title}}` and `{{date}}` are replaced by the note name and the current date
+    pub new_note_frontmatter: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -36,19 +121,114 @@ pub enum Case {
     Respect,
 }
 
+/// Which files diagnostics are recomputed for when a single file changes
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticsScope {
+    /// Every opened file
+    Open,
+    /// Only the changed file; opened files are fully recomputed on vault reconstruction
+    Active,
+}
+
+/// The severity of a kind of diagnostics, or `off` to not report them
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+    Off,
+    Hint,
+    Information,
+    Warning,
+}
+
+impl DiagnosticLevel {
+    pub fn severity(&self) -> Option<DiagnosticSeverity> {
+        match self {
+            DiagnosticLevel::Off => None,
+            DiagnosticLevel::Hint => Some(DiagnosticSeverity::HINT),
+            DiagnosticLevel::Information => Some(DiagnosticSeverity::INFORMATION),
+            DiagnosticLevel::Warning => Some(DiagnosticSeverity::WARNING),
+        }
+    }
+}
+
+/// Styles of links: `[[wiki links]]`, `[markdown links](note)`, or both
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyles {
+    Wiki,
+    Markdown,
+    Both,
+}
+
+/// The style of a link: `[[wiki link]]` or `[markdown link](note)`
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStyle {
+    Wiki,
+    Markdown,
+}
+
+/// The anchors markdown links name headings by, besides their percent-encoded text
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadingSlugStyle {
+    /// The slugs GitHub generates: lowercase, without punctuation and with dashes for spaces
+    Github,
+    /// Only the text of the heading, as Obsidian links to headings
+    Obsidian,
+}
+
+impl LinkStyles {
+    pub fn includes_wiki(&self) -> bool {
+        matches!(self, LinkStyles::Wiki | LinkStyles::Both)
+    }
+
+    pub fn includes_markdown(&self) -> bool {
+        matches!(self, LinkStyles::Markdown | LinkStyles::Both)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub enum EmbeddedBlockTransclusionLength {
     Partial(usize),
     Full,
 }
 
+/// The most days before or after today that are given relative daily note names
+const MAX_DAILY_NOTE_WINDOW: i64 = 366;
+
 impl Settings {
     pub fn new(root_dir: &Path, capabilities: &ClientCapabilities) -> anyhow::Result<Settings> {
+        let expanded = shellexpand::tilde("~/.config/moxide/settings");
+        Settings::from_config_files(root_dir, capabilities, Some(&expanded))
+    }
+
+    /// The settings of the vault alone, without the global settings file of the machine
+    #[cfg(test)]
+    pub(crate) fn without_global_config(
+        root_dir: &Path,
+        capabilities: &ClientCapabilities,
+    ) -> anyhow::Result<Settings> {
+        Settings::from_config_files(root_dir, capabilities, None)
+    }
+
+    fn from_config_files(
+        root_dir: &Path,
+        capabilities: &ClientCapabilities,
+        global_config: Option<&str>,
+    ) -> anyhow::Result<Settings> {
         let obsidian_daily_note_config = obsidian_daily_note_config(root_dir).unwrap_or_default();
         let obsidian_new_file_folder_path = obsidian_new_file_folder_path(root_dir);
-        let expanded = shellexpand::tilde("~/.config/moxide/settings");
-        let settings = Config::builder()
-            .add_source(File::with_name(&expanded).required(false))
+        let obsidian_attachment_folder_path = obsidian_attachment_folder_path(root_dir);
+        let obsidian_periodic_notes_config =
+            obsidian_periodic_notes_config(root_dir).unwrap_or_default();
+        let mut builder = Config::builder();
+        if let Some(global_config) = global_config {
+            builder = builder.add_source(File::with_name(global_config).required(false));
+        }
+
+        let settings = builder
             .add_source(
                 File::with_name(&format!(
                     "{}/.moxide",
@@ -62,27 +242,96 @@ impl Settings {
                 "new_file_folder_path",
                 obsidian_new_file_folder_path.unwrap_or("".to_string()),
             )?
+            .set_default(
+                "attachments_folder",
+                obsidian_attachment_folder_path.unwrap_or("".to_string()),
+            )?
+            .set_default("default_link_style", obsidian_link_style(root_dir))?
+            .set_default("heading_slug_style", "github")?
             .set_default(
                 "daily_notes_folder",
                 obsidian_daily_note_config.folder.unwrap_or("".to_string()),
             )?
+            .set_default("daily_note_template", obsidian_daily_note_config.template)?
             .set_default(
                 "dailynote",
                 obsidian_daily_note_config
                     .format
                     .unwrap_or("%Y-%m-%d".to_string()),
             )?
+            .set_default(
+                "weekly_note",
+                obsidian_periodic_notes_config
+                    .weekly
+                    .format
+                    .unwrap_or("%G-W%V".to_string()),
+            )?
+            .set_default(
+                "weekly_notes_folder",
+                obsidian_periodic_notes_config
+                    .weekly
+                    .folder
+                    .unwrap_or("".to_string()),
+            )?
+            .set_default(
+                "monthly_note",
+                obsidian_periodic_notes_config
+                    .monthly
+                    .format
+                    .unwrap_or("%Y-%m".to_string()),
+            )?
+            .set_default(
+                "monthly_notes_folder",
+                obsidian_periodic_notes_config
+                    .monthly
+                    .folder
+                    .unwrap_or("".to_string()),
+            )?
+            .set_default("daily_note_lookback", 7)?
+            .set_default("daily_note_lookahead", 7)?
+            .set_default("daily_note_locale", "en")?
             .set_default("heading_completions", true)?
+            .set_default("heading_completion_breadcrumbs", false)?
+            .set_default("content_completions", false)?
+            .set_default("completion_exclude_paths", Vec::<String>::new())?
+            .set_default("completion_prefer_prefix", false)?
+            .set_default("completion_match_paths", false)?
             .set_default("unresolved_diagnostics", true)?
+            .set_default("diagnostics_scope", "open")?
+            .set_default("background_diagnostics", false)?
+            .set_default("background_diagnostics_max_files", 1000)?
+            .set_default("collapse_duplicate_diagnostics", false)?
+            .set_default("redundant_display_text_diagnostics", false)?
+            .set_default("duplicate_heading_diagnostics", "information")?
+            .set_default("agenda_note", "Agenda")?
+            .set_default("task_states", vec![" ", "x"])?
+            .set_default("case_insensitive_links", true)?
+            .set_default("unicode_normalize_links", true)?
             .set_default("title_headings", true)?
+            .set_default("title_display_text", false)?
             .set_default("semantic_tokens", true)?
             .set_default("tags_in_codeblocks", true)?
+            .set_default("tag_completion_trailing_space", false)?
             .set_default("references_in_codeblocks", true)?
             .set_default("include_md_extension_md_link", false)?
             .set_default("include_md_extension_wikilink", false)?
+            .set_default("html_links", true)?
+            .set_default("rename_link_styles", "both")?
+            .set_default("attribute_anchors", false)?
             .set_default("hover", true)?
+            .set_default("tag_and_block_symbols", true)?
+            .set_default("callout_symbols", false)?
+            .set_default("hover_backlink_limit", 20)?
+            .set_default("hover_frontmatter", false)?
+            .set_default(
+                "hover_frontmatter_keys",
+                vec!["title", "tags", "aliases", "date", "status"],
+            )?
+            .set_default("preview_max_lines", 20)?
             .set_default("case_matching", "Smart")?
             .set_default("inlay_hints", true)?
+            .set_default("backlink_count_hints", false)?
+            .set_default("backlink_count_hints_zero", false)?
             .set_default("block_transclusion", true)?
             .set_default("block_transclusion_length", "Full")?
             .set_override_option(
@@ -97,16 +346,130 @@ impl Settings {
             .build()
             .map_err(|err| anyhow!("Build err: {err}"))?;
 
-        let settings = settings.try_deserialize::<Settings>()?;
+        let mut settings = settings.try_deserialize::<Settings>()?;
+
+        if let Some(window) = settings.daily_note_completion_window {
+            settings.daily_note_lookback = window;
+            settings.daily_note_lookahead = window;
+        }
+
+        // Each day of the window is given a name and a command, so the window is bounded to a year either side
+        settings.daily_note_lookback = settings.daily_note_lookback.clamp(0, MAX_DAILY_NOTE_WINDOW);
+        settings.daily_note_lookahead = settings
+            .daily_note_lookahead
+            .clamp(0, MAX_DAILY_NOTE_WINDOW);
+
+        // Formatting a date with an invalid format panics, so such formats are rejected up front
+        for (setting, format) in [
+            ("dailynote", &settings.dailynote),
+            ("weekly_note", &settings.weekly_note),
+            ("monthly_note", &settings.monthly_note),
+        ] {
+            if !format_is_valid(format) {
+                return Err(anyhow!(
+                    "Invalid {setting} format \"{format}\"; it must be a chrono strftime format such as \"%Y-%m-%d\""
+                ));
+            }
+        }
 
         anyhow::Ok(settings)
     }
+
+    /// Whether the note at `path` is left out of link completions by `completion_exclude_paths`; a pattern matching
+    /// a folder excludes everything in it
+    pub fn excluded_from_completion(&self, root_dir: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root_dir) else {
+            return false;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+
+        self.completion_exclude_paths.iter().any(|pattern| {
+            let pattern = pattern.trim_end_matches('/');
+
+            glob_matches(pattern, &relative)
+                || relative
+                    .match_indices('/')
+                    .any(|(i, _)| glob_matches(pattern, &relative[..i]))
+        })
+    }
+
+    /// The frontmatter block a new note named `title` is seeded with, if a template is configured
+    pub fn frontmatter_scaffold(&self, title: &str) -> Option<String> {
+        let template = self.new_note_frontmatter.as_ref()?;
+        let date = chrono::Local::now()
+            .date_naive()
+            .format("%Y-%m-%d")
+            .to_string();
+
+        let frontmatter = template
+            .replace("{{title}}", title)
+            .replace("{{date}}", &date);
+
+        Some(format!("---\n{}\n---\n", frontmatter.trim_end()))
+    }
+
+    /// A link to the note at `refname`, relative to the vault, in the default link style
+    pub fn note_link(&self, refname: &str) -> String {
+        match self.default_link_style {
+            LinkStyle::Wiki => format!(
+                "[[{}{}]]",
+                refname,
+                if self.include_md_extension_wikilink {
+                    ".md"
+                } else {
+                    ""
+                }
+            ),
+            LinkStyle::Markdown => format!(
+                "[{}]({}{})",
+                refname.rsplit('/').next().unwrap_or(refname),
+                encode_link_path(refname),
+                if self.include_md_extension_md_link {
+                    ".md"
+                } else {
+                    ""
+                }
+            ),
+        }
+    }
+}
+
+/// Match a path against a glob: `*` matches within a folder name, `**` across folders, and `?` a single character
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    if let Some(rest) = pattern.strip_prefix("**") {
+        return match rest.strip_prefix('/') {
+            // `**/` matches any number of whole folders, including none
+            Some(rest) => {
+                glob_matches(rest, path)
+                    || path
+                        .match_indices('/')
+                        .any(|(i, _)| glob_matches(rest, &path[i + 1..]))
+            }
+            None => (0..=path.len())
+                .filter(|i| path.is_char_boundary(*i))
+                .any(|i| glob_matches(rest, &path[i..])),
+        };
+    }
+
+    match (pattern.chars().next(), path.chars().next()) {
+        (None, None) => true,
+        (Some('*'), next) => {
+            glob_matches(&pattern[1..], path)
+                || next.is_some_and(|c| c != '/' && glob_matches(pattern, &path[c.len_utf8()..]))
+        }
+        (Some('?'), Some(c)) if c != '/' => glob_matches(&pattern[1..], &path[c.len_utf8()..]),
+        (Some(p), Some(c)) if p == c => {
+            glob_matches(&pattern[p.len_utf8()..], &path[c.len_utf8()..])
+        }
+        _ => false,
+    }
 }
 
 #[derive(Deserialize, Debug, Default)]
 struct ObsidianDailyNoteConfig {
     folder: Option<String>,
     format: Option<String>,
+    template: Option<String>,
 }
 
 fn obsidian_daily_note_config(root_dir: &Path) -> Option<ObsidianDailyNoteConfig> {
@@ -117,16 +480,63 @@ fn obsidian_daily_note_config(root_dir: &Path) -> Option<ObsidianDailyNoteConfig
     Some(ObsidianDailyNoteConfig {
         folder: config.folder,
         format: config.format.map(|x| convert_momentjs_to_chrono_format(&x)),
+        template: config.template.filter(|template| !template.is_empty()),
     })
 }
 
-fn obsidian_new_file_folder_path(root_dir: &Path) -> Option<String> {
+#[derive(Deserialize, Debug, Default)]
+struct ObsidianPeriodicNotesConfig {
+    #[serde(default)]
+    weekly: ObsidianPeriodicNoteConfig,
+    #[serde(default)]
+    monthly: ObsidianPeriodicNoteConfig,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ObsidianPeriodicNoteConfig {
+    #[serde(default)]
+    enabled: bool,
+    format: Option<String>,
+    folder: Option<String>,
+}
+
+/// Weekly and monthly note settings from the Periodic Notes plugin; settings of disabled or blank periods are ignored
+fn obsidian_periodic_notes_config(root_dir: &Path) -> Option<ObsidianPeriodicNotesConfig> {
+    let periodic_notes_config_file = root_dir
+        .join(".obsidian")
+        .join("plugins")
+        .join("periodic-notes")
+        .join("data.json");
+    let file = std::fs::read_to_string(periodic_notes_config_file).ok()?;
+    let config: ObsidianPeriodicNotesConfig = serde_json::from_str(&file).ok()?;
+
+    let convert = |period: ObsidianPeriodicNoteConfig| match period.enabled {
+        true => ObsidianPeriodicNoteConfig {
+            enabled: true,
+            format: period
+                .format
+                .filter(|format| !format.is_empty())
+                .map(|format| convert_momentjs_to_chrono_format(&format)),
+            folder: period.folder,
+        },
+        false => ObsidianPeriodicNoteConfig::default(),
+    };
+
+    Some(ObsidianPeriodicNotesConfig {
+        weekly: convert(config.weekly),
+        monthly: convert(config.monthly),
+    })
+}
+
+fn obsidian_app_config(root_dir: &Path) -> Option<HashMap<String, Value>> {
     let obsidian_settings_file = root_dir.join(".obsidian").join("app.json");
-    let file = std::fs::read(obsidian_settings_file).ok();
-    let config: Option<HashMap<String, Value>> = file.and_then(|file| {
-        let parsed = serde_json::from_slice(&file);
-        parsed.ok()
-    });
+    let file = std::fs::read(obsidian_settings_file).ok()?;
+
+    serde_json::from_slice(&file).ok()
+}
+
+fn obsidian_new_file_folder_path(root_dir: &Path) -> Option<String> {
+    let config = obsidian_app_config(root_dir);
 
     let new_file_folder_path = config.as_ref().and_then(|config| {
         let path = config
@@ -144,6 +554,31 @@ fn obsidian_new_file_folder_path(root_dir: &Path) -> Option<String> {
     new_file_folder_path
 }
 
+/// `markdown` if Obsidian is set to use markdown links rather than wiki links
+fn obsidian_link_style(root_dir: &Path) -> &'static str {
+    let use_markdown_links = obsidian_app_config(root_dir)
+        .and_then(|config| config.get("useMarkdownLinks")?.as_bool())
+        .unwrap_or(false);
+
+    match use_markdown_links {
+        true => "markdown",
+        false => "wiki",
+    }
+}
+
+/// The "Default location for new attachments" of obsidian, if it is a fixed folder; attachments put in the vault root
+/// or next to each note are not in a folder of their own
+fn obsidian_attachment_folder_path(root_dir: &Path) -> Option<String> {
+    let config = obsidian_app_config(root_dir)?;
+    let path = config.get("attachmentFolderPath")?.as_str()?;
+
+    match path.trim_matches('/') {
+        "" => None,
+        path if path == "." || path.starts_with("./") => None,
+        path => Some(path.to_string()),
+    }
+}
+
 use std::collections::HashMap;
 
 // GPT-4 code
@@ -160,13 +595,36 @@ fn momentjs_to_chrono_format_map() -> IndexMap<&'static str, &'static str> {
     map.insert("MM", "%m");
     map.insert("M", "%-m");
 
-    // Day
+    // Day; chrono has no ordinal suffixes, so `Do` is the plain day
     map.insert("DD", "%d");
     map.insert("D", "%-d");
+    map.insert("Do", "%-d");
+
+    // Day of year
+    map.insert("DDDD", "%j");
+    map.insert("DDD", "%-j");
 
     // Weekday
     map.insert("dddd", "%A");
     map.insert("ddd", "%a");
+    map.insert("d", "%w");
+    map.insert("e", "%w");
+    map.insert("E", "%u");
+
+    // Week; locale weeks are treated as ISO weeks
+    map.insert("gggg", "%G");
+    map.insert("GGGG", "%G");
+    map.insert("gg", "%g");
+    map.insert("GG", "%g");
+    map.insert("ww", "%V");
+    map.insert("WW", "%V");
+    map.insert("w", "%-V");
+    map.insert("W", "%-V");
+
+    // Tokens chrono cannot format are dropped rather than left in the format as text
+    map.insert("Q", "");
+    map.insert("Qo", "");
+    map.insert("dd", "");
 
     map
 }
@@ -174,10 +632,43 @@ fn momentjs_to_chrono_format_map() -> IndexMap<&'static str, &'static str> {
 // GPT-4 code
 fn convert_momentjs_to_chrono_format(moment_format: &str) -> String {
     let format_map = momentjs_to_chrono_format_map();
-    let mut chrono_format = moment_format.to_string();
 
-    for (moment_token, chrono_token) in format_map.iter() {
-        chrono_format = chrono_format.replace(moment_token, chrono_token);
+    // Text in brackets is literal in moment formats, as in `gggg-[W]ww`
+    moment_format
+        .split(['[', ']'])
+        .enumerate()
+        .map(|(i, segment)| match i % 2 {
+            0 => convert_momentjs_tokens(segment, &format_map),
+            _ => segment.replace('%', "%%"),
+        })
+        .collect()
+}
+
+/// Replace the moment tokens in `segment`, longest first, with their chrono equivalents; other text is kept literally
+fn convert_momentjs_tokens(segment: &str, format_map: &IndexMap<&str, &str>) -> String {
+    let mut chrono_format = String::new();
+    let mut rest = segment;
+
+    while let Some(next) = rest.chars().next() {
+        let token = format_map
+            .iter()
+            .filter(|(moment_token, _)| rest.starts_with(**moment_token))
+            .max_by_key(|(moment_token, _)| moment_token.len());
+
+        match (token, next) {
+            (Some((moment_token, chrono_token)), _) => {
+                chrono_format.push_str(chrono_token);
+                rest = &rest[moment_token.len()..];
+            }
+            (None, '%') => {
+                chrono_format.push_str("%%");
+                rest = &rest[1..];
+            }
+            (None, literal) => {
+                chrono_format.push(literal);
+                rest = &rest[literal.len_utf8()..];
+            }
+        }
     }
 
     chrono_format
@@ -188,10 +679,14 @@ mod test {
 
     use std::path::PathBuf;
 
+    use tower_lsp::lsp_types::ClientCapabilities;
+
     use crate::config::{
-        convert_momentjs_to_chrono_format, obsidian_daily_note_config,
-        obsidian_new_file_folder_path,
+        convert_momentjs_to_chrono_format, glob_matches, obsidian_attachment_folder_path,
+        obsidian_daily_note_config, obsidian_new_file_folder_path, obsidian_periodic_notes_config,
+        LinkStyle, Settings,
     };
+    use crate::vault::test_settings;
 
     #[test]
     fn test_format_conversion() {
@@ -200,6 +695,60 @@ mod test {
         assert_eq!(chrono_format, "%Y-%m-%d");
     }
 
+    #[test]
+    fn test_week_format_conversion() {
+        let moment_format = "gggg-[W]ww";
+        let chrono_format = convert_momentjs_to_chrono_format(moment_format);
+        assert_eq!(chrono_format, "%G-W%V");
+    }
+
+    #[test]
+    fn test_ordinal_day_conversion() {
+        assert_eq!(convert_momentjs_to_chrono_format("YYYY-DDDD"), "%Y-%j");
+        assert_eq!(convert_momentjs_to_chrono_format("YYYY-DDD"), "%Y-%-j");
+        assert_eq!(
+            convert_momentjs_to_chrono_format("MMMM Do, YYYY"),
+            "%B %-d, %Y"
+        );
+    }
+
+    #[test]
+    fn test_weekday_conversion() {
+        assert_eq!(
+            convert_momentjs_to_chrono_format("GGGG-[W]WW-E"),
+            "%G-W%V-%u"
+        );
+        assert_eq!(
+            convert_momentjs_to_chrono_format("YYYY-MM-DD d"),
+            "%Y-%m-%d %w"
+        );
+        assert_eq!(convert_momentjs_to_chrono_format("gg-w"), "%g-%-V");
+    }
+
+    #[test]
+    fn test_unsupported_token_conversion() {
+        assert_eq!(convert_momentjs_to_chrono_format("YYYY-[Q]Q"), "%Y-Q");
+        assert_eq!(convert_momentjs_to_chrono_format("dd DD"), " %d");
+        assert_eq!(convert_momentjs_to_chrono_format("YYYY%MM"), "%Y%%%m");
+    }
+
+    #[test]
+    fn test_periodic_notes_config() {
+        let periodic_notes_config = obsidian_periodic_notes_config(&root_dir()).unwrap();
+        assert_eq!(
+            periodic_notes_config.weekly.format,
+            Some("%G-W%V".to_string())
+        );
+        assert_eq!(
+            periodic_notes_config.weekly.folder,
+            Some("the-weekly-notes-folder".to_string())
+        );
+        assert_eq!(
+            periodic_notes_config.monthly.format,
+            Some("%Y-%m".to_string())
+        );
+    }
+
     #[test]
     fn test_daily_note_config() {
         let daily_notes_config = obsidian_daily_note_config(&root_dir()).unwrap();
@@ -219,7 +768,89 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_attachment_folder_path() {
+        let attachment_folder_path = obsidian_attachment_folder_path(&root_dir());
+        assert_eq!(attachment_folder_path, Some("media".to_string()));
+    }
+
+    #[test]
+    fn note_links_in_link_style() {
+        let (_, mut settings) = test_settings();
+
+        settings.default_link_style = LinkStyle::Wiki;
+        settings.include_md_extension_wikilink = false;
+        assert_eq!(settings.note_link("folder/My Note"), "[[folder/My Note]]");
+
+        settings.default_link_style = LinkStyle::Markdown;
+        settings.include_md_extension_md_link = true;
+        assert_eq!(
+            settings.note_link("folder/My Note"),
+            "[My Note](folder/My%20Note.md)"
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_scaffold() {
+        let (_, mut settings) = test_settings();
+        assert_eq!(settings.frontmatter_scaffold("Note"), None);
+
+        settings.new_note_frontmatter = Some("title: {{title}}\ntags: []\n".to_string());
+        assert_eq!(
+            settings.frontmatter_scaffold("Note"),
+            Some("---\ntitle: Note\ntags: []\n---\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("templates/*.md", "templates/daily.md"));
+        assert!(!glob_matches("templates/*.md", "templates/old/daily.md"));
+        assert!(glob_matches(
+            "**/templates/**",
+            "notes/templates/old/daily.md"
+        ));
+        assert!(glob_matches("**/*.excalidraw.md", "drawing.excalidraw.md"));
+        assert!(glob_matches("2024-0?-*", "2024-03-17"));
+        assert!(!glob_matches("2024-0?-*", "2024-10-17"));
+    }
+
+    #[test]
+    fn test_completion_exclude_paths() {
+        let (_, mut settings) = test_settings();
+        settings.completion_exclude_paths = vec!["templates/".to_string(), "*.tmpl.md".to_string()];
+
+        let root = root_dir();
+        assert!(settings.excluded_from_completion(&root, &root.join("templates/daily.md")));
+        assert!(settings.excluded_from_completion(&root, &root.join("templates/a/b.md")));
+        assert!(settings.excluded_from_completion(&root, &root.join("note.tmpl.md")));
+        assert!(!settings.excluded_from_completion(&root, &root.join("folder/note.tmpl.md")));
+        assert!(!settings.excluded_from_completion(&root, &root.join("note.md")));
+    }
+
+    #[test]
+    fn daily_note_window_is_bounded() {
+        let window = |config: &str| {
+            let root_dir = std::env::temp_dir().join(format!("moxide-{}", nanoid::nanoid!()));
+            std::fs::create_dir_all(&root_dir).unwrap();
+            std::fs::write(root_dir.join(".moxide.toml"), config).unwrap();
+
+            let settings =
+                Settings::without_global_config(&root_dir, &ClientCapabilities::default()).unwrap();
+            std::fs::remove_dir_all(&root_dir).unwrap();
+
+            (settings.daily_note_lookback, settings.daily_note_lookahead)
+        };
+
+        assert_eq!(
+            window("daily_note_lookback = -9223372036854775808\ndaily_note_lookahead = 100000"),
+            (0, 366)
+        );
+        assert_eq!(window("daily_note_completion_window = 1000000"), (366, 366));
+        assert_eq!(window("daily_note_lookback = 30"), (30, 7));
+    }
+
     fn root_dir() -> PathBuf {
-        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("TestFiles")
+        test_settings().0
     }
 }
@@ -1,3 +1,8 @@
+use std::fmt::Write;
+
+use chrono::format::{DelayedFormat, Item, StrftimeItems};
+use chrono::{Datelike, Duration, Months, NaiveDate};
+
 use crate::config::Settings;
 
 pub fn filename_is_formatted(context: &Settings, filename: &str) -> bool {
@@ -5,3 +10,350 @@ pub fn filename_is_formatted(context: &Settings, filename: &str) -> bool {
 
     try_parsed.is_ok()
 }
+
+/// Whether dates can be formatted with `format`
+pub fn format_is_valid(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// The formatted date, or none if the format is invalid; chrono panics when such a date is converted with `to_string`
+pub fn try_format(formatted: DelayedFormat<StrftimeItems>) -> Option<String> {
+    let mut date = String::new();
+    write!(date, "{}", formatted).ok()?;
+
+    Some(date)
+}
+
+/// Other formats that daily notes are commonly named in
+const DAILY_NOTE_FORMATS: [&str; 9] = [
+    "%Y-%m-%d",
+    "%Y_%m_%d",
+    "%Y.%m.%d",
+    "%Y %m %d",
+    "%Y%m%d",
+    "%B %d, %Y",
+    "%B %d %Y",
+    "%d %B %Y",
+    "%b %d, %Y",
+];
+
+/// The name a daily note named in another date format, such as `2024_3_5`, should have in the configured one
+pub fn canonical_daily_note_name(settings: &Settings, name: &str) -> Option<String> {
+    let date = std::iter::once(settings.dailynote.as_str())
+        .chain(DAILY_NOTE_FORMATS)
+        .find_map(|format| NaiveDate::parse_from_str(name, format).ok())?;
+
+    let canonical = try_format(date.format(&settings.dailynote))?;
+
+    (canonical != name).then_some(canonical)
+}
+
+/// The words used to name dates relative to today, such as "last friday"; `{}` is replaced by a weekday or a number of days
+struct RelativeDateWords {
+    today: &'static str,
+    tomorrow: &'static str,
+    yesterday: &'static str,
+    last: &'static str,
+    next: &'static str,
+    days_ago: &'static str,
+    in_days: &'static str,
+    /// Monday first
+    weekdays: [&'static str; 7],
+}
+
+fn relative_date_words(locale: &str) -> &'static RelativeDateWords {
+    static EN: RelativeDateWords = RelativeDateWords {
+        today: "today",
+        tomorrow: "tomorrow",
+        yesterday: "yesterday",
+        last: "last {}",
+        next: "next {}",
+        days_ago: "{} days ago",
+        in_days: "in {} days",
+        weekdays: [
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+        ],
+    };
+    static DE: RelativeDateWords = RelativeDateWords {
+        today: "heute",
+        tomorrow: "morgen",
+        yesterday: "gestern",
+        last: "letzten {}",
+        next: "nächsten {}",
+        days_ago: "vor {} Tagen",
+        in_days: "in {} Tagen",
+        weekdays: [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+    };
+    static FR: RelativeDateWords = RelativeDateWords {
+        today: "aujourd'hui",
+        tomorrow: "demain",
+        yesterday: "hier",
+        last: "{} dernier",
+        next: "{} prochain",
+        days_ago: "il y a {} jours",
+        in_days: "dans {} jours",
+        weekdays: [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+    };
+    static ES: RelativeDateWords = RelativeDateWords {
+        today: "hoy",
+        tomorrow: "mañana",
+        yesterday: "ayer",
+        last: "el {} pasado",
+        next: "el próximo {}",
+        days_ago: "hace {} días",
+        in_days: "en {} días",
+        weekdays: [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+    };
+    static PT: RelativeDateWords = RelativeDateWords {
+        today: "hoje",
+        tomorrow: "amanhã",
+        yesterday: "ontem",
+        last: "{} passado",
+        next: "próximo {}",
+        days_ago: "há {} dias",
+        in_days: "em {} dias",
+        weekdays: [
+            "segunda", "terça", "quarta", "quinta", "sexta", "sábado", "domingo",
+        ],
+    };
+
+    match locale.split(['_', '-']).next() {
+        Some("de") => &DE,
+        Some("fr") => &FR,
+        Some("es") => &ES,
+        Some("pt") => &PT,
+        _ => &EN,
+    }
+}
+
+/// The relative name of a date, such as "tomorrow" or "last friday", if it is within the configured range of daily notes
+pub fn relative_date_name(settings: &Settings, date: NaiveDate) -> Option<String> {
+    let today = chrono::Local::now().date_naive();
+
+    relative_name(
+        relative_date_words(&settings.daily_note_locale),
+        (settings.daily_note_lookback, settings.daily_note_lookahead),
+        today,
+        date,
+    )
+}
+
+/// All relative daily note names in the configured range, with their dates; these are also the daily note commands
+pub fn relative_dates(settings: &Settings) -> Vec<(String, NaiveDate)> {
+    let today = chrono::Local::now().date_naive();
+
+    relative_dates_from(
+        relative_date_words(&settings.daily_note_locale),
+        (settings.daily_note_lookback, settings.daily_note_lookahead),
+        today,
+    )
+}
+
+fn relative_dates_from(
+    words: &RelativeDateWords,
+    (lookback, lookahead): (i64, i64),
+    today: NaiveDate,
+) -> Vec<(String, NaiveDate)> {
+    (-lookback..=lookahead)
+        .flat_map(|days| today.checked_add_signed(Duration::try_days(days)?))
+        .flat_map(|date| {
+            Some((
+                relative_name(words, (lookback, lookahead), today, date)?,
+                date,
+            ))
+        })
+        .collect()
+}
+
+fn relative_name(
+    words: &RelativeDateWords,
+    (lookback, lookahead): (i64, i64),
+    today: NaiveDate,
+    date: NaiveDate,
+) -> Option<String> {
+    let weekday = words.weekdays[date.weekday().num_days_from_monday() as usize];
+
+    match (date - today).num_days() {
+        days if days < -lookback || days > lookahead => None,
+        0 => Some(words.today.to_string()),
+        1 => Some(words.tomorrow.to_string()),
+        -1 => Some(words.yesterday.to_string()),
+        2..=7 => Some(words.next.replace("{}", weekday)),
+        -7..=-2 => Some(words.last.replace("{}", weekday)),
+        days if days > 0 => Some(words.in_days.replace("{}", &days.to_string())),
+        days => Some(words.days_ago.replace("{}", &(-days).to_string())),
+    }
+}
+
+/// Periods of periodic notes other than daily notes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Week,
+    Month,
+}
+
+/// Names of periodic notes relative to the current period, with the period and its offset from the current one; these
+/// are also the periodic note commands
+pub const RELATIVE_PERIODS: [(&str, Period, i32); 6] = [
+    ("last week", Period::Week, -1),
+    ("this week", Period::Week, 0),
+    ("next week", Period::Week, 1),
+    ("last month", Period::Month, -1),
+    ("this month", Period::Month, 0),
+    ("next month", Period::Month, 1),
+];
+
+impl Period {
+    /// The first day of the period containing `date`, moved by `offset` periods
+    pub fn start(&self, date: NaiveDate, offset: i32) -> Option<NaiveDate> {
+        match self {
+            Period::Week => date
+                .checked_sub_signed(Duration::try_days(
+                    date.weekday().num_days_from_monday().into(),
+                )?)?
+                .checked_add_signed(Duration::try_weeks(offset.into())?),
+            Period::Month => {
+                let start = date.with_day(1)?;
+                let months = Months::new(offset.unsigned_abs());
+
+                match offset {
+                    0.. => start.checked_add_months(months),
+                    _ => start.checked_sub_months(months),
+                }
+            }
+        }
+    }
+
+    /// The format and folder of notes of this period
+    pub fn note_settings<'a>(&self, settings: &'a Settings) -> (&'a str, &'a str) {
+        match self {
+            Period::Week => (&settings.weekly_note, &settings.weekly_notes_folder),
+            Period::Month => (&settings.monthly_note, &settings.monthly_notes_folder),
+        }
+    }
+
+    /// The name of the note of the period containing `date`, moved by `offset` periods
+    pub fn note_name(&self, settings: &Settings, date: NaiveDate, offset: i32) -> Option<String> {
+        let (format, _) = self.note_settings(settings);
+
+        try_format(self.start(date, offset)?.format(format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::vault::test_settings;
+
+    use super::{
+        canonical_daily_note_name, format_is_valid, relative_date_words, relative_dates_from,
+        try_format, Period,
+    };
+
+    #[test]
+    fn relative_dates_are_unique_and_localized() {
+        // A wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+
+        let dates = relative_dates_from(relative_date_words("es_ES"), (10, 3), today);
+
+        assert_eq!(dates.len(), 14);
+        assert!(dates.contains(&(
+            "hace 10 días".to_string(),
+            NaiveDate::from_ymd_opt(2024, 5, 5).unwrap()
+        )));
+        assert!(dates.contains(&(
+            "el sábado pasado".to_string(),
+            NaiveDate::from_ymd_opt(2024, 5, 11).unwrap()
+        )));
+        assert!(dates.contains(&(
+            "mañana".to_string(),
+            NaiveDate::from_ymd_opt(2024, 5, 16).unwrap()
+        )));
+
+        // Commands are matched by name, so each name must refer to one date
+        let mut names = dates.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), dates.len());
+    }
+
+    #[test]
+    fn period_start() {
+        // A wednesday
+        let today = NaiveDate::from_ymd_opt(2024, 1, 17).unwrap();
+
+        assert_eq!(
+            Period::Week.start(today, -1),
+            NaiveDate::from_ymd_opt(2024, 1, 8)
+        );
+        assert_eq!(
+            Period::Week
+                .start(today, 0)
+                .map(|date| date.format("%G-W%V").to_string()),
+            Some("2024-W03".to_string())
+        );
+        assert_eq!(
+            Period::Month.start(today, -1),
+            NaiveDate::from_ymd_opt(2023, 12, 1)
+        );
+        assert_eq!(
+            Period::Month.start(today, 1),
+            NaiveDate::from_ymd_opt(2024, 2, 1)
+        );
+    }
+
+    #[test]
+    fn invalid_format_does_not_panic() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 17).unwrap();
+
+        assert!(!format_is_valid("%Y-%Q"));
+        assert!(format_is_valid("%G-W%V"));
+        assert_eq!(try_format(date.format("%Y-%Q")), None);
+        assert_eq!(
+            try_format(date.format("%Y-%j")),
+            Some("2024-017".to_string())
+        );
+    }
+
+    #[test]
+    fn canonical_daily_note_names() {
+        let (_, settings) = test_settings();
+
+        assert_eq!(
+            canonical_daily_note_name(&settings, "2024-3-5"),
+            Some("2024-03-05".to_string())
+        );
+        assert_eq!(
+            canonical_daily_note_name(&settings, "March 5, 2024"),
+            Some("2024-03-05".to_string())
+        );
+        assert_eq!(canonical_daily_note_name(&settings, "2024-03-05"), None);
+        assert_eq!(canonical_daily_note_name(&settings, "Meeting notes"), None);
+    }
+}
@@ -1,26 +1,48 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
+use itertools::Itertools;
+use once_cell::sync::OnceCell;
 use rayon::prelude::*;
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, DiagnosticTag, Location, Url,
+};
 
 use crate::{
-    config::Settings,
-    vault::{self, Reference, Referenceable, Vault},
+    completion::matcher::{fuzzy_match, Matchable},
+    config::{Case, DiagnosticsScope, Settings},
+    vault::{self, MDHeading, Reference, Referenceable, Refname, Vault},
 };
 
 pub fn path_unresolved_references<'a>(
     vault: &'a Vault,
     path: &'a Path,
+) -> Option<Vec<(&'a Path, &'a Reference)>> {
+    unresolved_references(vault, Some(path))
+}
+
+/// Unresolved references across the whole vault
+pub fn vault_unresolved_references(vault: &Vault) -> Option<Vec<(&Path, &Reference)>> {
+    unresolved_references(vault, None)
+}
+
+fn unresolved_references<'a>(
+    vault: &'a Vault,
+    path: Option<&'a Path>,
 ) -> Option<Vec<(&'a Path, &'a Reference)>> {
     let referenceables = vault.select_referenceable_nodes(None);
-    let pathreferences = vault.select_references(Some(path))?;
+    let pathreferences = vault.select_references(path)?;
 
     let unresolved = pathreferences
         .into_par_iter()
         .filter(|(path, reference)| {
-            let matched_option = referenceables
-                .iter()
-                .find(|referenceable| reference.references(vault.root_dir(), path, referenceable));
+            let matched_option = referenceables.iter().find(|referenceable| {
+                reference.references(vault.root_dir(), path, referenceable)
+                    && vault.matches_heading_chain(reference, referenceable)
+                    && vault.matches_case(reference, referenceable)
+            });
 
             matched_option.is_some_and(|matched| {
                 matches!(
@@ -36,24 +58,284 @@ pub fn path_unresolved_references<'a>(
     Some(unresolved)
 }
 
-pub fn diagnostics(
-    vault: &Vault,
+/// Diagnostics of the file at `path`. `candidates` are the suggestions for unresolved references; they are shared by
+/// the files diagnosed together.
+pub fn diagnostics<'a>(
+    vault: &'a Vault,
     settings: &Settings,
-    (path, _uri): (&PathBuf, &Url),
+    (path, uri): (&PathBuf, &Url),
+    candidates: &SuggestionCandidates<'a>,
 ) -> Option<Vec<Diagnostic>> {
-    if !settings.unresolved_diagnostics {
-        return None;
+    let unresolved = match settings.unresolved_diagnostics {
+        true => unresolved_diagnostics(vault, settings, path, candidates)?
+            .into_iter()
+            .chain(ambiguous_case_diagnostics(vault, path)?)
+            .chain(ambiguous_name_diagnostics(vault, path)?)
+            .collect(),
+        false => vec![],
+    };
+
+    let redundant = match settings.redundant_display_text_diagnostics {
+        true => redundant_display_text_diagnostics(vault, path)?,
+        false => vec![],
+    };
+
+    let duplicate_headings = match settings.duplicate_heading_diagnostics.severity() {
+        Some(severity) => duplicate_heading_diagnostics(vault, (path, uri), severity)?,
+        None => vec![],
+    };
+
+    Some(
+        unresolved
+            .into_iter()
+            .chain(redundant)
+            .chain(duplicate_headings)
+            .collect(),
+    )
+}
+
+/// The opened files to publish diagnostics for when `changed` is edited; with the `active` diagnostics scope, only
+/// that file
+pub fn diagnosed_files<'a>(
+    settings: &Settings,
+    opened_files: &'a HashSet<PathBuf>,
+    changed: Option<&Path>,
+) -> Vec<&'a PathBuf> {
+    let changed = changed.filter(|_| settings.diagnostics_scope == DiagnosticsScope::Active);
+
+    opened_files
+        .iter()
+        .filter(|path| changed.is_none() || changed == Some(path.as_path()))
+        .collect()
+}
+
+/// The files diagnosed in the background, in a stable order and up to `background_diagnostics_max_files`
+pub fn background_diagnosed_files(vault: &Vault, settings: &Settings) -> Vec<PathBuf> {
+    vault
+        .md_files
+        .keys()
+        .sorted()
+        .take(settings.background_diagnostics_max_files)
+        .cloned()
+        .collect()
+}
+
+/// Links matching several notes whose paths differ only by case, such as `Note.md` and `note.md`
+fn ambiguous_case_diagnostics(vault: &Vault, path: &Path) -> Option<Vec<Diagnostic>> {
+    let conflicts = vault.select_case_conflicts();
+    if conflicts.is_empty() {
+        return Some(vec![]);
+    }
+
+    Some(
+        vault
+            .select_references(Some(path))?
+            .into_iter()
+            .filter_map(|(reference_path, reference)| {
+                let matched = conflicts
+                    .iter()
+                    .filter(|file| {
+                        file.matches_reference(vault.root_dir(), reference, reference_path)
+                            && vault.matches_case(reference, file)
+                    })
+                    .collect_vec();
+
+                (matched.len() > 1).then(|| Diagnostic {
+                    range: *reference.data().range,
+                    message: format!(
+                        "Ambiguous link: {} notes differ only by case",
+                        matched.len()
+                    ),
+                    source: Some("Obsidian LS".into()),
+                    severity: Some(DiagnosticSeverity::INFORMATION),
+                    related_information: Some(
+                        matched
+                            .iter()
+                            .filter_map(|file| {
+                                Some(DiagnosticRelatedInformation {
+                                    location: Location {
+                                        uri: Url::from_file_path(file.get_path()).ok()?,
+                                        range: Default::default(),
+                                    },
+                                    message: "Matching note".to_string(),
+                                })
+                            })
+                            .collect(),
+                    ),
+                    ..Default::default()
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Links naming notes in several folders by their name alone, such as `[[note]]` for `a/note.md` and `b/note.md`. A
+/// note at the path the link names, such as `note.md`, is not ambiguous, as links resolve to it.
+fn ambiguous_name_diagnostics(vault: &Vault, path: &Path) -> Option<Vec<Diagnostic>> {
+    let conflicts = vault.select_name_conflicts();
+    if conflicts.is_empty() {
+        return Some(vec![]);
     }
 
+    Some(
+        vault
+            .select_references(Some(path))?
+            .into_iter()
+            .filter_map(|(reference_path, reference)| {
+                let matched = conflicts
+                    .iter()
+                    .filter(|file| {
+                        file.matches_reference(vault.root_dir(), reference, reference_path)
+                            && vault.matches_case(reference, file)
+                    })
+                    .collect_vec();
+
+                let refnames = matched
+                    .iter()
+                    .filter_map(|file| file.get_refname(vault.root_dir()))
+                    .map(|refname| refname.full_refname)
+                    .sorted()
+                    .collect_vec();
+
+                // Notes differing only by case are reported by `ambiguous_case_diagnostics`
+                let folders = refnames
+                    .iter()
+                    .map(|refname| refname.to_lowercase())
+                    .unique()
+                    .count();
+
+                (folders > 1
+                    && !matched
+                        .iter()
+                        .any(|file| vault.matches_exact_path(reference, file)))
+                .then(|| Diagnostic {
+                    range: *reference.data().range,
+                    message: format!(
+                        "Ambiguous link: {} notes have this name ({}); add a folder to the link",
+                        matched.len(),
+                        refnames.join(", ")
+                    ),
+                    source: Some("Obsidian LS".into()),
+                    severity: Some(DiagnosticSeverity::INFORMATION),
+                    related_information: Some(
+                        matched
+                            .iter()
+                            .filter_map(|file| {
+                                Some(DiagnosticRelatedInformation {
+                                    location: Location {
+                                        uri: Url::from_file_path(file.get_path()).ok()?,
+                                        range: Default::default(),
+                                    },
+                                    message: "Matching note".to_string(),
+                                })
+                            })
+                            .collect(),
+                    ),
+                    ..Default::default()
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Headings with the same text as an earlier heading of the file, with that heading. Links to the text resolve to
+/// all of them, whatever their levels, so links to a specific one are unreliable.
+pub fn duplicate_headings(headings: &[MDHeading]) -> Vec<(&MDHeading, &MDHeading)> {
+    headings
+        .iter()
+        .enumerate()
+        .filter_map(|(i, heading)| {
+            let first = headings[..i].iter().find(|other| {
+                other.heading_text.to_lowercase() == heading.heading_text.to_lowercase()
+            })?;
+
+            Some((heading, first))
+        })
+        .collect()
+}
+
+fn duplicate_heading_diagnostics(
+    vault: &Vault,
+    (path, uri): (&Path, &Url),
+    severity: DiagnosticSeverity,
+) -> Option<Vec<Diagnostic>> {
+    let headings = vault.select_headings(path)?;
+
+    Some(
+        duplicate_headings(headings)
+            .into_iter()
+            .map(|(heading, first)| Diagnostic {
+                range: *heading.range,
+                message: "Duplicate heading; links to it may go to another heading".to_string(),
+                source: Some("Obsidian LS".into()),
+                severity: Some(severity),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: *first.range,
+                    },
+                    message: "First heading with this text".to_string(),
+                }]),
+                ..Default::default()
+            })
+            .collect(),
+    )
+}
+
+fn redundant_display_text_diagnostics(vault: &Vault, path: &Path) -> Option<Vec<Diagnostic>> {
+    Some(
+        vault
+            .select_references(Some(path))?
+            .into_iter()
+            .filter(|(_, reference)| reference.has_redundant_display_text())
+            .map(|(_, reference)| Diagnostic {
+                range: *reference.data().range,
+                message: "Redundant display text".to_string(),
+                source: Some("Obsidian LS".into()),
+                severity: Some(DiagnosticSeverity::HINT),
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                ..Default::default()
+            })
+            .collect(),
+    )
+}
+
+fn unresolved_diagnostics<'a>(
+    vault: &'a Vault,
+    settings: &Settings,
+    path: &Path,
+    candidates: &SuggestionCandidates<'a>,
+) -> Option<Vec<Diagnostic>> {
     let unresolved = path_unresolved_references(vault, path)?;
 
     let allreferences = vault.select_references(None)?;
 
+    // Identical unresolved references in the file are collapsed into the diagnostic of the first one
+    let unresolved = match settings.collapse_duplicate_diagnostics {
+        true => unresolved.into_iter().fold(
+            Vec::<((&Path, &Reference), Vec<&Reference>)>::new(),
+            |mut collapsed, (path, reference)| {
+                match collapsed.iter_mut().find(|((_, first), _)| {
+                    first.matches_type(reference)
+                        && first.data().reference_text == reference.data().reference_text
+                }) {
+                    Some((_, duplicates)) => duplicates.push(reference),
+                    None => collapsed.push(((path, reference), vec![])),
+                }
+
+                collapsed
+            },
+        ),
+        false => unresolved
+            .into_iter()
+            .map(|unresolved| (unresolved, vec![]))
+            .collect(),
+    };
+
     let diags: Vec<Diagnostic> = unresolved
         .into_par_iter()
-        .map(|(path, reference)| Diagnostic {
-            range: *reference.data().range,
-            message: match allreferences
+        .map(|((path, reference), duplicates)| {
+            let uses = allreferences
                 .iter()
                 .filter(|(other_path, otherreference)| {
                     otherreference.matches_type(reference)
@@ -61,16 +343,316 @@ pub fn diagnostics(
                             || **other_path == *path)
                         && otherreference.data().reference_text == reference.data().reference_text
                 })
-                .count()
-            {
-                num if num > 1 => format!("Unresolved Reference used {} times", num),
-                _ => "Unresolved Reference".to_string(),
-            },
-            source: Some("Obsidian LS".into()),
-            severity: Some(DiagnosticSeverity::INFORMATION),
-            ..Default::default()
+                .count();
+
+            let duplicate_locations = duplicates.iter().filter_map(|duplicate| {
+                Some(DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: Url::from_file_path(path).ok()?,
+                        range: *duplicate.data().range,
+                    },
+                    message: "Same unresolved reference".to_string(),
+                })
+            });
+
+            // A reference used several times is likely a note that is meant to be created, not a typo
+            let related_information = match uses {
+                num if num > 1 => duplicate_locations.collect_vec(),
+                _ => suggested_targets(candidates.get(), reference)
+                    .into_iter()
+                    .filter_map(|suggestion| {
+                        Some(DiagnosticRelatedInformation {
+                            location: Location {
+                                uri: Url::from_file_path(suggestion.referenceable.get_path())
+                                    .ok()?,
+                                range: *suggestion.referenceable.get_range().unwrap_or_default(),
+                            },
+                            message: format!("Did you mean {}?", *suggestion.refname),
+                        })
+                    })
+                    .collect_vec(),
+            };
+
+            Diagnostic {
+                range: *reference.data().range,
+                message: match uses {
+                    num if num > 1 => format!("Unresolved Reference used {} times", num),
+                    _ => "Unresolved Reference".to_string(),
+                },
+                source: Some("Obsidian LS".into()),
+                severity: Some(DiagnosticSeverity::INFORMATION),
+                related_information: Some(related_information)
+                    .filter(|related| !related.is_empty()),
+                ..Default::default()
+            }
         })
         .collect();
 
     Some(diags)
 }
+
+/// A resolved note, heading or block that an unresolved reference may have been meant to link to
+pub struct Suggestion<'a> {
+    pub refname: Refname,
+    pub referenceable: Referenceable<'a>,
+}
+
+impl Matchable for &Suggestion<'_> {
+    fn match_string(&self) -> &str {
+        &self.refname
+    }
+}
+
+/// The maximum number of suggestions for one unresolved reference; more than this is noise for a genuinely new note
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Fuzzy matches must score at least this much per character of the reference to be suggested
+const SUGGESTION_SCORE_PER_CHAR: u32 = 12;
+
+/// The suggestion candidates of a vault, collected when they are first needed: most files have no unresolved
+/// references, and collecting them for every diagnosed file would cost as much as the vault is large
+pub struct SuggestionCandidates<'a> {
+    vault: &'a Vault,
+    candidates: OnceCell<Vec<Suggestion<'a>>>,
+}
+
+impl<'a> SuggestionCandidates<'a> {
+    pub fn new(vault: &'a Vault) -> SuggestionCandidates<'a> {
+        SuggestionCandidates {
+            vault,
+            candidates: OnceCell::new(),
+        }
+    }
+
+    pub fn get(&self) -> &[Suggestion<'a>] {
+        self.candidates
+            .get_or_init(|| suggestion_candidates(self.vault))
+    }
+}
+
+/// All resolved notes, headings and blocks in the vault that could be suggested for unresolved references
+pub fn suggestion_candidates(vault: &Vault) -> Vec<Suggestion<'_>> {
+    vault
+        .select_referenceable_nodes(None)
+        .into_iter()
+        .filter(|referenceable| {
+            matches!(
+                referenceable,
+                Referenceable::File(..)
+                    | Referenceable::Heading(..)
+                    | Referenceable::IndexedBlock(..)
+            )
+        })
+        .filter_map(|referenceable| {
+            Some(Suggestion {
+                refname: referenceable.get_refname(vault.root_dir())?,
+                referenceable,
+            })
+        })
+        .collect()
+}
+
+/// Guess the targets an unresolved reference was meant to link to, best first: first those whose name differs only
+/// in case, separators, or folder, then close fuzzy matches.
+pub fn suggested_targets<'a>(
+    candidates: &'a [Suggestion<'a>],
+    reference: &Reference,
+) -> Vec<&'a Suggestion<'a>> {
+    fn key(text: &str) -> String {
+        let (path, infile) = text.split_once('#').unwrap_or((text, ""));
+        let file = path.rsplit('/').next().unwrap_or(path);
+        let file = file.strip_suffix(".md").unwrap_or(file);
+
+        format!("{file}#{infile}")
+            .replace("%20", " ")
+            .replace(['-', '_'], " ")
+            .to_lowercase()
+    }
+
+    let reference_text = &reference.data().reference_text;
+    let target = key(reference_text);
+
+    let candidates = candidates
+        .iter()
+        .filter(|candidate| match reference {
+            Reference::WikiFileLink(..) | Reference::MDFileLink(..) => {
+                matches!(candidate.referenceable, Referenceable::File(..))
+            }
+            Reference::WikiHeadingLink(..) | Reference::MDHeadingLink(..) => {
+                matches!(candidate.referenceable, Referenceable::Heading(..))
+            }
+            Reference::WikiIndexedBlockLink(..) | Reference::MDIndexedBlockLink(..) => {
+                matches!(candidate.referenceable, Referenceable::IndexedBlock(..))
+            }
+            Reference::Tag(..) | Reference::Footnote(..) | Reference::LinkRef(..) => false,
+        })
+        .collect_vec();
+
+    let same_name = candidates
+        .iter()
+        .filter(|candidate| key(&candidate.refname) == target)
+        .copied();
+
+    // Separators are treated as spaces so that each word is matched on its own; case differences are likely typos
+    let pattern = reference_text
+        .replace("%20", " ")
+        .replace(['-', '_', '/'], " ");
+    let min_score =
+        SUGGESTION_SCORE_PER_CHAR * pattern.chars().filter(|c| !c.is_whitespace()).count() as u32;
+
+    let fuzzy = fuzzy_match(&pattern, candidates.iter().copied(), &Case::Ignore)
+        .into_iter()
+        .filter(|(_, score)| *score >= min_score)
+        .sorted_by_key(|(_, score)| std::cmp::Reverse(*score))
+        .map(|(candidate, _)| candidate);
+
+    same_name
+        .chain(fuzzy)
+        .unique_by(|candidate| &candidate.refname.full_refname)
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::PathBuf};
+
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::{Position, Range, Url};
+
+    use crate::completion::matcher::fuzzy_match;
+    use crate::config::{Case, DiagnosticsScope, Settings};
+    use crate::vault::{test_settings, HeadingLevel, MDHeading, Reference, Vault};
+
+    use super::{
+        background_diagnosed_files, diagnosed_files, diagnostics, duplicate_headings,
+        suggested_targets, SuggestionCandidates, MAX_SUGGESTIONS,
+    };
+
+    #[test]
+    fn suggestions_above_the_score_cutoff() {
+        let (root_dir, settings) = test_settings();
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                "Meeting Notes.md",
+                "Quarterly Budget Review Meeting.md",
+                "Plan 1.md",
+                "Plan 2.md",
+                "Plan 3.md",
+                "Plan 4.md",
+            ]
+            .map(|name| (root_dir.join(name), "")),
+        );
+        let candidates = SuggestionCandidates::new(&vault);
+
+        let suggested = |text| {
+            let reference = Reference::new(text, "test.md").next().unwrap();
+            suggested_targets(candidates.get(), &reference)
+                .into_iter()
+                .map(|suggestion| suggestion.refname.full_refname.clone())
+                .collect_vec()
+        };
+
+        assert_eq!(suggested("[[meeting-notes]]"), vec!["Meeting Notes"]);
+        assert_eq!(suggested("[[Meting Notes]]"), vec!["Meeting Notes"]);
+        assert_eq!(
+            suggested("[[Budget Review]]"),
+            vec!["Quarterly Budget Review Meeting"]
+        );
+        // Letters scattered through a name match it, but score below the cutoff
+        let scattered = fuzzy_match("rdvt", candidates.get(), &Case::Ignore);
+        assert!(!scattered.is_empty());
+        assert!(suggested("[[rdvt]]").is_empty());
+        assert!(suggested("[[Grocery List]]").is_empty());
+        assert_eq!(suggested("[[Plan]]").len(), MAX_SUGGESTIONS);
+    }
+
+    #[test]
+    fn suggestion_candidates_collected_for_unresolved_references() {
+        let (root_dir, settings) = test_settings();
+        let (resolved, unresolved) = (root_dir.join("resolved.md"), root_dir.join("unresolved.md"));
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                (resolved.clone(), "[[unresolved]]"),
+                (unresolved.clone(), "[[missing]]"),
+            ],
+        );
+        let candidates = SuggestionCandidates::new(&vault);
+
+        let uri = Url::from_file_path(&resolved).unwrap();
+        diagnostics(&vault, &settings, (&resolved, &uri), &candidates);
+        assert!(candidates.candidates.get().is_none());
+
+        let uri = Url::from_file_path(&unresolved).unwrap();
+        diagnostics(&vault, &settings, (&unresolved, &uri), &candidates);
+        assert!(candidates.candidates.get().is_some());
+    }
+
+    #[test]
+    fn duplicate_headings_of_any_level() {
+        let heading = |line, level, text: &str| MDHeading {
+            heading_text: text.to_string(),
+            range: Range::new(Position::new(line, 0), Position::new(line, 10)).into(),
+            level: HeadingLevel(level),
+        };
+
+        let headings = [
+            heading(0, 1, "Notes"),
+            heading(1, 2, "Tasks"),
+            heading(2, 3, "Notes"),
+            heading(3, 2, "Done"),
+            heading(4, 4, "tasks"),
+            heading(5, 2, "Notes"),
+        ];
+
+        assert_eq!(
+            duplicate_headings(&headings)
+                .into_iter()
+                .map(|(heading, first)| (heading.range.start.line, first.range.start.line))
+                .collect_vec(),
+            vec![(2, 0), (4, 1), (5, 0)]
+        );
+    }
+
+    #[test]
+    fn active_scope_diagnoses_only_the_changed_file() {
+        let (root_dir, mut settings) = test_settings();
+        let (a, b) = (root_dir.join("a.md"), root_dir.join("b.md"));
+        let opened_files = HashSet::from([a.clone(), b.clone()]);
+
+        let diagnosed = |settings: &Settings, changed: Option<&PathBuf>| {
+            diagnosed_files(settings, &opened_files, changed.map(|path| path.as_path()))
+                .into_iter()
+                .sorted()
+                .cloned()
+                .collect_vec()
+        };
+
+        settings.diagnostics_scope = DiagnosticsScope::Active;
+        assert_eq!(diagnosed(&settings, Some(&a)), vec![a.clone()]);
+        assert_eq!(diagnosed(&settings, None), vec![a.clone(), b.clone()]);
+
+        settings.diagnostics_scope = DiagnosticsScope::Open;
+        assert_eq!(diagnosed(&settings, Some(&a)), vec![a.clone(), b.clone()]);
+    }
+
+    #[test]
+    fn background_diagnosed_files_are_bounded() {
+        let (root_dir, mut settings) = test_settings();
+        settings.background_diagnostics_max_files = 2;
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            ["c.md", "a.md", "b.md"].map(|name| (root_dir.join(name), "")),
+        );
+
+        assert_eq!(
+            background_diagnosed_files(&vault, &settings),
+            vec![root_dir.join("a.md"), root_dir.join("b.md")]
+        );
+    }
+}
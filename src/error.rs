@@ -0,0 +1,77 @@
+use std::fmt;
+
+use tower_lsp::{
+    jsonrpc::{Error, ErrorCode},
+    lsp_types::Url,
+};
+
+/// Failures of requests to the server, with messages that clients can show or log
+#[derive(Debug)]
+pub enum BackendError {
+    /// The vault is read when the server is initialized; requests before that have nothing to answer from
+    VaultNotInitialized,
+    SettingsNotLoaded,
+    /// The uri of a request is not a file on disk
+    InvalidUri(Url),
+    ReadVault(std::io::Error),
+    ReadSettings(anyhow::Error),
+}
+
+impl BackendError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            BackendError::VaultNotInitialized | BackendError::ReadVault(_) => {
+                ErrorCode::ServerError(0)
+            }
+            BackendError::SettingsNotLoaded | BackendError::ReadSettings(_) => {
+                ErrorCode::ServerError(1)
+            }
+            BackendError::InvalidUri(_) => ErrorCode::InvalidParams,
+        }
+    }
+}
+
+// Messages are only ever written to the formatter, so formatting never fails; `to_string` panics on errors from `Display`
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::VaultNotInitialized => write!(f, "vault not initialized"),
+            BackendError::SettingsNotLoaded => write!(f, "settings not loaded"),
+            BackendError::InvalidUri(uri) => write!(f, "{uri} is not the uri of a file"),
+            BackendError::ReadVault(error) => write!(f, "failed to read the vault: {error}"),
+            BackendError::ReadSettings(error) => write!(f, "failed to read settings: {error:?}"),
+        }
+    }
+}
+
+impl From<BackendError> for Error {
+    fn from(error: BackendError) -> Error {
+        Error {
+            code: error.code(),
+            message: error.to_string().into(),
+            data: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::jsonrpc::{Error, ErrorCode};
+
+    use super::BackendError;
+
+    #[test]
+    fn errors_have_messages() {
+        let error = Error::from(BackendError::VaultNotInitialized);
+        assert_eq!(error.code, ErrorCode::ServerError(0));
+        assert_eq!(error.message, "vault not initialized");
+
+        let uri = "https://example.com/note".parse().unwrap();
+        let error = Error::from(BackendError::InvalidUri(uri));
+        assert_eq!(error.code, ErrorCode::InvalidParams);
+        assert_eq!(
+            error.message,
+            "https://example.com/note is not the uri of a file"
+        );
+    }
+}
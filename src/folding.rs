@@ -0,0 +1,26 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::vault::Vault;
+
+/// Callouts fold to their first line, which has their type and title; callouts nested in them fold on their own
+pub fn folding_ranges(vault: &Vault, path: &Path) -> Option<Vec<FoldingRange>> {
+    let md_file = vault.md_files.get(path)?;
+
+    Some(
+        md_file
+            .callouts
+            .iter()
+            .filter(|callout| callout.range.end.line > callout.range.start.line)
+            .map(|callout| FoldingRange {
+                start_line: callout.range.start.line,
+                start_character: None,
+                end_line: callout.range.end.line,
+                end_character: None,
+                kind: Some(FoldingRangeKind::Region),
+                collapsed_text: None,
+            })
+            .collect(),
+    )
+}
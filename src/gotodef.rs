@@ -2,35 +2,45 @@ use std::path::Path;
 
 use tower_lsp::lsp_types::{Location, Position, Url};
 
-use crate::vault::{Referenceable, Vault};
+use crate::config::Settings;
+use crate::vault::{MDHeading, Reference, Referenceable, Vault};
 
 pub fn goto_definition(
     vault: &Vault,
     cursor_position: Position,
     path: &Path,
+    settings: &Settings,
 ) -> Option<Vec<Location>> {
     // First, find the link that the cursor is in. Get a links for the file and match the cursor position up to one of them
     let reference = vault.select_reference_at_position(path, cursor_position)?;
     // Now we have the reference text. We need to find where this is actually referencing, or if it is referencing anything.
     // Lets get all of the referenceable nodes
 
-    let referenceables = vault.select_referenceables_for_reference(reference, path);
+    let referenceables = match (reference, &settings.tag_index_note) {
+        (Reference::Tag(..), Some(tag_index_note)) => vault
+            .select_file_referenceable(tag_index_note)
+            .into_iter()
+            .collect(),
+        _ => vault.select_referenceables_for_reference(reference, path),
+    };
 
     Some(
         referenceables
             .into_iter()
             .filter_map(|linkable| {
                 let range = match linkable {
-                    Referenceable::File(..) => tower_lsp::lsp_types::Range {
-                        start: Position {
-                            line: 0,
-                            character: 0,
-                        },
-                        end: Position {
-                            line: 0,
-                            character: 1,
-                        },
-                    },
+                    Referenceable::File(..) | Referenceable::Attachment(..) => {
+                        tower_lsp::lsp_types::Range {
+                            start: Position {
+                                line: 0,
+                                character: 0,
+                            },
+                            end: Position {
+                                line: 0,
+                                character: 1,
+                            },
+                        }
+                    }
                     _ => *linkable.get_range()?,
                 };
 
@@ -42,3 +52,46 @@ pub fn goto_definition(
             .collect(),
     )
 }
+
+/// The location of the heading of the section containing the cursor, for the custom `moxide/sectionHeading` request.
+/// This is structural navigation rather than following a link.
+pub fn section_heading(vault: &Vault, cursor_position: Position, path: &Path) -> Option<Location> {
+    let headings = vault.select_headings(path)?;
+    let heading = enclosing_heading(headings, cursor_position.line)?;
+
+    Some(Location {
+        uri: Url::from_file_path(path).ok()?,
+        range: *heading.range,
+    })
+}
+
+/// The nearest heading at or above `line`; content before the first heading is not in any section
+fn enclosing_heading(headings: &[MDHeading], line: u32) -> Option<&MDHeading> {
+    headings
+        .iter()
+        .filter(|heading| heading.range.start.line <= line)
+        .max_by_key(|heading| heading.range.start.line)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vault::MDHeading;
+
+    use super::enclosing_heading;
+
+    #[test]
+    fn enclosing_heading_of_line() {
+        let text = "intro\n# One\ntext\n## Two\n\nmore text\n# Three";
+        let headings = MDHeading::new(text).collect::<Vec<_>>();
+
+        let heading_text =
+            |line| enclosing_heading(&headings, line).map(|heading| heading.heading_text.as_str());
+
+        assert_eq!(heading_text(0), None);
+        assert_eq!(heading_text(1), Some("One"));
+        assert_eq!(heading_text(2), Some("One"));
+        assert_eq!(heading_text(5), Some("Two"));
+        assert_eq!(heading_text(6), Some("Three"));
+        assert_eq!(enclosing_heading(&[], 3), None);
+    }
+}
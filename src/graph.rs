@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::vault::{Reference, Referenceable, Vault};
+
+/// Params of the `moxide/noteGraph` request
+#[derive(Deserialize, Debug)]
+pub struct NoteGraphParams {
+    pub uri: Url,
+}
+
+/// The links out of a note and the backlinks into it
+#[derive(Serialize, Debug)]
+pub struct NoteGraph {
+    pub outgoing: Vec<GraphEdge>,
+    pub incoming: Vec<GraphEdge>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+    /// The file containing the link
+    pub source: Url,
+    /// The range of the link in the source file
+    pub range: Range,
+    /// The file the link resolves to; none for unresolved links
+    pub target: Option<Url>,
+    pub kind: LinkKind,
+    pub reference_text: String,
+    pub unresolved: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkKind {
+    File,
+    Heading,
+    Block,
+    LinkRef,
+}
+
+impl LinkKind {
+    /// The kind of a link between notes; tags and footnotes are not edges in the graph
+    fn of(reference: &Reference) -> Option<LinkKind> {
+        match reference {
+            Reference::WikiFileLink(..) | Reference::MDFileLink(..) => Some(LinkKind::File),
+            Reference::WikiHeadingLink(..) | Reference::MDHeadingLink(..) => {
+                Some(LinkKind::Heading)
+            }
+            Reference::WikiIndexedBlockLink(..) | Reference::MDIndexedBlockLink(..) => {
+                Some(LinkKind::Block)
+            }
+            Reference::LinkRef(..) => Some(LinkKind::LinkRef),
+            Reference::Tag(..) | Reference::Footnote(..) => None,
+        }
+    }
+}
+
+pub fn note_graph(vault: &Vault, path: &Path) -> Option<NoteGraph> {
+    let outgoing = vault
+        .select_references(Some(path))?
+        .into_iter()
+        .filter_map(|(source, reference)| {
+            let kind = LinkKind::of(reference)?;
+
+            let target = vault
+                .select_referenceables_for_reference(reference, source)
+                .into_iter()
+                .next();
+
+            let (target, unresolved) = match target {
+                Some(target) if !target.is_unresolved() => {
+                    (Url::from_file_path(target.get_path()).ok(), false)
+                }
+                _ => (None, true),
+            };
+
+            Some(GraphEdge {
+                source: Url::from_file_path(source).ok()?,
+                range: *reference.data().range,
+                target,
+                kind,
+                reference_text: reference.data().reference_text.clone(),
+                unresolved,
+            })
+        })
+        .collect();
+
+    let (file_path, md_file) = vault.md_files.get_key_value(path)?;
+
+    let incoming = vault
+        .select_references_for_referenceable(&Referenceable::File(file_path, md_file))?
+        .into_iter()
+        .filter_map(|(source, reference)| {
+            Some(GraphEdge {
+                source: Url::from_file_path(source).ok()?,
+                range: *reference.data().range,
+                target: Url::from_file_path(path).ok(),
+                kind: LinkKind::of(reference)?,
+                reference_text: reference.data().reference_text.clone(),
+                unresolved: false,
+            })
+        })
+        .collect();
+
+    Some(NoteGraph { outgoing, incoming })
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::vault::{test_settings, Vault};
+
+    use super::{note_graph, GraphEdge, LinkKind};
+
+    #[test]
+    fn note_graph_edges() {
+        let (root_dir, settings) = test_settings();
+        let note = |name: &str| root_dir.join(format!("{name}.md"));
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                (note("a"), "# A\n[[b]] [[b#Heading]] [[missing]] #tag"),
+                (note("b"), "# Heading\n[[a]]"),
+                (note("c"), "[b](b)"),
+            ],
+        );
+
+        let edges = |edges: Vec<GraphEdge>| {
+            edges
+                .into_iter()
+                .map(|edge| {
+                    let name = |uri: Url| {
+                        uri.to_file_path()
+                            .unwrap()
+                            .file_stem()
+                            .unwrap()
+                            .to_string_lossy()
+                            .to_string()
+                    };
+                    (
+                        name(edge.source),
+                        (edge.range.start.line, edge.range.start.character),
+                        edge.target.map(name),
+                        edge.kind,
+                        edge.reference_text,
+                        edge.unresolved,
+                    )
+                })
+                .sorted_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)))
+                .collect_vec()
+        };
+
+        let edge = |source: &str, start, target: Option<&str>, kind, text: &str, unresolved| {
+            (
+                source.to_string(),
+                start,
+                target.map(String::from),
+                kind,
+                text.to_string(),
+                unresolved,
+            )
+        };
+
+        let graph = note_graph(&vault, &note("a")).unwrap();
+        assert_eq!(
+            edges(graph.outgoing),
+            [
+                edge("a", (1, 0), Some("b"), LinkKind::File, "b", false),
+                edge(
+                    "a",
+                    (1, 6),
+                    Some("b"),
+                    LinkKind::Heading,
+                    "b#Heading",
+                    false
+                ),
+                edge("a", (1, 20), None, LinkKind::File, "missing", true),
+            ]
+        );
+
+        let graph = note_graph(&vault, &note("b")).unwrap();
+        assert_eq!(
+            edges(graph.incoming),
+            [
+                edge("a", (1, 0), Some("b"), LinkKind::File, "b", false),
+                edge(
+                    "a",
+                    (1, 6),
+                    Some("b"),
+                    LinkKind::Heading,
+                    "b#Heading",
+                    false
+                ),
+                edge("c", (0, 0), Some("b"), LinkKind::File, "b", false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Position};
+
+use crate::vault::{Reference, Referenceable, Vault};
+
+/// Highlight the referenceable under the cursor, or the one the link under the cursor points to, along with all
+/// references to it in the current file.
+pub fn document_highlights(
+    vault: &Vault,
+    cursor_position: Position,
+    path: &Path,
+) -> Option<Vec<DocumentHighlight>> {
+    let file_references = vault.select_references(Some(path))?;
+
+    let referenceables = match (
+        vault.select_referenceable_at_position(path, cursor_position),
+        vault.select_reference_at_position(path, cursor_position),
+    ) {
+        // Only the exact tag is highlighted; not its parent or child tags
+        (_, Some(Reference::Tag(tag))) => {
+            return Some(
+                file_references
+                    .into_iter()
+                    .filter(|(_, reference)| match reference {
+                        Reference::Tag(other) => other.reference_text == tag.reference_text,
+                        _ => false,
+                    })
+                    .map(|(_, reference)| DocumentHighlight {
+                        range: *reference.data().range,
+                        kind: Some(DocumentHighlightKind::TEXT),
+                    })
+                    .collect(),
+            )
+        }
+        // The cursor is not on a heading, block, or link; there is nothing to highlight
+        (Some(Referenceable::File(..)), None) | (None, None) => return None,
+        (Some(referenceable), None) => vec![referenceable],
+        (_, Some(reference)) => vault.select_referenceables_for_reference(reference, path),
+    };
+
+    let definitions = referenceables
+        .iter()
+        .filter(|referenceable| referenceable.get_path() == path)
+        .filter_map(|referenceable| {
+            Some(DocumentHighlight {
+                range: *referenceable.get_range()?,
+                kind: Some(DocumentHighlightKind::WRITE),
+            })
+        });
+
+    let references = file_references
+        .into_iter()
+        .filter(|(reference_path, reference)| {
+            referenceables.iter().any(|referenceable| {
+                referenceable.matches_reference(vault.root_dir(), reference, reference_path)
+                    && vault.matches_heading_chain(reference, referenceable)
+                    && vault.matches_case(reference, referenceable)
+            })
+        })
+        .map(|(_, reference)| DocumentHighlight {
+            range: *reference.data().range,
+            kind: Some(DocumentHighlightKind::READ),
+        });
+
+    Some(definitions.chain(references).collect_vec())
+}
@@ -1,11 +1,11 @@
 use std::path::Path;
 
-use tower_lsp::lsp_types::{Hover, HoverContents, HoverParams};
+use tower_lsp::lsp_types::{Hover, HoverContents, HoverParams, MarkupContent, MarkupKind, Url};
 
 use crate::{
     config::Settings,
     ui::{preview_reference, preview_referenceable},
-    vault::Vault,
+    vault::{Reference, Vault},
 };
 
 pub fn hover(
@@ -24,10 +24,27 @@ pub fn hover(
         vault.select_reference_at_position(path, cursor_position),
         vault.select_referenceable_at_position(path, cursor_position),
     ) {
-        (Some(reference), _) => preview_reference(vault, path, reference).map(|markup| Hover {
-            contents: HoverContents::Markup(markup),
-            range: None,
-        }),
+        (Some(Reference::Tag(..)), _) if settings.tag_index_note.is_some() => {
+            let index_note =
+                vault.select_file_referenceable(settings.tag_index_note.as_deref()?)?;
+            let uri = Url::from_file_path(index_note.get_path()).ok()?;
+            let name = index_note.display_name()?;
+            let preview = preview_referenceable(vault, &index_note, settings)?;
+
+            Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: format!("Tag index: [{}]({})\n\n{}", name, uri, preview.value),
+                }),
+                range: None,
+            })
+        }
+        (Some(reference), _) => {
+            preview_reference(vault, path, reference, settings).map(|markup| Hover {
+                contents: HoverContents::Markup(markup),
+                range: None,
+            })
+        }
         _ => None,
     }
 }
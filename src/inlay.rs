@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::{InlayHint, InlayHintLabel, Range};
+
+use crate::{
+    config::Settings,
+    vault::{Referenceable, Vault},
+};
+
+/// Hints at the end of the headings in `range` with the number of links to them, as in `3 backlinks`
+pub fn backlink_count_hints(
+    vault: &Vault,
+    path: &Path,
+    range: Range,
+    settings: &Settings,
+) -> Vec<InlayHint> {
+    vault
+        .select_referenceable_nodes(Some(path))
+        .into_iter()
+        .filter_map(|referenceable| {
+            let Referenceable::Heading(_, heading) = referenceable else {
+                return None;
+            };
+
+            if heading.range.start.line < range.start.line
+                || heading.range.end.line > range.end.line
+            {
+                return None;
+            }
+
+            let count = vault
+                .select_references_for_referenceable(&referenceable)
+                .map_or(0, |references| references.len());
+
+            backlink_count_label(count, settings.backlink_count_hints_zero).map(|label| InlayHint {
+                position: heading.range.end,
+                label: InlayHintLabel::String(label),
+                kind: None,
+                data: None,
+                tooltip: None,
+                text_edits: None,
+                padding_left: Some(true),
+                padding_right: None,
+            })
+        })
+        .collect()
+}
+
+/// Headings without backlinks get no hint unless `show_zero` is set, so that most headings stay uncluttered
+fn backlink_count_label(count: usize, show_zero: bool) -> Option<String> {
+    match count {
+        0 if !show_zero => None,
+        1 => Some("1 backlink".to_string()),
+        n => Some(format!("{} backlinks", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backlink_count_label;
+
+    #[test]
+    fn backlink_count_labels() {
+        assert_eq!(backlink_count_label(0, false), None);
+        assert_eq!(
+            backlink_count_label(0, true),
+            Some("0 backlinks".to_string())
+        );
+        assert_eq!(
+            backlink_count_label(1, false),
+            Some("1 backlink".to_string())
+        );
+        assert_eq!(
+            backlink_count_label(3, false),
+            Some("3 backlinks".to_string())
+        );
+    }
+}
@@ -5,7 +5,9 @@ macro_rules! params_path {
         let path_result = $x.text_document.uri.to_file_path();
 
         let Ok(path) = path_result else {
-            return Result::Err(Error::new(ErrorCode::ServerError(0)));
+            return Result::Err(
+                $crate::error::BackendError::InvalidUri($x.text_document.uri.clone()).into(),
+            );
         };
 
         Result::Ok(path)
@@ -20,7 +22,10 @@ macro_rules! params_position_path {
         let path_result = $x.text_document_position.text_document.uri.to_file_path();
 
         let Ok(path) = path_result else {
-            return Result::Err(Error::new(ErrorCode::ServerError(0)));
+            return Result::Err($crate::error::BackendError::InvalidUri(
+                $x.text_document_position.text_document.uri.clone(),
+            )
+            .into());
         };
 
         Result::Ok(path)
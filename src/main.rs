@@ -1,25 +1,30 @@
 use std::collections::HashSet;
 use std::ops::{Deref, DerefMut};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use completion::get_completions;
 use config::{EmbeddedBlockTransclusionLength, Settings};
-use diagnostics::diagnostics;
+use diagnostics::{background_diagnosed_files, diagnosed_files, diagnostics, SuggestionCandidates};
+use error::BackendError;
 use itertools::Itertools;
 use rayon::prelude::*;
 use references::references;
 use serde_json::Value;
 use symbol::{document_symbol, workspace_symbol};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 
-use gotodef::goto_definition;
-use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
+use gotodef::{goto_definition, section_heading};
+use graph::{note_graph, NoteGraph, NoteGraphParams};
+use highlight::document_highlights;
+use tower_lsp::jsonrpc::{Error, Result};
 
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
-use vault::{Preview, Rangeable, Reference, Vault};
+use vault::{is_attachment, Preview, Rangeable, Reference, Vault, ATTACHMENT_EXTENSIONS};
 
+mod check;
 mod codeactions;
 mod codelens;
 mod commands;
@@ -27,12 +32,18 @@ mod completion;
 mod config;
 mod daily;
 mod diagnostics;
+mod error;
+mod folding;
 mod gotodef;
+mod graph;
+mod highlight;
 mod hover;
+mod inlay;
 mod macros;
 mod references;
 mod rename;
 mod symbol;
+mod tasks;
 mod tokens;
 mod ui;
 mod vault;
@@ -43,6 +54,8 @@ struct Backend {
     vault: Arc<RwLock<Option<Vault>>>,
     opened_files: Arc<RwLock<HashSet<PathBuf>>>,
     settings: Arc<RwLock<Option<Settings>>>,
+    sent_tokens: Arc<RwLock<tokens::SentTokens>>,
+    background_diagnostics: Arc<RwLock<Option<JoinHandle<()>>>>,
 }
 
 struct TextDocumentItem {
@@ -81,7 +94,7 @@ impl Backend {
             .log_message(MessageType::WARNING, "Update Vault Done")
             .await;
 
-        match self.publish_diagnostics().await {
+        match self.publish_diagnostics(Some(&path)).await {
             Ok(_) => (),
             Err(e) => {
                 self.client
@@ -98,6 +111,71 @@ impl Backend {
         }
     }
 
+    /// Update only the files reported by the file watcher, rather than reconstructing the whole vault
+    async fn update_vault_from_disk(&self, changes: Vec<FileEvent>) {
+        let timer = std::time::Instant::now();
+
+        let Ok(settings) = self.bind_settings(|settings| Ok(settings.clone())).await else {
+            return;
+        };
+
+        let paths = changes
+            .iter()
+            .filter_map(|change| change.uri.to_file_path().ok())
+            .collect_vec();
+
+        // Changes to folders (such as deleting or renaming one) affect every file in them
+        if paths.len() != changes.len()
+            || paths.iter().any(|path| {
+                path.extension().and_then(|ext| ext.to_str()) != Some("md") && !is_attachment(path)
+            })
+        {
+            return self.reconstruct_vault().await;
+        }
+
+        {
+            let _ = self
+                .bind_vault_mut(|vault| {
+                    for path in &paths {
+                        Vault::update_vault_from_disk(&settings, vault, path);
+                    }
+
+                    Ok(())
+                })
+                .await;
+        }
+
+        self.client
+            .log_message(
+                MessageType::WARNING,
+                format!(
+                    "Updated {} changed files in {}ms",
+                    paths.len(),
+                    timer.elapsed().as_millis()
+                ),
+            )
+            .await;
+
+        match self.publish_diagnostics(None).await {
+            Ok(_) => (),
+            Err(e) => {
+                self.client
+                    .log_message(
+                        MessageType::ERROR,
+                        format!(
+                            "Failed calculating diagnostics on watched file change {:?}",
+                            e
+                        ),
+                    )
+                    .await
+            }
+        };
+
+        if settings.semantic_tokens {
+            let _ = self.client.semantic_tokens_refresh().await;
+        }
+    }
+
     async fn reconstruct_vault(&self) {
         let progress = self
             .client
@@ -114,17 +192,16 @@ impl Backend {
         {
             let _ = self
                 .bind_vault_mut(|vault| {
-                    let Ok(new_vault) = Vault::construct_vault(&settings, vault.root_dir()) else {
-                        return Err(Error::new(ErrorCode::ServerError(0)));
-                    };
-
-                    *vault = new_vault;
+                    *vault = Vault::construct_vault(&settings, vault.root_dir(), Some(vault))
+                        .map_err(BackendError::ReadVault)?;
 
                     Ok(())
                 })
                 .await;
         }
 
+        self.sent_tokens.write().await.clear();
+
         let elapsed = timer.elapsed();
 
         progress
@@ -140,7 +217,7 @@ impl Backend {
                 .await;
         }
 
-        match self.publish_diagnostics().await {
+        match self.publish_diagnostics(None).await {
             Ok(_) => (),
             Err(e) => {
                 self.client
@@ -155,37 +232,43 @@ impl Backend {
             }
         };
 
+        self.start_background_diagnostics().await;
+
         if settings.semantic_tokens {
             let _ = self.client.semantic_tokens_refresh().await;
         }
     }
 
-    async fn publish_diagnostics(&self) -> Result<()> {
+    /// Publish diagnostics for the opened files. `changed` is the file that triggered the update, if any;
+    /// with the `active` diagnostics scope, only that file is recomputed.
+    async fn publish_diagnostics(&self, changed: Option<&Path>) -> Result<()> {
         let timer = std::time::Instant::now();
 
         self.client
             .log_message(MessageType::WARNING, "Diagnostics Started")
             .await;
 
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+
         let uris = self
             .bind_opened_files(|files| {
-                Ok(files
+                Ok(diagnosed_files(&settings, files, changed)
                     .into_par_iter()
                     .filter_map(|url| Url::from_file_path(url).ok())
                     .collect::<Vec<_>>())
             })
             .await?;
 
-        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
-
         let diagnostics = self
             .bind_vault(|vault| {
+                let candidates = SuggestionCandidates::new(vault);
+
                 Ok(uris
                     .par_iter()
                     .filter_map(|uri| {
                         let path = uri.to_file_path().ok()?;
 
-                        diagnostics(vault, &settings, (&path, uri))
+                        diagnostics(vault, &settings, (&path, uri), &candidates)
                             .map(|diags| (uri.clone(), diags))
                     })
                     .collect::<Vec<_>>())
@@ -212,6 +295,69 @@ impl Backend {
         Ok(())
     }
 
+    /// Publish diagnostics for the files of the vault that are not opened, in batches with pauses between them so that
+    /// the client is not flooded; files without diagnostics are skipped. A pass still running is cancelled when another
+    /// starts or the server shuts down.
+    async fn start_background_diagnostics(&self) {
+        const BATCH_SIZE: usize = 50;
+        const BATCH_PAUSE: std::time::Duration = std::time::Duration::from_millis(100);
+
+        let Ok(settings) = self.bind_settings(|settings| Ok(settings.clone())).await else {
+            return;
+        };
+
+        if !settings.background_diagnostics {
+            return;
+        }
+
+        let (client, vault, opened_files) = (
+            self.client.clone(),
+            self.vault.clone(),
+            self.opened_files.clone(),
+        );
+
+        let pass = tokio::spawn(async move {
+            let paths = match vault.read().await.deref() {
+                Some(vault) => background_diagnosed_files(vault, &settings),
+                None => return,
+            };
+
+            for batch in paths.chunks(BATCH_SIZE) {
+                // Opened files are diagnosed as they change
+                let opened = opened_files.read().await.clone();
+
+                let diagnostics = match vault.read().await.deref() {
+                    Some(vault) => {
+                        let candidates = SuggestionCandidates::new(vault);
+
+                        batch
+                            .par_iter()
+                            .filter(|path| !opened.contains(*path))
+                            .filter_map(|path| {
+                                let uri = Url::from_file_path(path).ok()?;
+
+                                diagnostics(vault, &settings, (path, &uri), &candidates)
+                                    .filter(|diags| !diags.is_empty())
+                                    .map(|diags| (uri, diags))
+                            })
+                            .collect::<Vec<_>>()
+                    }
+                    None => return,
+                };
+
+                for (uri, diags) in diagnostics {
+                    client.publish_diagnostics(uri, diags, None).await;
+                }
+
+                tokio::time::sleep(BATCH_PAUSE).await;
+            }
+        });
+
+        if let Some(previous) = self.background_diagnostics.write().await.replace(pass) {
+            previous.abort();
+        }
+    }
+
     /// This is an FP reference. Lets say that there is monad around the vault of type Result<Vault>, representing accesing the RwLock arond it in async
     /// This function will extract the vautl result, apply the given function which will return another monad (which I am asuming to be another result)
     /// The function then returns this monad
@@ -224,7 +370,7 @@ impl Backend {
     async fn bind_vault<T>(&self, callback: impl FnOnce(&Vault) -> Result<T>) -> Result<T> {
         let guard = self.vault.read().await;
         let Some(vault) = guard.deref() else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
+            return Err(BackendError::VaultNotInitialized.into());
         };
 
         callback(vault)
@@ -246,7 +392,7 @@ impl Backend {
 
         let mut guard = self.vault.write().await;
         let Some(ref mut vault) = *guard else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
+            return Err(BackendError::VaultNotInitialized.into());
         };
 
         callback(vault)
@@ -255,12 +401,42 @@ impl Backend {
     async fn bind_settings<T>(&self, callback: impl FnOnce(&Settings) -> Result<T>) -> Result<T> {
         let guard = self.settings.read().await;
         let Some(settings) = guard.deref() else {
-            return Err(Error::new(ErrorCode::ServerError(1)));
+            return Err(BackendError::SettingsNotLoaded.into());
         };
 
         callback(settings)
     }
 
+    /// Handle the custom `moxide/noteGraph` request: the links out of and backlinks into a note
+    async fn note_graph(&self, params: NoteGraphParams) -> Result<Option<NoteGraph>> {
+        self.bind_vault(|vault| {
+            let Ok(path) = params.uri.to_file_path() else {
+                return Err(BackendError::InvalidUri(params.uri.clone()).into());
+            };
+
+            Ok(note_graph(vault, &path))
+        })
+        .await
+    }
+
+    /// Handle the custom `moxide/sectionHeading` request: the heading of the section containing the cursor
+    async fn section_heading(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<Location>> {
+        self.bind_vault(|vault| {
+            let path = params_path!(params)?;
+
+            Ok(section_heading(vault, params.position, &path))
+        })
+        .await
+    }
+
+    /// Handle the custom `moxide/tasks` request: the open tasks of the vault
+    async fn tasks(&self) -> Result<Vec<tasks::Task>> {
+        self.bind_vault(|vault| Ok(tasks::open_tasks(vault))).await
+    }
+
     async fn bind_opened_files<T>(
         &self,
         callback: impl Fn(&HashSet<PathBuf>) -> Result<T>,
@@ -295,10 +471,8 @@ impl Backend {
 impl LanguageServer for Backend {
     async fn initialize(&self, i: InitializeParams) -> Result<InitializeResult> {
         let root_dir = match i.root_uri {
-            Some(uri) => uri
-                .to_file_path()
-                .or(Err(Error::new(ErrorCode::InvalidParams)))?,
-            None => std::env::current_dir().or(Err(Error::new(ErrorCode::InvalidParams)))?,
+            Some(uri) => uri.to_file_path().or(Err(BackendError::InvalidUri(uri)))?,
+            None => std::env::current_dir().map_err(BackendError::ReadVault)?,
         };
 
         let read_settings = match Settings::new(&root_dir, &i.capabilities) {
@@ -310,13 +484,12 @@ impl LanguageServer for Backend {
                         format!("Failed to read settings {:?}", e),
                     )
                     .await;
-                return Err(Error::new(ErrorCode::ServerError(1)));
+                return Err(BackendError::ReadSettings(e).into());
             }
         };
 
-        let Ok(vault) = Vault::construct_vault(&read_settings, &root_dir) else {
-            return Err(Error::new(ErrorCode::ServerError(0)));
-        };
+        let vault = Vault::construct_vault(&read_settings, &root_dir, None)
+            .map_err(BackendError::ReadVault)?;
         let mut value = self.vault.write().await;
         *value = Some(vault);
 
@@ -358,9 +531,11 @@ impl LanguageServer for Backend {
                 inlay_hint_provider: Some(OneOf::Left(true)),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
                 rename_provider: Some(OneOf::Left(true)),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 workspace: Some(WorkspaceServerCapabilities {
@@ -376,39 +551,40 @@ impl LanguageServer for Backend {
                     resolve_provider: None,
                 }),
                 execute_command_provider: Some(ExecuteCommandOptions {
-                    commands: vec![
-                        "apply_edits".into(),
-                        "jump".into(),
-                        "tomorrow".into(),
-                        "today".into(),
-                        "yesterday".into(),
-                        "last friday".into(),
-                        "last saturday".into(),
-                        "last sunday".into(),
-                        "last monday".into(),
-                        "last tuesday".into(),
-                        "last wednesday".into(),
-                        "last thursday".into(),
-                        "next friday".into(),
-                        "next saturday".into(),
-                        "next sunday".into(),
-                        "next monday".into(),
-                        "next tuesday".into(),
-                        "next wednesday".into(),
-                        "next thursday".into(),
-                    ],
+                    commands: [
+                        "apply_edits".to_string(),
+                        "jump".to_string(),
+                        "normalize_daily_notes".to_string(),
+                        "fix_broken_anchors".to_string(),
+                        "build_agenda".to_string(),
+                        "daily".to_string(),
+                        "list_broken_links".to_string(),
+                        "insert_backlink".to_string(),
+                        "renumber_footnotes".to_string(),
+                        "toggle_task".to_string(),
+                    ]
+                    .into_iter()
+                    .chain(
+                        settings
+                            .iter()
+                            .flat_map(daily::relative_dates)
+                            .map(|(name, _)| name),
+                    )
+                    .chain(
+                        daily::RELATIVE_PERIODS
+                            .iter()
+                            .map(|(name, ..)| name.to_string()),
+                    )
+                    .collect(),
                     ..Default::default()
                 }),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
-                            full: Some(SemanticTokensFullOptions::Bool(true)),
-                            range: Some(false),
+                            full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                            range: Some(true),
                             legend: SemanticTokensLegend {
-                                token_types: vec![
-                                    SemanticTokenType::DECORATOR,
-                                    SemanticTokenType::COMMENT,
-                                ],
+                                token_types: tokens::TOKEN_TYPES.to_vec(),
                                 token_modifiers: vec![
                                     SemanticTokenModifier::DECLARATION,
                                     SemanticTokenModifier::DEPRECATED,
@@ -423,9 +599,26 @@ impl LanguageServer for Backend {
         });
     }
 
+    /// Stop publishing diagnostics and clear those already published; the server stops once the client sends `exit`,
+    /// after the response to this request has been written
     async fn shutdown(&self) -> Result<()> {
-        // TODO: remove all code lenses
-        std::process::exit(0);
+        if let Some(pass) = self.background_diagnostics.write().await.take() {
+            pass.abort();
+        }
+
+        // Diagnostics are only published for opened files, so none are published once they are forgotten
+        let opened = self
+            .bind_opened_files_mut(|files| Ok(std::mem::take(files)))
+            .await?;
+
+        for uri in opened.iter().filter_map(|path| Url::from_file_path(path).ok()) {
+            self.client.publish_diagnostics(uri, vec![], None).await;
+        }
+
+        // Requests after shutdown are rejected, so the refreshed code lenses come back empty
+        let _ = self.client.code_lens_refresh().await;
+
+        Ok(())
     }
 
     async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
@@ -453,10 +646,20 @@ impl LanguageServer for Backend {
         };
 
         let value = serde_json::to_value(DidChangeWatchedFilesRegistrationOptions {
-            watchers: vec![FileSystemWatcher {
-                glob_pattern: GlobPattern::String("**/*.md".into()),
-                kind: None,
-            }],
+            watchers: vec![
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String("**/*.md".into()),
+                    kind: None,
+                },
+                // Links to attachments resolve only while the files exist
+                FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(format!(
+                        "**/*.{{{}}}",
+                        ATTACHMENT_EXTENSIONS.join(",")
+                    )),
+                    kind: None,
+                },
+            ],
         })
         .unwrap();
 
@@ -470,9 +673,13 @@ impl LanguageServer for Backend {
             .register_capability(vec![registration])
             .await
             .unwrap();
+
+        self.start_background_diagnostics().await;
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let opened_path = params.text_document.uri.to_file_path().ok();
+
         {
             let _new_files = self
                 .bind_opened_files_mut(|files| {
@@ -496,7 +703,7 @@ impl LanguageServer for Backend {
             .await; // usually, this is not necesary; however some may start the LS without saving a changed file, so it is necessary
         } // drop the lock
 
-        match self.publish_diagnostics().await {
+        match self.publish_diagnostics(opened_path.as_deref()).await {
             Ok(_) => (),
             Err(e) => {
                 self.client
@@ -523,6 +730,11 @@ impl LanguageServer for Backend {
                 .log_message(MessageType::LOG, format!("Remove file {:?}", file))
                 .await;
         }
+
+        self.sent_tokens
+            .write()
+            .await
+            .forget(&params.text_document.uri);
     }
 
     async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
@@ -533,20 +745,24 @@ impl LanguageServer for Backend {
         .await;
     }
 
-    async fn did_change_watched_files(&self, _params: DidChangeWatchedFilesParams) {
-        self.reconstruct_vault().await
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        self.update_vault_from_disk(params.changes).await
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
         self.bind_vault(|vault| {
             let path = params_path!(params.text_document_position_params)?;
-            Ok(
-                goto_definition(vault, params.text_document_position_params.position, &path)
-                    .map(GotoDefinitionResponse::Array),
+            Ok(goto_definition(
+                vault,
+                params.text_document_position_params.position,
+                &path,
+                &settings,
             )
+            .map(GotoDefinitionResponse::Array))
         })
         .await
     }
@@ -563,6 +779,21 @@ impl LanguageServer for Backend {
         .await
     }
 
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        self.bind_vault(|vault| {
+            let path = params_path!(params.text_document_position_params)?;
+            Ok(document_highlights(
+                vault,
+                params.text_document_position_params.position,
+                &path,
+            ))
+        })
+        .await
+    }
+
     async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
         self.client
             .log_message(MessageType::WARNING, "Completions Started")
@@ -575,9 +806,9 @@ impl LanguageServer for Backend {
             .bind_opened_files(|files| Ok(files.clone().into_iter().collect::<Box<[_]>>()))
             .await?;
 
-        let Ok(settings) = self.bind_settings(|settings| Ok(settings.to_owned())).await else {
-            return Err(Error::new(ErrorCode::ServerError(2)));
-        }; // TODO: this is bad
+        let settings = self
+            .bind_settings(|settings| Ok(settings.to_owned()))
+            .await?;
 
         let res = self
             .bind_vault(|vault| Ok(get_completions(vault, &files, &params, &path, &settings)))
@@ -615,6 +846,196 @@ impl LanguageServer for Backend {
 
                 Ok(None)
             }
+            ExecuteCommandParams { command, .. } if *command == *"normalize_daily_notes" => {
+                let (renamed, edit) = self
+                    .bind_vault(|vault| Ok(commands::normalize_daily_notes(vault, &settings)))
+                    .await?;
+
+                if renamed > 0 {
+                    self.client.apply_edit(edit).await?;
+                }
+
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!("Renamed {renamed} daily notes to the daily note format"),
+                    )
+                    .await;
+
+                Ok(None)
+            }
+            ExecuteCommandParams { command, .. } if *command == *"fix_broken_anchors" => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(|arg| serde_json::from_value::<Url>(arg.clone()).ok())
+                else {
+                    return Err(Error::invalid_params("Expected the uri of a file"));
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Err(Error::invalid_params("Expected the uri of a file"));
+                };
+
+                let fixes = self
+                    .bind_vault(|vault| Ok(commands::broken_anchor_fixes(vault, &path)))
+                    .await?;
+
+                if fixes.is_empty() {
+                    self.client
+                        .show_message(MessageType::INFO, "No broken heading links")
+                        .await;
+                    return Ok(None);
+                }
+
+                let report = fixes
+                    .iter()
+                    .map(|fix| format!("{} -> {}", fix.reference_text, fix.heading))
+                    .join(", ");
+
+                let fix_action = MessageActionItem {
+                    title: "Fix".to_string(),
+                    properties: Default::default(),
+                };
+
+                let response = self
+                    .client
+                    .show_message_request(
+                        MessageType::INFO,
+                        format!("Broken heading links: {report}"),
+                        Some(vec![fix_action.clone()]),
+                    )
+                    .await?;
+
+                if response == Some(fix_action) {
+                    let edits = fixes
+                        .into_iter()
+                        .map(|fix| TextEdit {
+                            range: fix.range,
+                            new_text: fix.new_text,
+                        })
+                        .collect();
+
+                    self.client
+                        .apply_edit(WorkspaceEdit {
+                            changes: Some([(uri, edits)].into_iter().collect()),
+                            ..Default::default()
+                        })
+                        .await?;
+                }
+
+                Ok(None)
+            }
+            ExecuteCommandParams { command, .. } if *command == *"build_agenda" => {
+                let Some((count, edit)) = self
+                    .bind_vault(|vault| Ok(commands::build_agenda(vault, &settings)))
+                    .await?
+                else {
+                    return Err(Error::invalid_params("Could not build the agenda note"));
+                };
+
+                self.client.apply_edit(edit).await?;
+
+                self.client
+                    .show_message(
+                        MessageType::INFO,
+                        format!("Collected {count} open tasks in {}", settings.agenda_note),
+                    )
+                    .await;
+
+                Ok(None)
+            }
+            ExecuteCommandParams { command, .. } if *command == *"daily" => {
+                commands::daily(&self.client, &root_dir, &settings).await
+            }
+            ExecuteCommandParams { command, .. } if *command == *"list_broken_links" => {
+                let locations = self
+                    .bind_vault(|vault| Ok(commands::broken_links(vault)))
+                    .await?;
+
+                Ok(serde_json::to_value(locations).ok())
+            }
+            ExecuteCommandParams { command, .. } if *command == *"insert_backlink" => {
+                let (Some(uri), Some(position)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(|arg| serde_json::from_value::<Url>(arg.clone()).ok()),
+                    params
+                        .arguments
+                        .get(1)
+                        .and_then(|arg| serde_json::from_value::<Position>(arg.clone()).ok()),
+                ) else {
+                    return Err(Error::invalid_params(
+                        "Expected the uri of a file and a position",
+                    ));
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Err(BackendError::InvalidUri(uri).into());
+                };
+
+                let sources = self
+                    .bind_vault(|vault| {
+                        Ok(commands::backlink_sources(
+                            vault, &settings, &path, position,
+                        ))
+                    })
+                    .await?;
+
+                Ok(serde_json::to_value(sources.unwrap_or_default()).ok())
+            }
+            ExecuteCommandParams { command, .. } if *command == *"toggle_task" => {
+                let (Some(uri), Some(position)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(|arg| serde_json::from_value::<Url>(arg.clone()).ok()),
+                    params
+                        .arguments
+                        .get(1)
+                        .and_then(|arg| serde_json::from_value::<Position>(arg.clone()).ok()),
+                ) else {
+                    return Err(Error::invalid_params(
+                        "Expected the uri of a file and a position",
+                    ));
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Err(BackendError::InvalidUri(uri).into());
+                };
+
+                let edit = self
+                    .bind_vault(|vault| {
+                        Ok(commands::toggle_task(vault, &settings, &path, position))
+                    })
+                    .await?;
+
+                if let Some(edit) = edit {
+                    self.client.apply_edit(edit).await?;
+                }
+
+                Ok(None)
+            }
+            ExecuteCommandParams { command, .. } if *command == *"renumber_footnotes" => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(|arg| serde_json::from_value::<Url>(arg.clone()).ok())
+                else {
+                    return Err(Error::invalid_params("Expected the uri of a file"));
+                };
+                let Ok(path) = uri.to_file_path() else {
+                    return Err(BackendError::InvalidUri(uri).into());
+                };
+
+                let edit = self
+                    .bind_vault(|vault| Ok(commands::renumber_footnotes(vault, &path)))
+                    .await?;
+
+                if let Some(edit) = edit {
+                    self.client.apply_edit(edit).await?;
+                }
+
+                Ok(None)
+            }
             ExecuteCommandParams { command, .. } if *command == *"jump" => {
                 let jump_to = params.arguments.first().and_then(|val| val.as_str());
                 let settings = self
@@ -644,9 +1065,18 @@ impl LanguageServer for Backend {
         &self,
         params: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
         self.bind_vault(|vault| {
             let path = params_path!(params)?;
-            Ok(document_symbol(vault, &params, &path))
+            Ok(document_symbol(vault, &params, &path, &settings))
+        })
+        .await
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        self.bind_vault(|vault| {
+            let path = params_path!(params)?;
+            Ok(folding::folding_ranges(vault, &path))
         })
         .await
     }
@@ -655,14 +1085,16 @@ impl LanguageServer for Backend {
         &self,
         params: WorkspaceSymbolParams,
     ) -> Result<Option<Vec<SymbolInformation>>> {
-        self.bind_vault(|vault| Ok(workspace_symbol(vault, &params)))
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+        self.bind_vault(|vault| Ok(workspace_symbol(vault, &params, &settings)))
             .await
     }
 
     async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
         self.bind_vault(|vault| {
             let path = params_position_path!(params)?;
-            Ok(rename::rename(vault, &params, &path))
+            Ok(rename::rename(vault, &params, &path, &settings))
         })
         .await
     }
@@ -687,12 +1119,8 @@ impl LanguageServer for Backend {
 
         let path = params_path!(params)?;
         let res = self
-            .bind_vault(|vault| {
-                Ok(tokens::semantic_tokens_full(
-                    vault, &path, params, &settings,
-                ))
-            })
-            .await;
+            .bind_vault(|vault| Ok(tokens::semantic_tokens_full(vault, &path, &settings)))
+            .await?;
 
         let elapsed = timer.elapsed();
 
@@ -703,7 +1131,51 @@ impl LanguageServer for Backend {
             )
             .await;
 
-        return res;
+        let Some(data) = res else {
+            return Ok(None);
+        };
+
+        let mut sent_tokens = self.sent_tokens.write().await;
+        Ok(Some(SemanticTokensResult::Tokens(
+            sent_tokens.send(params.text_document.uri, data),
+        )))
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+        let path = params_path!(params)?;
+
+        let Some(data) = self
+            .bind_vault(|vault| Ok(tokens::semantic_tokens_full(vault, &path, &settings)))
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut sent_tokens = self.sent_tokens.write().await;
+        Ok(Some(sent_tokens.send_delta(
+            params.text_document.uri,
+            &params.previous_result_id,
+            data,
+        )))
+    }
+
+    async fn semantic_tokens_range(
+        &self,
+        params: SemanticTokensRangeParams,
+    ) -> Result<Option<SemanticTokensRangeResult>> {
+        let settings = self.bind_settings(|settings| Ok(settings.clone())).await?;
+        let path = params_path!(params)?;
+
+        self.bind_vault(|vault| {
+            Ok(tokens::semantic_tokens_range(
+                vault, &path, params, &settings,
+            ))
+        })
+        .await
     }
 
     async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
@@ -714,13 +1186,19 @@ impl LanguageServer for Backend {
 
         let hints = self
             .bind_vault(|vault| {
+                let path = params_path!(params)?;
+
+                let backlink_hints = match settings.backlink_count_hints {
+                    true => inlay::backlink_count_hints(vault, &path, params.range, &settings),
+                    false => vec![],
+                };
+
                 if !settings.block_transclusion {
-                    return Ok(None);
+                    return Ok(Some(backlink_hints));
                 }
 
-                let path = params_path!(params)?;
                 let Some(references) = vault.select_references(Some(&path)) else {
-                    return Ok(None);
+                    return Ok(Some(backlink_hints));
                 };
 
                 let embed_block_references_in_range = references
@@ -759,14 +1237,12 @@ impl LanguageServer for Backend {
                             })?;
                     let preview = binding.trim();
                     let index_index = preview.rfind("^")?;
-                    let preview = preview.get(0..index_index)?.trim();
+                    let preview = ui::plain_text(preview.get(0..index_index)?);
                     // only first x chars
-                    let preview = (match settings.block_transclusion_length {
-                        EmbeddedBlockTransclusionLength::Partial(x) => preview.get(0..=x),
-                        EmbeddedBlockTransclusionLength::Full => None,
-                    })
-                    .map(|it| format!("{it}..."))
-                    .unwrap_or(preview.to_string());
+                    let preview = match settings.block_transclusion_length {
+                        EmbeddedBlockTransclusionLength::Partial(x) => ui::truncated(&preview, x),
+                        EmbeddedBlockTransclusionLength::Full => preview,
+                    };
 
                     Some((
                         preview.to_string(),
@@ -791,6 +1267,7 @@ impl LanguageServer for Backend {
                             padding_right: None,
                         })
                     })
+                    .chain(backlink_hints)
                     .collect();
 
                 Ok(Some(hints))
@@ -814,7 +1291,40 @@ async fn jump_to_specific(
     root_dir: &PathBuf,
     settings: &Settings,
 ) -> Result<Option<Value>> {
-    commands::jump(client, root_dir, settings, Some(day)).await
+    let today = chrono::Local::now().date_naive();
+
+    let periodic_note = daily::RELATIVE_PERIODS
+        .iter()
+        .find(|(name, ..)| *name == day)
+        .and_then(|(_, period, offset)| {
+            Some((
+                period.note_settings(settings),
+                period.start(today, *offset)?,
+            ))
+        });
+
+    // Relative daily note commands are matched by name, as they may not be in English
+    let daily_note = || {
+        daily::relative_dates(settings)
+            .into_iter()
+            .find(|(name, _)| name == day)
+            .map(|(_, date)| {
+                (
+                    (
+                        settings.dailynote.as_str(),
+                        settings.daily_notes_folder.as_str(),
+                    ),
+                    date,
+                )
+            })
+    };
+
+    match periodic_note.or_else(daily_note) {
+        Some((note_settings, date)) => {
+            commands::jump_to_date(client, root_dir, settings, note_settings, date).await
+        }
+        None => commands::jump(client, root_dir, settings, Some(day)).await,
+    }
 }
 
 use std::env;
@@ -826,14 +1336,25 @@ async fn main() {
         return;
     }
 
+    if env::args().nth(1).as_deref() == Some("check") {
+        let root_dir = env::args().nth(2).unwrap_or(".".to_string());
+        std::process::exit(check::check(Path::new(&root_dir)));
+    }
+
     let stdin = tokio::io::stdin();
     let stdout = tokio::io::stdout();
 
-    let (service, socket) = LspService::new(|client| Backend {
+    let (service, socket) = LspService::build(|client| Backend {
         client,
         vault: Arc::new(None.into()),
         opened_files: Arc::new(HashSet::new().into()),
         settings: Arc::new(None.into()),
-    });
+        sent_tokens: Arc::new(Default::default()),
+        background_diagnostics: Arc::new(None.into()),
+    })
+    .custom_method("moxide/noteGraph", Backend::note_graph)
+    .custom_method("moxide/sectionHeading", Backend::section_heading)
+    .custom_method("moxide/tasks", Backend::tasks)
+    .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 }
@@ -1,21 +1,52 @@
 use std::iter;
 use std::path::Path;
 
+use pathdiff::diff_paths;
 use tower_lsp::lsp_types::{
     DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
-    RenameFile, RenameParams, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+    Position, Range, RenameFile, RenameParams, ResourceOp, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
 };
 
-use crate::vault::{MDHeading, Reference, Referenceable, Vault};
+use crate::config::Settings;
+use crate::vault::{
+    decode_link_path, encode_link_path, heading_slug, MDHeading, Reference, ReferenceData,
+    Referenceable, Vault,
+};
 
-pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<WorkspaceEdit> {
+pub fn rename(
+    vault: &Vault,
+    params: &RenameParams,
+    path: &Path,
+    settings: &Settings,
+) -> Option<WorkspaceEdit> {
     let position = params.text_document_position.position;
     let referenceable = vault.select_referenceable_at_position(path, position)?;
 
+    rename_referenceable(vault, referenceable, &params.new_name, settings)
+}
+
+/// Rename a heading, file, or tag to `new_name`, updating the references to it; updated links include the `.md`
+/// extension as completed links of their style do
+pub fn rename_referenceable(
+    vault: &Vault,
+    referenceable: Referenceable,
+    new_name: &str,
+    settings: &Settings,
+) -> Option<WorkspaceEdit> {
+    let wiki_ext = match settings.include_md_extension_wikilink {
+        true => ".md",
+        false => "",
+    };
+    let md_ext = match settings.include_md_extension_md_link {
+        true => ".md",
+        false => "",
+    };
+
     let (referenceable_document_change, new_ref_name): (Option<DocumentChangeOperation>, String) =
         match referenceable {
             Referenceable::Heading(path, heading) => {
-                let new_text = format!("{} {}", "#".repeat(heading.level.0), params.new_name); // move this obsidian syntax specific stuff to the vault
+                let new_text = format!("{} {}", "#".repeat(heading.level.0), new_name); // move this obsidian syntax specific stuff to the vault
 
                 let change_op = DocumentChangeOperation::Edit(TextDocumentEdit {
                     text_document: tower_lsp::lsp_types::OptionalVersionedTextDocumentIdentifier {
@@ -29,12 +60,12 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 });
 
                 // {path name}#{new name}
-                let name = format!("{}#{}", path.file_stem()?.to_string_lossy().to_owned(), params.new_name);
+                let name = format!("{}#{}", path.file_stem()?.to_string_lossy().to_owned(), new_name);
 
                 (Some(change_op), name.to_string())
             }
             Referenceable::File(path, _file) => {
-                let new_path = path.with_file_name(&params.new_name).with_extension("md");
+                let new_path = path.with_file_name(new_name).with_extension("md");
 
                 let change_op = DocumentChangeOperation::Op(ResourceOp::Rename(RenameFile {
                     old_uri: Url::from_file_path(path).ok()?,
@@ -43,12 +74,12 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                     annotation_id: None,
                 }));
 
-                let name = params.new_name.clone();
+                let name = new_name.to_string();
 
                 (Some(change_op), name)
             }
             Referenceable::Tag(_path, _tag) => {
-                let new_ref_name = params.new_name.clone();
+                let new_ref_name = new_name.to_string();
 
                 let _new_tag = format!("#{}", new_ref_name);
 
@@ -70,8 +101,9 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                     if matches!(referenceable, Referenceable::File(..)) =>
                 {
                     let new_text = format!(
-                        "[[{}{}]]",
+                        "[[{}{}{}]]",
                         new_ref_name,
+                        wiki_ext,
                         data.display_text
                             .as_ref()
                             .map(|text| format!("|{text}"))
@@ -90,13 +122,64 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                         })],
                     })
                 }
+                // Links within the renamed file that do not name it, such as `[[#Heading]]`, stay as they are
+                Reference::WikiHeadingLink(data, ..)
+                | Reference::WikiIndexedBlockLink(data, ..)
+                | Reference::MDHeadingLink(data, ..)
+                | Reference::MDIndexedBlockLink(data, ..)
+                    if matches!(referenceable, Referenceable::File(..))
+                        && is_same_file_link(vault, path, data) =>
+                {
+                    None
+                }
+                // Html links keep their syntax and attributes; only the path of the href changes
+                Reference::MDFileLink(data)
+                | Reference::MDHeadingLink(data, ..)
+                | Reference::MDIndexedBlockLink(data, ..)
+                    if matches!(referenceable, Referenceable::File(..))
+                        && html_link_href(vault, path, data).is_some() =>
+                {
+                    let Referenceable::File(renamed_path, _) = referenceable else {
+                        return None;
+                    };
+                    let (range, href) = html_link_href(vault, path, data)?;
+                    let (href_path, anchor) = match href.find('#') {
+                        Some(index) => href.split_at(index),
+                        None => (href.as_str(), ""),
+                    };
+
+                    let new_path = renamed_path.with_file_name(new_name).with_extension("md");
+                    let new_href_path = match href_path.starts_with('/') {
+                        true => format!(
+                            "/{}",
+                            new_path.strip_prefix(vault.root_dir()).ok()?.to_str()?
+                        ),
+                        false => diff_paths(&new_path, path.parent()?)?.to_str()?.to_string(),
+                    };
+                    let new_href_path = match href_path.to_lowercase().ends_with(".md") {
+                        true => new_href_path,
+                        false => new_href_path.trim_end_matches(".md").to_string(),
+                    };
+
+                    Some(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri: Url::from_file_path(path).ok()?,
+                            version: None,
+                        },
+                        edits: vec![OneOf::Left(TextEdit {
+                            range,
+                            new_text: format!("{}{}", encode_link_path(&new_href_path), anchor),
+                        })],
+                    })
+                }
                 Reference::WikiHeadingLink(data, _file, infile)
                 | Reference::WikiIndexedBlockLink(data, _file, infile)
                     if matches!(referenceable, Referenceable::File(..)) =>
                 {
                     let new_text = format!(
-                        "[[{}#{}{}]]",
+                        "[[{}{}#{}{}]]",
                         new_ref_name,
+                        wiki_ext,
                         infile,
                         data.display_text
                             .as_ref()
@@ -117,11 +200,20 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                     })
                 }
                 Reference::WikiHeadingLink(data, _file, _heading)
-                    if matches!(referenceable, Referenceable::Heading(..)) =>
+                    if matches!(referenceable, Referenceable::Heading(..))
+                        && settings.rename_link_styles.includes_wiki() =>
                 {
+                    // The extension goes after the file, before the heading
+                    let (file, heading) = new_ref_name.split_once('#')?;
+                    let (file, wiki_ext) = match is_same_file_link(vault, path, data) {
+                        true => ("", ""),
+                        false => (file, wiki_ext),
+                    };
                     let new_text = format!(
-                        "[[{}{}]]",
-                        new_ref_name,
+                        "[[{}{}#{}{}]]",
+                        file,
+                        wiki_ext,
+                        heading,
                         data.display_text
                             .as_ref()
                             .map(|text| format!("|{text}"))
@@ -163,12 +255,13 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                 }
                 Reference::MDFileLink(data) if matches!(referenceable, Referenceable::File(..)) => {
                     let new_text = format!(
-                        "[{}]({})",
+                        "[{}]({}{})",
                         data.display_text
                             .as_ref()
                             .map(|text| format!("|{text}"))
                             .unwrap_or_else(|| String::from("")),
-                        new_ref_name,
+                        encode_link_path(&new_ref_name),
+                        md_ext,
                     );
 
                     Some(TextDocumentEdit {
@@ -189,12 +282,13 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                     if matches!(referenceable, Referenceable::File(..)) =>
                 {
                     let new_text = format!(
-                        "[{}]({}#{})",
+                        "[{}]({}{}#{})",
                         data.display_text
                             .as_ref()
                             .map(|text| format!("|{text}"))
                             .unwrap_or_else(|| String::from("")),
-                        new_ref_name,
+                        encode_link_path(&new_ref_name),
+                        md_ext,
                         infile,
                     );
 
@@ -210,17 +304,51 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
                         })],
                     })
                 }
-                Reference::WikiHeadingLink(data, _file, _heading)
-                    if matches!(referenceable, Referenceable::Heading(..)) =>
+                Reference::MDHeadingLink(data, file, link_heading)
+                    if matches!(referenceable, Referenceable::Heading(..))
+                        && settings.rename_link_styles.includes_markdown() =>
                 {
-                    let new_text = format!(
-                        "[{}]({})",
-                        data.display_text
-                            .as_ref()
-                            .map(|text| format!("|{text}"))
-                            .unwrap_or_else(|| String::from("")),
-                        new_ref_name,
-                    );
+                    // The file is kept as it was linked, such as with a relative path, and the heading as it was
+                    // named: by its slug or by its text
+                    let (_, new_heading) = new_ref_name.split_once('#')?;
+                    let new_anchor = match referenceable {
+                        Referenceable::Heading(_, heading)
+                            if is_slug_anchor(link_heading, &heading.heading_text) =>
+                        {
+                            heading_slug(new_heading)
+                        }
+                        _ => encode_link_path(new_heading),
+                    };
+
+                    if let Some((range, href)) = html_link_href(vault, path, data) {
+                        let href_path = href.split('#').next().unwrap_or_default();
+
+                        return Some(TextDocumentEdit {
+                            text_document: OptionalVersionedTextDocumentIdentifier {
+                                uri: Url::from_file_path(path).ok()?,
+                                version: None,
+                            },
+                            edits: vec![OneOf::Left(TextEdit {
+                                range,
+                                new_text: format!("{}#{}", href_path, new_anchor),
+                            })],
+                        });
+                    }
+
+                    let new_text = match is_same_file_link(vault, path, data) {
+                        true => format!(
+                            "[{}](#{})",
+                            data.display_text.as_deref().unwrap_or_default(),
+                            new_anchor,
+                        ),
+                        false => format!(
+                            "[{}]({}{}#{})",
+                            data.display_text.as_deref().unwrap_or_default(),
+                            encode_link_path(&decode_link_path(file)),
+                            md_ext,
+                            new_anchor,
+                        ),
+                    };
 
                     Some(TextDocumentEdit {
                         text_document:
@@ -255,3 +383,193 @@ pub fn rename(vault: &Vault, params: &RenameParams, path: &Path) -> Option<Works
         ..Default::default()
     })
 }
+
+/// Whether a link is to a heading or block of the file it is in without naming the file, as `[[#Heading]]` and
+/// `[text](#heading)` are
+fn is_same_file_link(vault: &Vault, path: &Path, data: &ReferenceData) -> bool {
+    let Some(line) = vault.select_line(path, data.range.start.line as isize) else {
+        return false;
+    };
+    let link = String::from_iter(line.iter().skip(data.range.start.character as usize));
+
+    link.starts_with("[[#")
+        || link.starts_with('[')
+            && !link.starts_with("[[")
+            && link.split_once("](").is_some_and(|(_, destination)| {
+                destination.trim_start_matches('<').starts_with('#')
+            })
+        || html_link_href(vault, path, data).is_some_and(|(_, href)| href.starts_with('#'))
+}
+
+/// The range and the text of the href of a link that is a raw html anchor, such as `<a href="note.md">display</a>`
+fn html_link_href(vault: &Vault, path: &Path, data: &ReferenceData) -> Option<(Range, String)> {
+    let line = vault.select_line(path, data.range.start.line as isize)?;
+    let link = String::from_iter(line.iter().skip(data.range.start.character as usize));
+
+    if !link.starts_with("<a") {
+        return None;
+    }
+
+    let tag = &link[..link.find('>').unwrap_or(link.len())];
+    let href_start = tag.find("href=\"")? + "href=\"".len();
+    let href = &tag[href_start..][..tag[href_start..].find('"')?];
+
+    let start = data.range.start.character + tag[..href_start].chars().count() as u32;
+    let end = start + href.chars().count() as u32;
+
+    Some((
+        Range::new(
+            Position::new(data.range.start.line, start),
+            Position::new(data.range.start.line, end),
+        ),
+        href.to_string(),
+    ))
+}
+
+/// Whether the anchor of a link names the heading by its slug rather than by its text
+fn is_slug_anchor(anchor: &str, heading_text: &str) -> bool {
+    let anchor = decode_link_path(anchor).to_lowercase();
+
+    anchor != heading_text.to_lowercase() && anchor == heading_slug(heading_text)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use tower_lsp::lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf};
+
+    use crate::config::Settings;
+    use crate::vault::{test_settings, Referenceable, Vault};
+
+    use super::{is_slug_anchor, rename_referenceable};
+
+    /// The start characters and new texts of the edits renaming `referenceable` makes to the note named `linking`
+    fn new_texts(
+        vault: &Vault,
+        settings: &Settings,
+        referenceable: Referenceable,
+        new_name: &str,
+        linking: &str,
+    ) -> Vec<(u32, String)> {
+        let edit = rename_referenceable(vault, referenceable, new_name, settings).unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("rename returns document changes")
+        };
+
+        operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit)
+                    if edit.text_document.uri.path().ends_with(linking) =>
+                {
+                    Some(edit.edits)
+                }
+                _ => None,
+            })
+            .flatten()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => (edit.range.start.character, edit.new_text),
+                OneOf::Right(edit) => (
+                    edit.text_edit.range.start.character,
+                    edit.text_edit.new_text,
+                ),
+            })
+            .sorted()
+            .collect_vec()
+    }
+
+    #[test]
+    fn slug_anchors() {
+        assert!(is_slug_anchor("initial-setup", "Initial Setup!"));
+        assert!(!is_slug_anchor("Initial%20Setup", "Initial Setup"));
+        assert!(!is_slug_anchor("setup", "Setup"));
+    }
+
+    #[test]
+    fn html_links_keep_their_syntax() {
+        let (root_dir, mut settings) = test_settings();
+        settings.html_links = true;
+
+        let target = root_dir.join("c/Target.md");
+        let linking = root_dir.join("a/Linking.md");
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                (target.clone(), "# Heading"),
+                (
+                    linking.clone(),
+                    r#"<a href="../c/Target.md">T</a> <a class="x" href="../c/Target#Heading">H</a>"#,
+                ),
+            ],
+        );
+
+        let new_texts = |referenceable, new_name| {
+            new_texts(&vault, &settings, referenceable, new_name, "Linking.md")
+        };
+
+        let md_file = &vault.md_files[&target];
+        assert_eq!(
+            new_texts(Referenceable::File(&target, md_file), "Renamed"),
+            vec![
+                (9, "../c/Renamed.md".to_string()),
+                (50, "../c/Renamed#Heading".to_string())
+            ]
+        );
+        assert_eq!(
+            new_texts(
+                Referenceable::Heading(&target, &md_file.headings[0]),
+                "New Heading"
+            ),
+            vec![(50, "../c/Target#New%20Heading".to_string())]
+        );
+    }
+
+    #[test]
+    fn wiki_links_with_md_extension() {
+        let (root_dir, mut settings) = test_settings();
+        settings.include_md_extension_wikilink = true;
+
+        let target = root_dir.join("Target.md");
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                (target.clone(), "# Heading\n[[#Heading]]"),
+                (
+                    root_dir.join("Linking.md"),
+                    "[[Target]] [[Target#Heading|h]] [[Target.md#Heading]]",
+                ),
+            ],
+        );
+
+        let md_file = &vault.md_files[&target];
+        assert_eq!(
+            new_texts(
+                &vault,
+                &settings,
+                Referenceable::File(&target, md_file),
+                "Renamed",
+                "Linking.md"
+            ),
+            vec![
+                (0, "[[Renamed.md]]".to_string()),
+                (11, "[[Renamed.md#Heading|h]]".to_string()),
+                (32, "[[Renamed.md#Heading]]".to_string()),
+            ]
+        );
+
+        let heading = Referenceable::Heading(&target, &md_file.headings[0]);
+        assert_eq!(
+            new_texts(&vault, &settings, heading.clone(), "New", "Linking.md"),
+            vec![
+                (11, "[[Target.md#New|h]]".to_string()),
+                (32, "[[Target.md#New]]".to_string()),
+            ]
+        );
+        assert_eq!(
+            new_texts(&vault, &settings, heading, "New", "Target.md"),
+            vec![(0, "# New".to_string()), (0, "[[#New]]".to_string())]
+        );
+    }
+}
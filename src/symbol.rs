@@ -1,67 +1,241 @@
-use std::{iter, path::Path};
+use std::path::Path;
 
 use itertools::Itertools;
 use tower_lsp::lsp_types::{
-    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Location, SymbolInformation,
-    SymbolKind, Url, WorkspaceSymbolParams,
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, Location, Range,
+    SymbolInformation, SymbolKind, SymbolTag, Url, WorkspaceSymbolParams,
 };
 
-use crate::vault::{MDHeading, Referenceable, Vault};
+use crate::{
+    completion::matcher::{fuzzy_match, Matchable},
+    config::Settings,
+    vault::{get_obsidian_ref_path, MDHeading, Referenceable, Vault},
+};
+
+impl Matchable for SymbolInformation {
+    fn match_string(&self) -> &str {
+        &self.name
+    }
+}
 
+/// The most workspace symbols returned for one query; vaults can have many thousands of headings and tags
+const MAX_WORKSPACE_SYMBOLS: usize = 200;
+
+/// Symbols for the referenceables and note aliases in the vault, best matches of the query first. Ties, and the
+/// symbols for an empty query, are ordered by name so that results are stable between requests. Symbols within notes
+/// are named by their text alone, with the note as their container, so that identical headings in different notes
+/// can be told apart.
 pub fn workspace_symbol(
     vault: &Vault,
-    _params: &WorkspaceSymbolParams,
+    params: &WorkspaceSymbolParams,
+    settings: &Settings,
 ) -> Option<Vec<SymbolInformation>> {
+    let file_start = tower_lsp::lsp_types::Range {
+        start: tower_lsp::lsp_types::Position {
+            line: 0,
+            character: 0,
+        },
+        end: tower_lsp::lsp_types::Position {
+            line: 0,
+            character: 1,
+        },
+    };
+
     let referenceables = vault.select_referenceable_nodes(None);
-    let symbol_informations = referenceables
-        .into_iter()
-        .flat_map(|referenceable| {
-            let range = match referenceable {
-                Referenceable::File(..) => tower_lsp::lsp_types::Range {
-                    start: tower_lsp::lsp_types::Position {
-                        line: 0,
-                        character: 0,
-                    },
-                    end: tower_lsp::lsp_types::Position {
-                        line: 0,
-                        character: 1,
-                    },
-                },
-                _ => *referenceable.get_range()?,
-            };
+    let referenceable_symbols = referenceables.into_iter().flat_map(|referenceable| {
+        let range = match referenceable {
+            Referenceable::File(..) => file_start,
+            _ => *referenceable.get_range()?,
+        };
 
+        let container_name = get_obsidian_ref_path(vault.root_dir(), referenceable.get_path());
+
+        #[allow(deprecated)]
+        Some(SymbolInformation {
+            name: match referenceable {
+                Referenceable::Heading(_, heading) => heading.heading_text.clone(),
+                _ => referenceable.display_name()?,
+            },
+            kind: match referenceable {
+                Referenceable::File(_, _) => SymbolKind::FILE,
+                Referenceable::Heading(_, _) => SymbolKind::STRUCT,
+                Referenceable::Tag(_, _) => SymbolKind::CONSTANT,
+                _ => SymbolKind::KEY,
+            },
+            location: Location {
+                uri: Url::from_file_path(referenceable.get_path()).ok()?,
+                range,
+            },
+            container_name,
+            tags: symbol_tags(vault, referenceable.get_path()),
+            deprecated: None,
+        })
+    });
+
+    let alias_symbols = vault.md_files.iter().flat_map(|(path, md_file)| {
+        let aliases = md_file
+            .metadata
+            .iter()
+            .flat_map(|metadata| metadata.aliases());
+
+        aliases.flat_map(move |alias| {
+            #[allow(deprecated)]
             Some(SymbolInformation {
-                name: referenceable.get_refname(vault.root_dir())?.to_string(),
-                kind: match referenceable {
-                    Referenceable::File(_, _) => SymbolKind::FILE,
-                    Referenceable::Tag(_, _) => SymbolKind::CONSTANT,
-                    _ => SymbolKind::KEY,
-                },
+                name: alias.clone(),
+                kind: SymbolKind::FILE,
                 location: Location {
-                    uri: Url::from_file_path(referenceable.get_path()).ok()?,
-                    range,
+                    uri: Url::from_file_path(path).ok()?,
+                    range: file_start,
                 },
-                container_name: None,
-                tags: None,
+                container_name: get_obsidian_ref_path(vault.root_dir(), path),
+                tags: symbol_tags(vault, path),
                 deprecated: None,
             })
         })
+    });
+
+    let symbol_informations = referenceable_symbols
+        .chain(alias_symbols)
+        .sorted_by(|a, b| {
+            (&a.name, a.location.uri.as_str()).cmp(&(&b.name, b.location.uri.as_str()))
+        })
         .collect_vec();
 
-    Some(symbol_informations)
+    if params.query.is_empty() {
+        return Some(
+            symbol_informations
+                .into_iter()
+                .take(MAX_WORKSPACE_SYMBOLS)
+                .collect(),
+        );
+    }
+
+    Some(
+        fuzzy_match(&params.query, symbol_informations, &settings.case_matching)
+            .into_iter()
+            .sorted_by_key(|(_, score)| std::cmp::Reverse(*score))
+            .take(MAX_WORKSPACE_SYMBOLS)
+            .map(|(symbol, _)| symbol)
+            .collect(),
+    )
+}
+
+/// Symbols of notes in an `archive` folder, or marked `deprecated: true` in their frontmatter, are tagged as deprecated
+/// so that editors show them struck through
+fn symbol_tags(vault: &Vault, path: &Path) -> Option<Vec<SymbolTag>> {
+    let deprecated = vault
+        .md_files
+        .get(path)
+        .and_then(|md_file| md_file.metadata.as_ref())
+        .is_some_and(|metadata| metadata.deprecated());
+
+    (deprecated || is_archived(vault.root_dir(), path)).then(|| vec![SymbolTag::DEPRECATED])
+}
+
+/// Whether the note is within an `archive` folder of the vault, at any depth
+fn is_archived(root_dir: &Path, path: &Path) -> bool {
+    path.strip_prefix(root_dir)
+        .unwrap_or(path)
+        .parent()
+        .is_some_and(|folder| {
+            folder
+                .components()
+                .any(|component| component.as_os_str().eq_ignore_ascii_case("archive"))
+        })
 }
 
 pub fn document_symbol(
     vault: &Vault,
     _params: &DocumentSymbolParams,
     path: &Path,
+    settings: &Settings,
 ) -> Option<DocumentSymbolResponse> {
-    let headings = vault.select_headings(path)?;
+    let md_file = vault.md_files.get(path)?;
+
+    let symbol_tags = symbol_tags(vault, path);
+
+    let mut lsp = construct_tree(&md_file.headings)
+        .map(|tree| map_to_lsp_tree(tree, &symbol_tags))
+        .unwrap_or_default();
+
+    if settings.tag_and_block_symbols {
+        let tags = md_file.tags.iter().map(|tag| {
+            symbol(
+                format!("#{}", tag.tag_ref),
+                SymbolKind::KEY,
+                *tag.range,
+                &symbol_tags,
+            )
+        });
+        let blocks = md_file.indexed_blocks.iter().map(|block| {
+            symbol(
+                format!("^{}", block.index),
+                SymbolKind::FIELD,
+                *block.range,
+                &symbol_tags,
+            )
+        });
+
+        for symbol in tags.chain(blocks) {
+            insert_in_section(&mut lsp, symbol);
+        }
+    }
+
+    if settings.callout_symbols {
+        for callout in &md_file.callouts {
+            let name = match &callout.title {
+                Some(title) => format!("[!{}] {}", callout.kind, title),
+                None => format!("[!{}]", callout.kind),
+            };
+
+            insert_in_section(
+                &mut lsp,
+                DocumentSymbol {
+                    selection_range: *callout.title_range,
+                    ..symbol(name, SymbolKind::OBJECT, *callout.range, &symbol_tags)
+                },
+            );
+        }
+    }
 
-    let tree = construct_tree(headings)?;
-    let lsp = map_to_lsp_tree(tree);
+    Some(lsp)
+        .filter(|lsp| !lsp.is_empty())
+        .map(DocumentSymbolResponse::Nested)
+}
 
-    Some(DocumentSymbolResponse::Nested(lsp))
+fn symbol(
+    name: String,
+    kind: SymbolKind,
+    range: Range,
+    tags: &Option<Vec<SymbolTag>>,
+) -> DocumentSymbol {
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name,
+        kind,
+        deprecated: None,
+        tags: tags.clone(),
+        range,
+        detail: None,
+        selection_range: range,
+        children: None,
+    }
+}
+
+/// Add a tag or block symbol under the heading of the section it falls within, keeping symbols in document order
+fn insert_in_section(symbols: &mut Vec<DocumentSymbol>, symbol: DocumentSymbol) {
+    let section = symbols
+        .iter_mut()
+        .rev()
+        .find(|other| other.kind == SymbolKind::STRUCT && other.range.start <= symbol.range.start);
+
+    match section {
+        Some(section) => insert_in_section(section.children.get_or_insert_with(Vec::new), symbol),
+        None => {
+            symbols.push(symbol);
+            symbols.sort_by_key(|symbol| (symbol.range.start.line, symbol.range.start.character));
+        }
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -70,64 +244,71 @@ struct Node {
     children: Option<Vec<Node>>,
 }
 
+/// Nest headings under the closest preceding heading of a lower level. Headings are scanned in order with a stack of
+/// the open sections, so a document starting at a deeper level, such as an H3 before any H1, keeps those headings at
+/// the top level.
 fn construct_tree(headings: &[MDHeading]) -> Option<Vec<Node>> {
-    match &headings {
-        [only] => {
-            let node = Node {
-                heading: only.clone(),
-                children: None,
-            };
-            Some(vec![node])
+    fn close_section(open: &mut Vec<Node>, roots: &mut Vec<Node>) {
+        let Some(node) = open.pop() else {
+            return;
+        };
+
+        match open.last_mut() {
+            Some(parent) => parent.children.get_or_insert_with(Vec::new).push(node),
+            None => roots.push(node),
         }
-        [first, rest @ ..] => {
-            let break_index = rest
-                .iter()
-                .find_position(|heading| first.level >= heading.level);
-
-            match break_index.map(|(index, _)| (&rest[..index], &rest[index..])) {
-                Some((to_next, rest)) => {
-                    // to_next is could be an empty list and rest has at least one item
-                    let node = Node {
-                        heading: first.clone(),
-                        children: construct_tree(to_next), // if to_next is empty, this will return none
-                    };
-
-                    Some(
-                        iter::once(node)
-                            .chain(construct_tree(rest).into_iter().flatten())
-                            .collect(),
-                    )
-                }
-                None => {
-                    let node = Node {
-                        heading: first.clone(),
-                        children: construct_tree(rest),
-                    };
-                    Some(vec![node])
-                }
-            }
+    }
+
+    let mut open: Vec<Node> = vec![];
+    let mut roots = vec![];
+
+    for heading in headings {
+        while open
+            .last()
+            .is_some_and(|node| node.heading.level >= heading.level)
+        {
+            close_section(&mut open, &mut roots);
         }
-        [] => None,
+
+        open.push(Node {
+            heading: heading.clone(),
+            children: None,
+        });
+    }
+
+    while !open.is_empty() {
+        close_section(&mut open, &mut roots);
     }
+
+    Some(roots).filter(|roots| !roots.is_empty())
 }
 
-fn map_to_lsp_tree(tree: Vec<Node>) -> Vec<DocumentSymbol> {
+fn map_to_lsp_tree(tree: Vec<Node>, tags: &Option<Vec<SymbolTag>>) -> Vec<DocumentSymbol> {
     tree.into_iter()
-        .map(|node| DocumentSymbol {
-            name: node.heading.heading_text,
-            kind: SymbolKind::STRUCT,
-            deprecated: None,
-            tags: None,
-            range: *node.heading.range,
-            detail: None,
-            selection_range: *node.heading.range,
-            children: node.children.map(map_to_lsp_tree),
+        .map(|node| {
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: node.heading.heading_text,
+                kind: SymbolKind::STRUCT,
+                deprecated: None,
+                tags: tags.clone(),
+                range: *node.heading.range,
+                detail: None,
+                selection_range: *node.heading.range,
+                children: node
+                    .children
+                    .map(|children| map_to_lsp_tree(children, tags)),
+            }
         })
         .collect()
 }
 
 #[cfg(test)]
 mod test {
+    use std::path::Path;
+
+    use tower_lsp::lsp_types::{DocumentSymbol, Position, Range, SymbolKind};
+
     use crate::{
         symbol,
         vault::{HeadingLevel, MDHeading},
@@ -299,4 +480,112 @@ mod test {
 
         assert_eq!(tree, Some(expected))
     }
+
+    #[test]
+    fn test_tree_starting_below_top_level() {
+        let heading = |level, text: &str| MDHeading {
+            level: HeadingLevel(level),
+            heading_text: text.to_string(),
+            range: Default::default(),
+        };
+
+        let headings = vec![
+            heading(3, "Third"),
+            heading(4, "Fourth"),
+            heading(1, "First"),
+            heading(3, "Skipped"),
+            heading(2, "Second"),
+        ];
+
+        let tree = super::construct_tree(&headings);
+
+        let expected = vec![
+            symbol::Node {
+                heading: heading(3, "Third"),
+                children: Some(vec![symbol::Node {
+                    heading: heading(4, "Fourth"),
+                    children: None,
+                }]),
+            },
+            symbol::Node {
+                heading: heading(1, "First"),
+                children: Some(vec![
+                    symbol::Node {
+                        heading: heading(3, "Skipped"),
+                        children: None,
+                    },
+                    symbol::Node {
+                        heading: heading(2, "Second"),
+                        children: None,
+                    },
+                ]),
+            },
+        ];
+
+        assert_eq!(tree, Some(expected));
+        assert_eq!(super::construct_tree(&[]), None);
+    }
+
+    #[test]
+    fn test_symbols_in_sections() {
+        let range = |line| Range {
+            start: Position { line, character: 0 },
+            end: Position { line, character: 1 },
+        };
+
+        let mut symbols = vec![
+            super::symbol("First".to_string(), SymbolKind::STRUCT, range(1), &None),
+            super::symbol("Second".to_string(), SymbolKind::STRUCT, range(5), &None),
+        ];
+
+        super::insert_in_section(
+            &mut symbols,
+            super::symbol("#top".to_string(), SymbolKind::KEY, range(0), &None),
+        );
+        super::insert_in_section(
+            &mut symbols,
+            super::symbol("^block".to_string(), SymbolKind::FIELD, range(3), &None),
+        );
+        super::insert_in_section(
+            &mut symbols,
+            super::symbol("#tag".to_string(), SymbolKind::KEY, range(2), &None),
+        );
+
+        let names = |symbols: &[DocumentSymbol]| {
+            symbols
+                .iter()
+                .map(|symbol| symbol.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&symbols), vec!["#top", "First", "Second"]);
+        assert_eq!(
+            names(symbols[1].children.as_ref().unwrap()),
+            vec!["#tag", "^block"]
+        );
+        assert_eq!(symbols[2].children, None);
+    }
+
+    #[test]
+    fn archived_notes() {
+        let root_dir = Path::new("/vault");
+
+        assert!(super::is_archived(
+            root_dir,
+            &root_dir.join("Archive/Old.md")
+        ));
+        assert!(super::is_archived(
+            root_dir,
+            &root_dir.join("projects/archive/2023/Plan.md")
+        ));
+        assert!(!super::is_archived(root_dir, &root_dir.join("archive.md")));
+        assert!(!super::is_archived(
+            root_dir,
+            &root_dir.join("archived/Note.md")
+        ));
+        assert!(!super::is_archived(
+            Path::new("/archive/vault"),
+            Path::new("/archive/vault/Note.md")
+        ));
+    }
 }
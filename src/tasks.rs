@@ -0,0 +1,226 @@
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use tower_lsp::lsp_types::Url;
+
+use crate::vault::{Rangeable, Vault};
+
+/// An open task of the vault, as returned by the custom `moxide/tasks` request
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub uri: Url,
+    pub line: u32,
+    /// The text of the task, without its checkbox and block index
+    pub text: String,
+    /// The tags on the line of the task, as `#tag`
+    pub tags: Vec<String>,
+    /// The text of the list items the task is nested in, outermost first
+    pub parents: Vec<String>,
+    /// The index of the block of the task, as `a1b2c` for `^a1b2c`, if it has one
+    pub index: Option<String>,
+}
+
+/// The open tasks, `- [ ] text`, of every note in order of file and line; tasks in fenced code blocks are left out
+pub fn open_tasks(vault: &Vault) -> Vec<Task> {
+    vault
+        .md_files
+        .iter()
+        .sorted_by_key(|(path, _)| *path)
+        .filter_map(|(path, md_file)| {
+            let uri = Url::from_file_path(path).ok()?;
+            let rope = vault.ropes.get(path)?;
+            let lines = rope.lines().map(|line| line.to_string()).collect_vec();
+
+            // Inline code spans are code blocks on a single line; only lines between fences are code
+            let in_code_block = |line: usize| {
+                md_file.codeblocks.iter().any(|codeblock| {
+                    let range = codeblock.range();
+                    (range.start.line as usize) < line && line < range.end.line as usize
+                })
+            };
+
+            let tasks = list_tasks(&lines, in_code_block)
+                .into_iter()
+                .map(|task| Task {
+                    uri: uri.clone(),
+                    line: task.line as u32,
+                    tags: md_file
+                        .tags
+                        .iter()
+                        .filter(|tag| tag.range.start.line as usize == task.line)
+                        .map(|tag| format!("#{}", tag.tag_ref))
+                        .collect(),
+                    text: task.text,
+                    parents: task.parents,
+                    index: task.index,
+                })
+                .collect_vec();
+
+            Some(tasks)
+        })
+        .flatten()
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct ListTask {
+    line: usize,
+    text: String,
+    parents: Vec<String>,
+    index: Option<String>,
+}
+
+/// The open tasks of the lines with the list items they are nested in. A list item is nested in the closest earlier
+/// item that is indented less, until a line of other text that is not indented ends the list.
+fn list_tasks(lines: &[impl AsRef<str>], in_code_block: impl Fn(usize) -> bool) -> Vec<ListTask> {
+    static LIST_ITEM_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"^(?<indent>\s*)(?:[-*+]|\d+[.)]) (?:\[(?<state>[^\]])\] )?(?<text>.*?)(?: \^(?<index>\w+))?\s*$",
+        )
+        .unwrap()
+    });
+
+    let mut open_items: Vec<(usize, String)> = vec![];
+    let mut tasks = vec![];
+
+    for (line, text) in lines.iter().enumerate() {
+        let text = text.as_ref();
+        if in_code_block(line) {
+            continue;
+        }
+
+        let Some(captures) = LIST_ITEM_RE.captures(text) else {
+            if !text.trim().is_empty() && !text.starts_with(char::is_whitespace) {
+                open_items.clear();
+            }
+            continue;
+        };
+
+        let indent = captures["indent"].chars().count();
+        let item_text = captures["text"].trim().to_string();
+
+        while open_items.last().is_some_and(|(other, _)| *other >= indent) {
+            open_items.pop();
+        }
+
+        if captures
+            .name("state")
+            .is_some_and(|state| state.as_str() == " ")
+            && !item_text.is_empty()
+        {
+            tasks.push(ListTask {
+                line,
+                text: item_text.clone(),
+                parents: open_items.iter().map(|(_, text)| text.clone()).collect(),
+                index: captures
+                    .name("index")
+                    .map(|index| index.as_str().to_string()),
+            });
+        }
+
+        open_items.push((indent, item_text));
+    }
+
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use crate::vault::{test_settings, Vault};
+
+    use super::{list_tasks, open_tasks, ListTask};
+
+    #[test]
+    fn open_tasks_of_vault() {
+        let (root_dir, settings) = test_settings();
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [
+                (
+                    root_dir.join("b.md"),
+                    "- [ ] Plan #work #q3\n  - [ ] Book room\n- [x] Done\n```\n- [ ] Code\n```",
+                ),
+                (root_dir.join("a.md"), "Text\n* [ ] Call Alice ^a1b2c"),
+            ],
+        );
+
+        let tasks = open_tasks(&vault)
+            .into_iter()
+            .map(|task| {
+                (
+                    task.uri.to_file_path().unwrap(),
+                    task.line,
+                    task.text,
+                    task.tags,
+                    task.parents,
+                )
+            })
+            .collect_vec();
+
+        assert_eq!(
+            tasks,
+            vec![
+                (
+                    root_dir.join("a.md"),
+                    1,
+                    "Call Alice".to_string(),
+                    vec![],
+                    vec![]
+                ),
+                (
+                    root_dir.join("b.md"),
+                    0,
+                    "Plan #work #q3".to_string(),
+                    vec!["#work".to_string(), "#q3".to_string()],
+                    vec![]
+                ),
+                (
+                    root_dir.join("b.md"),
+                    1,
+                    "Book room".to_string(),
+                    vec![],
+                    vec!["Plan #work #q3".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn open_tasks_with_parents() {
+        let lines = [
+            "# Project",
+            "- [ ] Ship release ^a1b2c",
+            "  - Docs",
+            "    - [ ] Write changelog #docs",
+            "    - [x] Update readme",
+            "  - [ ] Tag version",
+            "```",
+            "- [ ] Not a task",
+            "```",
+            "Other text",
+            "1. [ ] Unrelated",
+        ];
+
+        let task = |line, text: &str, parents: &[&str], index: Option<&str>| ListTask {
+            line,
+            text: text.to_string(),
+            parents: parents.iter().map(|parent| parent.to_string()).collect(),
+            index: index.map(String::from),
+        };
+
+        assert_eq!(
+            list_tasks(&lines, |line| line == 7),
+            vec![
+                task(1, "Ship release", &[], Some("a1b2c")),
+                task(3, "Write changelog #docs", &["Ship release", "Docs"], None),
+                task(5, "Tag version", &["Ship release"], None),
+                task(10, "Unrelated", &[], None),
+            ]
+        );
+    }
+}
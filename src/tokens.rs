@@ -1,21 +1,212 @@
-use std::{collections::HashSet, iter, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
 use itertools::Itertools;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use tower_lsp::lsp_types::{SemanticToken, SemanticTokensParams, SemanticTokensResult};
+use tower_lsp::lsp_types::{
+    Position, Range, SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensDelta,
+    SemanticTokensEdit, SemanticTokensFullDeltaResult, SemanticTokensRangeParams,
+    SemanticTokensRangeResult, Url,
+};
 
-use crate::{config::Settings, diagnostics::path_unresolved_references, vault::Vault};
+use crate::{
+    config::Settings,
+    diagnostics::path_unresolved_references,
+    vault::{Reference, Vault},
+};
 
+/// The legend of the semantic tokens; the index of a type in it is the type of the encoded token
+pub const TOKEN_TYPES: [SemanticTokenType; 6] = [
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::new("wikiLink"),
+    SemanticTokenType::new("markdownLink"),
+    SemanticTokenType::new("heading"),
+    SemanticTokenType::new("tag"),
+    SemanticTokenType::new("footnote"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenType {
+    Unresolved = 0,
+    WikiLink = 1,
+    MarkdownLink = 2,
+    Heading = 3,
+    Tag = 4,
+    Footnote = 5,
+}
+
+impl TokenType {
+    fn of(reference: &Reference) -> TokenType {
+        match reference {
+            Reference::WikiFileLink(..)
+            | Reference::WikiHeadingLink(..)
+            | Reference::WikiIndexedBlockLink(..) => TokenType::WikiLink,
+            Reference::MDFileLink(..)
+            | Reference::MDHeadingLink(..)
+            | Reference::MDIndexedBlockLink(..)
+            | Reference::LinkRef(..) => TokenType::MarkdownLink,
+            Reference::Tag(..) => TokenType::Tag,
+            Reference::Footnote(..) => TokenType::Footnote,
+        }
+    }
+}
+
+/// A token on one line, from the `start` character up to the `end` character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    line: u32,
+    start: u32,
+    end: u32,
+    token_type: TokenType,
+}
+
+impl Span {
+    /// Tokens cannot span lines; constructs spanning lines are not highlighted
+    fn new(range: &Range, token_type: TokenType) -> Option<Span> {
+        (range.start.line == range.end.line && range.start.character < range.end.character)
+            .then_some(Span {
+                line: range.start.line,
+                start: range.start.character,
+                end: range.end.character,
+                token_type,
+            })
+    }
+
+    /// Whether any part of the token is within `range`
+    fn intersects(&self, range: &Range) -> bool {
+        let (start, end) = (
+            Position::new(self.line, self.start),
+            Position::new(self.line, self.end),
+        );
+
+        start < range.end && range.start < end
+    }
+}
+
+/// The tokens of the whole file
 pub fn semantic_tokens_full(
     vault: &Vault,
     path: &Path,
-    _params: SemanticTokensParams,
     settings: &Settings,
-) -> Option<SemanticTokensResult> {
+) -> Option<Vec<SemanticToken>> {
+    if !settings.semantic_tokens {
+        return None;
+    }
+
+    Some(encode(&spans(vault, path)?))
+}
+
+/// The tokens last sent for each document, so that later requests for the whole file can be answered with only the
+/// tokens that changed
+#[derive(Debug, Default)]
+pub struct SentTokens {
+    last_result_id: u64,
+    documents: HashMap<Url, (String, Vec<SemanticToken>)>,
+}
+
+impl SentTokens {
+    /// Remember the tokens sent for the document; they are returned with the id later requests refer to them by
+    pub fn send(&mut self, uri: Url, data: Vec<SemanticToken>) -> SemanticTokens {
+        self.last_result_id += 1;
+        let result_id = self.last_result_id.to_string();
+
+        self.documents
+            .insert(uri, (result_id.clone(), data.clone()));
+
+        SemanticTokens {
+            result_id: Some(result_id),
+            data,
+        }
+    }
+
+    /// The edits from the tokens sent with `previous_result_id` to `data`; all tokens if those are no longer known
+    pub fn send_delta(
+        &mut self,
+        uri: Url,
+        previous_result_id: &str,
+        data: Vec<SemanticToken>,
+    ) -> SemanticTokensFullDeltaResult {
+        let edit = self
+            .documents
+            .get(&uri)
+            .filter(|(result_id, _)| result_id == previous_result_id)
+            .map(|(_, previous)| edit(previous, &data));
+
+        let tokens = self.send(uri, data);
+
+        match edit {
+            Some(edit) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: tokens.result_id,
+                edits: edit.into_iter().collect(),
+            }),
+            None => SemanticTokensFullDeltaResult::Tokens(tokens),
+        }
+    }
+
+    pub fn forget(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+    }
+
+    /// Forget the tokens of all documents, such as when the vault is reconstructed and the tokens of any file may
+    /// have changed; the next requests are answered with all tokens
+    pub fn clear(&mut self) {
+        self.documents.clear();
+    }
+}
+
+/// The edit replacing the tokens of `previous` that differ from `current`, keeping those the two start and end with;
+/// none if they are the same. Edits index the integers of the encoded tokens, of which there are five per token.
+fn edit(previous: &[SemanticToken], current: &[SemanticToken]) -> Option<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current)
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(previous, current)| previous == current)
+        .count();
+
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix..current.len() - suffix];
+
+    (deleted > 0 || !inserted.is_empty()).then(|| SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted as u32,
+        data: Some(inserted.to_vec()),
+    })
+}
+
+/// The tokens within the requested range, such as the part of a large file visible in the editor
+pub fn semantic_tokens_range(
+    vault: &Vault,
+    path: &Path,
+    params: SemanticTokensRangeParams,
+    settings: &Settings,
+) -> Option<SemanticTokensRangeResult> {
     if !settings.semantic_tokens {
         return None;
     }
 
+    let spans = spans(vault, path)?
+        .into_iter()
+        .filter(|span| span.intersects(&params.range))
+        .collect_vec();
+
+    // Each token is relative to the previous one in the range; the first is relative to the start of the file
+    Some(SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: encode(&spans),
+    }))
+}
+
+/// The tokens of the file, in document order and not overlapping
+fn spans(vault: &Vault, path: &Path) -> Option<Vec<Span>> {
     let references_in_file = vault.select_references(Some(path))?;
 
     let path_unresolved: Option<HashSet<_>> =
@@ -26,59 +217,240 @@ pub fn semantic_tokens_full(
                 .collect()
         });
 
-    let tokens = references_in_file
+    let references = references_in_file.into_iter().filter_map(|(_, reference)| {
+        let is_unresolved = path_unresolved
+            .as_ref()
+            .is_some_and(|unresolved| unresolved.contains(reference));
+
+        Span::new(
+            &reference.data().range,
+            match is_unresolved {
+                true => TokenType::Unresolved,
+                false => TokenType::of(reference),
+            },
+        )
+    });
+
+    let headings = vault
+        .select_headings(path)
         .into_iter()
-        .sorted_by_key(|(_, reference)| {
-            (
-                reference.data().range.start.line,
-                reference.data().range.start.character,
-            )
-        })
-        .fold(vec![], |acc, (_path, reference)| {
-            let range = reference.data().range;
-
-            let is_unresolved = path_unresolved
-                .as_ref()
-                .is_some_and(|unresolved| unresolved.contains(reference));
-
-            match acc[..] {
-                [] => vec![(
-                    reference,
-                    SemanticToken {
-                        delta_line: range.start.line,
-                        delta_start: range.start.character,
-                        length: range.end.character - range.start.character,
-                        token_type: if is_unresolved { 1 } else { 0 },
-                        token_modifiers_bitset: 0,
-                    },
-                )],
-                [.., (prev_ref, _)] => acc
-                    .into_iter()
-                    .chain(iter::once((
-                        reference,
-                        SemanticToken {
-                            delta_line: range.start.line - prev_ref.data().range.start.line,
-                            delta_start: if range.start.line == prev_ref.data().range.start.line {
-                                range.start.character - prev_ref.data().range.start.character
-                            } else {
-                                range.start.character
-                            },
-                            length: range.end.character - range.start.character,
-                            token_type: if is_unresolved { 1 } else { 0 },
-                            token_modifiers_bitset: 0,
-                        },
-                    )))
-                    .collect_vec(),
+        .flatten()
+        .filter_map(|heading| Span::new(&heading.range, TokenType::Heading));
+
+    // References come first so that they take precedence over the headings they are in
+    Some(without_overlaps(references.chain(headings)))
+}
+
+/// Split the spans so that none overlap, in document order: the parts of a span covered by an earlier span, such as a
+/// tag within a heading, are left out. Clients reject overlapping tokens, whose deltas can be negative.
+fn without_overlaps(spans: impl IntoIterator<Item = Span>) -> Vec<Span> {
+    let mut placed: Vec<Span> = vec![];
+
+    for span in spans {
+        let covering = placed
+            .iter()
+            .filter(|other| {
+                other.line == span.line && other.start < span.end && span.start < other.end
+            })
+            .map(|other| (other.start, other.end))
+            .sorted()
+            .collect_vec();
+
+        let mut start = span.start;
+        let mut parts = vec![];
+        for (covered_start, covered_end) in covering {
+            if covered_start > start {
+                parts.push(Span {
+                    start,
+                    end: covered_start,
+                    ..span
+                });
             }
+            start = start.max(covered_end);
+        }
+        if start < span.end {
+            parts.push(Span { start, ..span });
+        }
+
+        placed.extend(parts);
+    }
+
+    placed.sort_by_key(|span| (span.line, span.start));
+
+    placed
+}
+
+/// Encode the spans, which must be in document order and not overlap, relative to the previous one
+fn encode(spans: &[Span]) -> Vec<SemanticToken> {
+    spans
+        .iter()
+        .scan(None, |previous: &mut Option<&Span>, span| {
+            let token = match previous {
+                Some(previous) if previous.line == span.line => SemanticToken {
+                    delta_line: 0,
+                    delta_start: span.start - previous.start,
+                    length: span.end - span.start,
+                    token_type: span.token_type as u32,
+                    token_modifiers_bitset: 0,
+                },
+                _ => SemanticToken {
+                    delta_line: span.line - previous.map_or(0, |previous| previous.line),
+                    delta_start: span.start,
+                    length: span.end - span.start,
+                    token_type: span.token_type as u32,
+                    token_modifiers_bitset: 0,
+                },
+            };
+
+            *previous = Some(span);
+
+            Some(token)
         })
-        .into_par_iter()
-        .map(|(_, token)| token)
-        .collect::<Vec<_>>(); // TODO: holy this is bad
-
-    Some(SemanticTokensResult::Tokens(
-        tower_lsp::lsp_types::SemanticTokens {
-            result_id: None,
-            data: tokens,
-        },
-    ))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{
+        Position, Range, SemanticToken, SemanticTokensRangeParams, SemanticTokensRangeResult,
+        TextDocumentIdentifier, Url,
+    };
+
+    use crate::vault::{test_settings, Vault};
+
+    use super::{edit, encode, semantic_tokens_range, without_overlaps, Span, TokenType};
+
+    #[test]
+    fn tag_in_heading_does_not_overlap() {
+        let span = |line, start, end, token_type| Span {
+            line,
+            start,
+            end,
+            token_type,
+        };
+
+        // `## Plans #todo for [[Note]]` on line 2, and a tag on line 4
+        let spans = without_overlaps([
+            span(2, 9, 14, TokenType::Tag),
+            span(2, 19, 27, TokenType::WikiLink),
+            span(4, 0, 4, TokenType::Tag),
+            span(2, 0, 27, TokenType::Heading),
+        ]);
+
+        assert_eq!(
+            spans,
+            vec![
+                span(2, 0, 9, TokenType::Heading),
+                span(2, 9, 14, TokenType::Tag),
+                span(2, 14, 19, TokenType::Heading),
+                span(2, 19, 27, TokenType::WikiLink),
+                span(4, 0, 4, TokenType::Tag),
+            ]
+        );
+
+        let token = |delta_line, delta_start, length, token_type| SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        };
+
+        assert_eq!(
+            encode(&spans),
+            vec![
+                token(2, 0, 9, 3),
+                token(0, 9, 5, 4),
+                token(0, 5, 5, 3),
+                token(0, 5, 8, 1),
+                token(2, 0, 4, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_in_range() {
+        let span = |line, start, end| Span {
+            line,
+            start,
+            end,
+            token_type: TokenType::Tag,
+        };
+        let range = Range {
+            start: Position::new(3, 5),
+            end: Position::new(6, 0),
+        };
+
+        assert!(!span(2, 0, 10).intersects(&range));
+        assert!(!span(3, 0, 5).intersects(&range));
+        assert!(span(3, 4, 8).intersects(&range));
+        assert!(span(5, 0, 2).intersects(&range));
+        assert!(!span(6, 0, 4).intersects(&range));
+    }
+
+    #[test]
+    fn range_tokens_relative_to_file_start() {
+        let (root_dir, settings) = test_settings();
+        let path = root_dir.join("a.md");
+        let vault = Vault::of_notes(
+            &settings,
+            &root_dir,
+            [(path.clone(), "# Head\n\ntext\n\n#one and #two\n\n#three")],
+        );
+
+        let params = SemanticTokensRangeParams {
+            text_document: TextDocumentIdentifier {
+                uri: Url::from_file_path(&path).unwrap(),
+            },
+            range: Range {
+                start: Position::new(3, 0),
+                end: Position::new(5, 0),
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let Some(SemanticTokensRangeResult::Tokens(tokens)) =
+            semantic_tokens_range(&vault, &path, params, &settings)
+        else {
+            panic!("no tokens in range")
+        };
+
+        // As the LSP specification requires, the first token in the range is relative to the start of the file rather
+        // than to the start of the range
+        let token = |delta_line, delta_start, length| SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type: TokenType::Tag as u32,
+            token_modifiers_bitset: 0,
+        };
+        assert_eq!(tokens.data, vec![token(4, 0, 4), token(0, 9, 4)]);
+    }
+
+    #[test]
+    fn edit_between_tokens() {
+        let token = |delta_line| SemanticToken {
+            delta_line,
+            delta_start: 0,
+            length: 4,
+            token_type: 1,
+            token_modifiers_bitset: 0,
+        };
+        let previous = [token(0), token(1), token(2), token(3)];
+
+        assert_eq!(edit(&previous, &previous), None);
+
+        let changed = edit(&previous, &[token(0), token(5), token(3)]).unwrap();
+        assert_eq!((changed.start, changed.delete_count), (5, 10));
+        assert_eq!(changed.data, Some(vec![token(5)]));
+
+        let appended = edit(
+            &previous,
+            &[token(0), token(1), token(2), token(3), token(4)],
+        )
+        .unwrap();
+        assert_eq!((appended.start, appended.delete_count), (20, 0));
+        assert_eq!(appended.data, Some(vec![token(4)]));
+    }
 }
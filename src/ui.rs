@@ -1,12 +1,23 @@
-use std::path::Path;
+use std::{iter, ops::Range, path::Path};
 
 use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use ropey::Rope;
 use tower_lsp::lsp_types::{MarkupContent, MarkupKind};
 
-use crate::vault::{get_obsidian_ref_path, Preview, Reference, Referenceable, Vault};
+use crate::{
+    config::Settings,
+    vault::{get_obsidian_ref_path, Preview, Reference, Referenceable, Vault},
+};
 
-fn referenceable_string(vault: &Vault, referenceables: &[Referenceable]) -> Option<String> {
+fn referenceable_string(
+    vault: &Vault,
+    referenceables: &[Referenceable],
+    settings: &Settings,
+) -> Option<String> {
     let referenceable = referenceables.first()?;
+    let backlink_limit = settings.hover_backlink_limit;
 
     let preview = vault.select_referenceable_preview(referenceable);
 
@@ -28,33 +39,81 @@ fn referenceable_string(vault: &Vault, referenceables: &[Referenceable]) -> Opti
         .flatten()
         .collect_vec()
     {
-        references if !references.is_empty() => references
-            .into_iter()
-            .take(20)
-            .flat_map(|(path, reference)| {
-                let line = String::from_iter(
-                    vault.select_line(path, reference.data().range.start.line as isize)?,
-                );
+        references if !references.is_empty() => {
+            let total = references.len();
 
-                let path = get_obsidian_ref_path(vault.root_dir(), path)?;
+            let backlinks = references
+                .into_iter()
+                .take(backlink_limit)
+                .flat_map(|(path, reference)| {
+                    let line = String::from_iter(
+                        vault.select_line(path, reference.data().range.start.line as isize)?,
+                    );
 
-                Some(format!("- `{}`: `{}`", path, line)) // and select indented list
-            })
-            .join("\n"),
+                    let path = get_obsidian_ref_path(vault.root_dir(), path)?;
+
+                    Some(format!("- `{}`: `{}`", path, line)) // and select indented list
+                })
+                .join("\n");
+
+            match total > backlink_limit {
+                true => format!(
+                    "{}\n\n`Showing {} of {} backlinks`",
+                    backlinks, backlink_limit, total
+                ),
+                false => backlinks,
+            }
+        }
         _ => "No Backlinks".to_string(),
     };
 
+    let frontmatter = match settings.hover_frontmatter {
+        true => frontmatter_table(vault, referenceable, &settings.hover_frontmatter_keys)
+            .map(|table| format!("{table}\n\n")),
+        false => None,
+    };
+
     Some(format!(
-        "{}\n\n`...`\n\n---\n\n# Backlinks\n\n{}",
-        written_text_preview, backlinks_preview
+        "{}{}\n\n`...`\n\n---\n\n# Backlinks\n\n{}",
+        frontmatter.unwrap_or_default(),
+        written_text_preview,
+        backlinks_preview
     ))
 }
 
+/// The fields of the frontmatter of the note of `referenceable` named by `keys`, in that order, as a table; frontmatter
+/// can be long, so other fields are left out
+fn frontmatter_table(
+    vault: &Vault,
+    referenceable: &Referenceable,
+    keys: &[String],
+) -> Option<String> {
+    let (Referenceable::File(path, _)
+    | Referenceable::Heading(path, _)
+    | Referenceable::IndexedBlock(path, _)) = referenceable
+    else {
+        return None;
+    };
+
+    let metadata = vault.md_files.get(*path)?.metadata.as_ref()?;
+
+    let rows = keys
+        .iter()
+        .filter_map(|key| {
+            let value = metadata.field(key)?;
+            Some(format!("| {} | {} |", key, value.replace('|', "\\|")))
+        })
+        .collect_vec();
+
+    (!rows.is_empty()).then(|| format!("| | |\n| --- | --- |\n{}", rows.join("\n")))
+}
+
 pub fn preview_referenceable(
     vault: &Vault,
     referenceable: &Referenceable,
+    settings: &Settings,
 ) -> Option<MarkupContent> {
-    let display = referenceable_string(vault, &[referenceable.clone()])?;
+    let display = referenceable_string(vault, &[referenceable.clone()], settings)?;
 
     Some(MarkupContent {
         kind: MarkupKind::Markdown,
@@ -68,6 +127,7 @@ pub fn preview_reference(
     vault: &Vault,
     reference_path: &Path,
     reference: &Reference,
+    settings: &Settings,
 ) -> Option<MarkupContent> {
     match reference {
         WikiFileLink(..)
@@ -81,7 +141,20 @@ pub fn preview_reference(
             let referenceables_for_reference =
                 vault.select_referenceables_for_reference(reference, reference_path);
 
-            let display = referenceable_string(vault, &referenceables_for_reference)?;
+            // Embeds show what they transclude, as the rendered note would, rather than a preview of their target
+            if is_embed(vault, reference_path, reference) {
+                if let Some(content) = referenceables_for_reference
+                    .first()
+                    .and_then(|referenceable| embedded_content(vault, referenceable, 0))
+                {
+                    return Some(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: format!("`Embedded Preview:`\n\n{}", content),
+                    });
+                }
+            }
+
+            let display = referenceable_string(vault, &referenceables_for_reference, settings)?;
 
             Some(MarkupContent {
                 kind: MarkupKind::Markdown,
@@ -91,3 +164,151 @@ pub fn preview_reference(
         Tag(_) => None,
     }
 }
+
+/// Embeds within embedded content are expanded this many levels deep; this stops notes that embed each other from
+/// expanding forever
+const MAX_EMBED_DEPTH: usize = 3;
+
+/// Whether the reference is an embed, as in `![[note#^block]]`
+fn is_embed(vault: &Vault, path: &Path, reference: &Reference) -> bool {
+    let start = reference.data().range.start;
+
+    start.character > 0
+        && vault
+            .select_line(path, start.line as isize)
+            .and_then(|line| line.get(start.character as usize - 1).copied())
+            == Some('!')
+}
+
+/// The lines of the file that an embed of `referenceable` transcludes: a whole heading section, a block, or the entire
+/// file
+fn embedded_lines<'a>(
+    vault: &'a Vault,
+    referenceable: &'a Referenceable,
+) -> Option<(&'a Path, Range<usize>)> {
+    Some((
+        referenceable.get_path(),
+        vault.select_referenceable_lines(referenceable)?,
+    ))
+}
+
+/// The full content transcluded by an embed of `referenceable`; embeds within it are expanded in block quotes until
+/// `MAX_EMBED_DEPTH`
+fn embedded_content(vault: &Vault, referenceable: &Referenceable, depth: usize) -> Option<String> {
+    static BLOCK_INDEX_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r" \^\w+\s*$").unwrap());
+
+    let (path, lines) = embedded_lines(vault, referenceable)?;
+
+    let embeds = match depth < MAX_EMBED_DEPTH {
+        true => vault
+            .select_references(Some(path))?
+            .into_iter()
+            .filter(|(path, reference)| {
+                lines.contains(&(reference.data().range.start.line as usize))
+                    && is_embed(vault, path, reference)
+            })
+            .collect_vec(),
+        false => vec![],
+    };
+
+    let content = lines
+        .filter_map(|ln| {
+            let line = String::from_iter(vault.select_line(path, ln as isize)?);
+            let line = BLOCK_INDEX_RE.replace(line.trim_end(), "").to_string();
+
+            let nested = embeds
+                .iter()
+                .filter(|(_, reference)| reference.data().range.start.line as usize == ln)
+                .filter_map(|(path, reference)| {
+                    let target = vault
+                        .select_referenceables_for_reference(reference, path)
+                        .into_iter()
+                        .next()?;
+
+                    embedded_content(vault, &target, depth + 1)
+                })
+                .map(|content| content.lines().map(|line| format!("> {}", line)).join("\n"));
+
+            Some(iter::once(line).chain(nested).join("\n"))
+        })
+        .join("\n");
+
+    Some(content.trim().to_string()).filter(|content| !content.is_empty())
+}
+
+/// The text of markdown as it reads, for showing it inline: links are replaced by their display text, or by their target
+/// as `Note > Heading`, tags lose their `#`, footnote references are dropped, and whitespace is collapsed
+pub fn plain_text(text: &str) -> String {
+    let rope = Rope::from_str(text);
+    let chars = text.chars().collect_vec();
+
+    let references = Reference::new(text, "")
+        .filter_map(|reference| {
+            let range = reference.data().range;
+            let start = rope.try_line_to_char(range.start.line as usize).ok()?
+                + range.start.character as usize;
+            let end =
+                rope.try_line_to_char(range.end.line as usize).ok()? + range.end.character as usize;
+
+            Some((start..end, reference))
+        })
+        .sorted_by_key(|(range, _)| range.start);
+
+    let mut plain = String::new();
+    let mut position = 0;
+    for (range, reference) in references {
+        let Some(before) = chars.get(position..range.start) else {
+            continue; // overlaps the previous reference
+        };
+
+        plain.extend(before);
+        plain.push_str(&match reference {
+            Reference::Tag(data) => data.reference_text.trim_start_matches('#').to_string(),
+            Reference::Footnote(..) => String::new(),
+            _ => match &reference.data().display_text {
+                Some(display) if !display.is_empty() => display.clone(),
+                _ => reference
+                    .data()
+                    .reference_text
+                    .split('#')
+                    .filter(|part| !part.is_empty())
+                    .join(" > "),
+            },
+        });
+        position = range.end;
+    }
+    plain.extend(chars.get(position..).unwrap_or_default());
+
+    plain.split_whitespace().join(" ")
+}
+
+/// At most the first `length` characters of the text, with `...` if any were cut
+pub fn truncated(text: &str, length: usize) -> String {
+    match text.char_indices().nth(length) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plain_text, truncated};
+
+    #[test]
+    fn plain_text_of_markdown() {
+        assert_eq!(
+            plain_text("See [[Note#Setup]] and [[Other|the other]]  for #project/a\n[docs](docs.md)[^1] ^block"),
+            "See Note > Setup and the other for project/a docs ^block"
+        );
+    }
+
+    #[test]
+    fn truncation_at_multibyte_boundaries() {
+        assert_eq!(truncated("Größenordnung", 4), "Größ...");
+        assert_eq!(truncated("日本語のノート", 3), "日本語...");
+        assert_eq!(truncated("🦀🦀🦀 crabs", 2), "🦀🦀...");
+        assert_eq!(truncated("日本語", 3), "日本語");
+        assert_eq!(truncated("short", 5), "short");
+        assert_eq!(truncated("", 0), "");
+    }
+}
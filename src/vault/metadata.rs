@@ -1,10 +1,18 @@
+use itertools::Itertools;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
 
 #[derive(Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
 pub struct MDMetadata {
+    #[serde(default)]
     aliases: Vec<String>,
+    #[serde(default)]
+    deprecated: bool,
+    /// Every field of the frontmatter with a value that fits on a line, in order
+    #[serde(skip)]
+    fields: Vec<(String, String)>,
 }
 
 impl MDMetadata {
@@ -18,14 +26,48 @@ impl MDMetadata {
 
         let metadata_match = metadata_match?;
 
-        let md_metadata = serde_yaml::from_str::<MDMetadata>(metadata_match.as_str());
+        let mut md_metadata = serde_yaml::from_str::<MDMetadata>(metadata_match.as_str()).ok()?;
 
-        md_metadata.ok()
+        md_metadata.fields = serde_yaml::from_str::<Mapping>(metadata_match.as_str())
+            .map(|mapping| {
+                mapping
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.as_str()?.to_string(), inline(value)?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(md_metadata)
+    }
+
+    /// The value of the frontmatter field `key`, with lists joined by commas; fields of nested mappings have none
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == key)
+            .map(|(_, value)| value.as_str())
     }
 
     pub fn aliases(&self) -> &[String] {
         &self.aliases
     }
+
+    /// Whether the note is marked `deprecated: true` in its frontmatter
+    pub fn deprecated(&self) -> bool {
+        self.deprecated
+    }
+}
+
+/// A scalar or a list of scalars as text, as in `draft` or `project, idea`
+fn inline(value: &Value) -> Option<String> {
+    match value {
+        Value::String(string) => Some(string.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(bool) => Some(bool.to_string()),
+        Value::Sequence(values) => Some(values.iter().filter_map(inline).join(", ")),
+        Value::Tagged(tagged) => inline(&tagged.value),
+        Value::Null | Value::Mapping(_) => None,
+    }
 }
 
 #[cfg(test)]
@@ -50,4 +92,26 @@ aliases:
         .unwrap();
         assert_eq!(metadata.aliases(), &["alias1", "alias2"]);
     }
+
+    #[test]
+    fn test_deprecated() {
+        let metadata = MDMetadata::new("---\ndeprecated: true\n---").unwrap();
+        assert!(metadata.deprecated());
+        assert!(metadata.aliases().is_empty());
+    }
+
+    #[test]
+    fn frontmatter_fields() {
+        let metadata = MDMetadata::new(
+            "---\ntitle: Plans\ntags: [project, idea]\ndate: 2024-03-05\nrating: 4\nnested:\n  key: value\n---",
+        )
+        .unwrap();
+
+        assert_eq!(metadata.field("title"), Some("Plans"));
+        assert_eq!(metadata.field("tags"), Some("project, idea"));
+        assert_eq!(metadata.field("date"), Some("2024-03-05"));
+        assert_eq!(metadata.field("rating"), Some("4"));
+        assert_eq!(metadata.field("nested"), None);
+        assert_eq!(metadata.field("status"), None);
+    }
 }
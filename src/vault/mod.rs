@@ -4,7 +4,7 @@ mod parsing;
 use std::{
     char,
     collections::{HashMap, HashSet},
-    hash::Hash,
+    hash::{DefaultHasher, Hash, Hasher},
     iter,
     ops::{Deref, DerefMut, Not, Range},
     path::{Path, PathBuf},
@@ -19,51 +19,74 @@ use regex::{Captures, Match, Regex};
 use ropey::Rope;
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::Position;
+use unicode_normalization::UnicodeNormalization;
 use walkdir::WalkDir;
 
 impl Vault {
-    pub fn construct_vault(context: &Settings, root_dir: &Path) -> Result<Vault, std::io::Error> {
-        let md_file_paths = WalkDir::new(root_dir)
+    /// Construct the vault from the files in `root_dir`. Files whose content is unchanged from `old` reuse
+    /// their existing parse instead of being parsed again.
+    pub fn construct_vault(
+        context: &Settings,
+        root_dir: &Path,
+        old: Option<&Vault>,
+    ) -> Result<Vault, std::io::Error> {
+        let file_paths = WalkDir::new(root_dir)
             .into_iter()
-            .filter_entry(|e| {
-                !e.file_name()
-                    .to_str()
-                    .map(|s| s.starts_with('.') || s == "logseq") // TODO: This is a temporary fix; a hidden config is better
-                    .unwrap_or(false)
-            })
+            .filter_entry(|e| !e.file_name().to_str().is_some_and(is_ignored_file_name))
             .flatten()
-            .filter(|f| f.path().extension().and_then(|e| e.to_str()) == Some("md"))
             .collect_vec();
 
-        let md_files: HashMap<PathBuf, MDFile> = md_file_paths
+        // Attachments are not parsed; links to them only need to know that they exist
+        let (md_file_paths, attachments): (Vec<_>, Vec<_>) = file_paths
+            .into_iter()
+            .filter(|f| !f.file_type().is_dir())
+            .partition(|f| f.path().extension().and_then(|e| e.to_str()) == Some("md"));
+        let attachments = attachments
+            .into_iter()
+            .map(|f| f.into_path())
+            .filter(|path| is_attachment(path))
+            .collect();
+
+        let (md_files, ropes): (HashMap<PathBuf, MDFile>, HashMap<PathBuf, Rope>) = md_file_paths
             .par_iter()
             .flat_map(|p| {
-                let text = std::fs::read_to_string(p.path())?;
-                let md_file = MDFile::new(context, &text, PathBuf::from(p.path()));
+                let path = PathBuf::from(p.path());
+                let text = read_note(&path)?;
+                let content_hash = MDFile::content_hash(&text);
 
-                return Ok::<(PathBuf, MDFile), std::io::Error>((p.path().into(), md_file));
-            })
-            .collect();
+                let unchanged = old.and_then(|old| {
+                    let md_file = old.md_files.get(&path)?;
+                    let rope = old.ropes.get(&path)?;
 
-        let ropes: HashMap<PathBuf, Rope> = md_file_paths
-            .iter()
-            .flat_map(|p| {
-                let text = std::fs::read_to_string(p.path())?;
-                let rope = Rope::from_str(&text);
+                    (md_file.content_hash == content_hash).then(|| (md_file.clone(), rope.clone()))
+                });
+
+                let (md_file, rope) = unchanged.unwrap_or_else(|| {
+                    (
+                        MDFile::new(context, root_dir, &text, path.clone()),
+                        Rope::from_str(&text),
+                    )
+                });
 
-                return Ok::<(PathBuf, Rope), std::io::Error>((p.path().into(), rope));
+                Ok::<_, std::io::Error>(((path.clone(), md_file), (path, rope)))
             })
-            .collect();
+            .unzip();
 
         Ok(Vault {
             ropes: ropes.into(),
             md_files: md_files.into(),
+            attachments,
+            attachments_folder: root_dir.join(&context.attachments_folder),
+            case_insensitive_links: context.case_insensitive_links,
+            unicode_normalize_links: context.unicode_normalize_links,
+            heading_slug_style: context.heading_slug_style,
+            preview_max_lines: context.preview_max_lines,
             root_dir: root_dir.into(),
         })
     }
 
     pub fn update_vault(context: &Settings, old: &mut Vault, new_file: (&PathBuf, &str)) {
-        let new_md_file = MDFile::new(context, new_file.1, new_file.0.clone());
+        let new_md_file = MDFile::new(context, &old.root_dir, new_file.1, new_file.0.clone());
         let new = old.md_files.get_mut(new_file.0);
         match new {
             Some(file) => {
@@ -86,6 +109,142 @@ impl Vault {
             }
         }
     }
+
+    /// Update the vault with the current state of a file on disk; this handles files that were created, changed or deleted.
+    pub fn update_vault_from_disk(context: &Settings, old: &mut Vault, path: &Path) {
+        let ignored = diff_paths(path, &old.root_dir).is_some_and(|relative| {
+            relative.components().any(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .is_some_and(is_ignored_file_name)
+            })
+        });
+
+        if ignored {
+            return;
+        }
+
+        if is_attachment(path) {
+            match path.is_file() {
+                true => old.attachments.insert(path.to_path_buf()),
+                false => old.attachments.remove(path),
+            };
+            return;
+        }
+
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            return;
+        }
+
+        match read_note(path) {
+            Ok(text) => Vault::update_vault(context, old, (&path.to_path_buf(), &text)),
+            Err(_) => {
+                old.md_files.remove(path);
+                old.ropes.remove(path);
+            }
+        }
+    }
+}
+
+/// Read a note from disk. Notes that are not valid UTF-8 (such as latin-1 encoded ones) are decoded lossily rather than
+/// left out of the vault.
+fn read_note(path: &Path) -> Result<String, std::io::Error> {
+    std::fs::read(path).map(decode_note_text)
+}
+
+fn decode_note_text(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|error| String::from_utf8_lossy(error.as_bytes()).into_owned())
+}
+
+/// Extensions of the files other than notes that links can point to, such as `![[diagram.png]]`; these are the files
+/// Obsidian can embed
+pub const ATTACHMENT_EXTENSIONS: [&str; 21] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "svg", "webp", "avif", "pdf", "mp3", "wav", "m4a", "ogg",
+    "flac", "3gp", "webm", "mp4", "ogv", "mov", "mkv", "canvas",
+];
+
+/// Whether the file at `path` is an attachment that links can point to
+pub fn is_attachment(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(is_attachment_extension)
+}
+
+fn is_attachment_extension(ext: &str) -> bool {
+    ATTACHMENT_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+}
+
+/// Whether a link ending is the extension of notes; like other extensions, its case does not matter, as in `note.MD`
+fn is_md_ending(ending: &str) -> bool {
+    ending.eq_ignore_ascii_case(".md")
+}
+
+/// Whether a link with this ending, such as `.md` in `[[note.md]]`, can point to a note or an attachment
+fn is_link_ending(ending: Option<Match>) -> bool {
+    match ending.map(|ending| ending.as_str()) {
+        Some(ending) if is_md_ending(ending) => true,
+        None => true,
+        Some(ending) => ending
+            .rsplit('.')
+            .next()
+            .is_some_and(is_attachment_extension),
+    }
+}
+
+/// The byte index of the `)` closing the destination of a markdown link that starts at `start`, just after the `(`.
+/// As in CommonMark, a destination in `<>` ends at the `>`, other destinations may contain balanced parentheses, as
+/// in `(https://en.wikipedia.org/wiki/Foo_(bar))`, and neither spans lines.
+fn md_link_destination_end(text: &str, start: usize) -> Option<usize> {
+    let destination = text.get(start..)?;
+
+    if let Some(bracketed) = destination.strip_prefix('<') {
+        let end = bracketed.find(['>', '<', '\n', '\r'])?;
+
+        return bracketed[end..].starts_with(">)").then_some(start + 1 + end + 1);
+    }
+
+    let mut depth = 0;
+    let mut chars = destination.char_indices();
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '\n' | '\r' => return None,
+            '\\' => {
+                chars.next();
+            }
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(start + index),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// The lines of the block ending at `line`: the list item on the line, or the paragraph back to the previous blank
+/// line, heading or list item
+fn block_lines(rope: &Rope, line: usize) -> Range<usize> {
+    static BLOCK_START_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"^\s*(#|[-*+] |\d+[.)] |>|$)").unwrap());
+
+    let starts_block = |ln: usize| {
+        rope.get_line(ln)
+            .is_some_and(|text| BLOCK_START_RE.is_match(&text.to_string()))
+    };
+
+    let mut start = line;
+    while start > 0 && !starts_block(start) && !starts_block(start - 1) {
+        start -= 1;
+    }
+
+    start..line + 1
+}
+
+/// Files and folders that are not part of the vault
+fn is_ignored_file_name(name: &str) -> bool {
+    name.starts_with('.') || name == "logseq" // TODO: This is a temporary fix; a hidden config is better
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -134,6 +293,14 @@ impl Hash for Vault {
 pub struct Vault {
     pub md_files: MyHashMap<MDFile>,
     pub ropes: MyHashMap<Rope>,
+    /// Files other than notes, such as images and pdfs, that links can point to
+    pub attachments: HashSet<PathBuf>,
+    /// The folder that links to attachments may be relative to, instead of the root of the vault
+    attachments_folder: PathBuf,
+    case_insensitive_links: bool,
+    unicode_normalize_links: bool,
+    heading_slug_style: HeadingSlugStyle,
+    preview_max_lines: usize,
     root_dir: PathBuf,
 }
 
@@ -230,6 +397,11 @@ impl Vault {
                     .par_bridge()
                     .into_par_iter()
                     .flat_map(|file| file.get_referenceables())
+                    .chain(
+                        self.attachments
+                            .par_iter()
+                            .map(|path| Referenceable::Attachment(path, &self.attachments_folder)),
+                    )
                     .collect::<Vec<_>>();
 
                 let resolved_referenceables_refnames: HashSet<String> = resolved_referenceables
@@ -338,6 +510,8 @@ impl Vault {
                 .into_par_iter()
                 .filter(|(ref_path, reference)| {
                     referenceable.matches_reference(&self.root_dir, reference, ref_path)
+                        && self.matches_heading_chain(reference, referenceable)
+                        && self.matches_case(reference, referenceable)
                 })
                 .map(|(path, reference)| {
                     match std::fs::metadata(path).and_then(|meta| meta.modified()) {
@@ -354,6 +528,27 @@ impl Vault {
         )
     }
 
+    /// Select the file that a link with the given text, as in `[[link text]]`, resolves to
+    pub fn select_file_referenceable(&self, link_text: &str) -> Option<Referenceable<'_>> {
+        self.md_files
+            .iter()
+            .map(|(path, md_file)| Referenceable::File(path, md_file))
+            .find(|referenceable| {
+                matches_path_or_file(link_text, referenceable.get_refname(self.root_dir()))
+            })
+    }
+
+    /// Select every file that a link with the given text may resolve to; a bare name can match notes in several folders
+    pub fn select_file_referenceables(&self, link_text: &str) -> Vec<Referenceable<'_>> {
+        self.md_files
+            .iter()
+            .map(|(path, md_file)| Referenceable::File(path, md_file))
+            .filter(|referenceable| {
+                matches_path_or_file(link_text, referenceable.get_refname(self.root_dir()))
+            })
+            .collect()
+    }
+
     pub fn select_referenceables_for_reference(
         &self,
         reference: &Reference,
@@ -361,11 +556,174 @@ impl Vault {
     ) -> Vec<Referenceable> {
         let referenceables = self.select_referenceable_nodes(None);
 
-        referenceables
+        let resolved = referenceables
+            .into_iter()
+            .filter(|i| {
+                reference.references(self.root_dir(), reference_path, i)
+                    && self.matches_heading_chain(reference, i)
+                    && self.matches_case(reference, i)
+            })
+            .collect_vec();
+
+        // Unresolved referenceables are named by the link text, so a link to a note by a path relative to the linking
+        // note also names one; the note it resolves to is preferred
+        let resolved = match resolved.iter().all(|i| i.is_unresolved()) {
+            true => resolved,
+            false => resolved
+                .into_iter()
+                .filter(|i| !i.is_unresolved())
+                .collect(),
+        };
+
+        // Where notes differ only by case, those named with the same case as the link are preferred
+        let (exact, other): (Vec<_>, Vec<_>) = resolved.into_iter().partition(|i| {
+            matches_exact_case(self.root_dir(), reference, i, self.slug_anchors(), |text| {
+                text.nfc().collect()
+            })
+        });
+        let resolved = match exact.is_empty() {
+            true => other,
+            false => exact,
+        };
+
+        // Notes named by their path, such as `note.md` for `[[note]]`, are preferred over notes with the same name in
+        // folders
+        let (exact_path, other): (Vec<_>, Vec<_>) = resolved
             .into_iter()
-            .filter(|i| reference.references(self.root_dir(), reference_path, i))
+            .partition(|i| self.matches_exact_path(reference, i));
+
+        match exact_path.is_empty() {
+            true => other,
+            false => exact_path,
+        }
+    }
+
+    /// Whether the link names the note of `referenceable` by its path from the root of the vault, as `[[note]]` does
+    /// `note.md` and `[[folder/note]]` does `folder/note.md`, rather than by its name alone
+    pub fn matches_exact_path(&self, reference: &Reference, referenceable: &Referenceable) -> bool {
+        let (WikiFileLink(ReferenceData {
+            reference_text: file,
+            ..
+        })
+        | MDFileLink(ReferenceData {
+            reference_text: file,
+            ..
+        })
+        | WikiHeadingLink(_, file, _)
+        | MDHeadingLink(_, file, _)
+        | WikiIndexedBlockLink(_, file, _)
+        | MDIndexedBlockLink(_, file, _)) = reference
+        else {
+            return false;
+        };
+
+        let Some(path) = referenceable
+            .get_refname(self.root_dir())
+            .and_then(|refname| refname.path)
+        else {
+            return false;
+        };
+
+        let file = decode_link_path(file);
+        link_key(file.trim_start_matches("./").trim_start_matches('/')) == link_key(&path)
+    }
+
+    /// Whether the link names `referenceable` with the right case and the same Unicode normalization, unless links are
+    /// resolved case-insensitively and normalized, and by a heading slug only if the heading slug style allows it
+    pub fn matches_case(&self, reference: &Reference, referenceable: &Referenceable) -> bool {
+        let root_dir = self.root_dir();
+        let slug_anchors = self.slug_anchors();
+
+        match (self.case_insensitive_links, self.unicode_normalize_links) {
+            (true, true) if slug_anchors => true,
+            (true, true) => {
+                matches_exact_case(root_dir, reference, referenceable, slug_anchors, link_key)
+            }
+            (true, false) => matches_exact_case(
+                root_dir,
+                reference,
+                referenceable,
+                slug_anchors,
+                str::to_lowercase,
+            ),
+            (false, true) => {
+                matches_exact_case(root_dir, reference, referenceable, slug_anchors, |text| {
+                    text.nfc().collect()
+                })
+            }
+            (false, false) => matches_exact_case(
+                root_dir,
+                reference,
+                referenceable,
+                slug_anchors,
+                str::to_string,
+            ),
+        }
+    }
+
+    /// Whether markdown links may name headings by their slug, as GitHub renders anchors
+    fn slug_anchors(&self) -> bool {
+        self.heading_slug_style == HeadingSlugStyle::Github
+    }
+
+    /// Notes whose paths differ only by case from another note, as on case-sensitive file systems; links to them are
+    /// ambiguous
+    pub fn select_case_conflicts(&self) -> Vec<Referenceable<'_>> {
+        self.md_files
+            .iter()
+            .into_group_map_by(|(path, _)| path.to_string_lossy().to_lowercase())
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .flatten()
+            .map(|(path, md_file)| Referenceable::File(path, md_file))
+            .collect()
+    }
+
+    /// Notes with the same name as a note in another folder, such as `a/note.md` and `b/note.md`; links naming them
+    /// without a folder are ambiguous
+    pub fn select_name_conflicts(&self) -> Vec<Referenceable<'_>> {
+        self.md_files
+            .iter()
+            .into_group_map_by(|(path, _)| {
+                path.file_stem()
+                    .map(|stem| link_key(&stem.to_string_lossy()))
+            })
+            .into_values()
+            .filter(|files| files.len() > 1)
+            .flatten()
+            .map(|(path, md_file)| Referenceable::File(path, md_file))
             .collect()
     }
+
+    /// Whether `referenceable` is the heading that a chained heading link such as `[[Note#H1#H2]]` names: the earlier
+    /// headings of the link must be parents of it, outermost first, and of no other heading with its text, as the
+    /// chain would not tell them apart. Markdown links name headings by their slug only with GitHub style slugs.
+    /// Anything else is not constrained by this.
+    pub fn matches_heading_chain(
+        &self,
+        reference: &Reference,
+        referenceable: &Referenceable,
+    ) -> bool {
+        let (
+            Reference::WikiHeadingLink(.., link_heading)
+            | Reference::MDHeadingLink(.., link_heading),
+            Referenceable::Heading(path, heading),
+        ) = (reference, referenceable)
+        else {
+            return true;
+        };
+
+        let link_heading = decode_link_path(link_heading);
+        if link_heading.to_lowercase() == heading.heading_text.to_lowercase() {
+            return true;
+        }
+
+        let chain = link_heading.split('#').collect_vec();
+
+        self.select_headings(path).is_some_and(|headings| {
+            is_only_heading_with_parents(headings, heading, &chain[..chain.len() - 1])
+        })
+    }
 }
 
 pub enum Preview {
@@ -393,7 +751,14 @@ impl Vault {
         }
 
         match referenceable {
-            Referenceable::Footnote(_, _) | Referenceable::LinkRefDef(..) => {
+            Referenceable::LinkRefDef(_, refdef) => Some(
+                match &refdef.title {
+                    Some(title) => format!("{}\n\n{}", title, refdef.url),
+                    None => refdef.url.clone(),
+                }
+                .into(),
+            ),
+            Referenceable::Footnote(_, _) => {
                 let range = referenceable.get_range()?;
                 Some(
                     String::from_iter(
@@ -402,38 +767,56 @@ impl Vault {
                     .into(),
                 )
             }
-            Referenceable::Heading(_, _) => {
-                let range = referenceable.get_range()?;
-                Some(
-                    (range.start.line..=range.end.line + 10)
-                        .filter_map(|ln| self.select_line(referenceable.get_path(), ln as isize)) // flatten those options!
-                        .map(String::from_iter)
-                        .join("")
-                        .into(),
-                )
-            }
-            Referenceable::IndexedBlock(_, _) => {
-                let range = referenceable.get_range()?;
-                self.select_line(referenceable.get_path(), range.start.line as isize)
+            Referenceable::Heading(..)
+            | Referenceable::IndexedBlock(..)
+            | Referenceable::File(..) => Some(
+                self.select_referenceable_lines(referenceable)?
+                    .take(self.preview_max_lines)
+                    .filter_map(|ln| self.select_line(referenceable.get_path(), ln as isize)) // flatten those options!
                     .map(String::from_iter)
-                    .map(Into::into)
-            }
-            Referenceable::File(_, _) => {
-                Some(
-                    (0..=13)
-                        .filter_map(|ln| self.select_line(referenceable.get_path(), ln as isize)) // flatten those options!
-                        .map(String::from_iter)
-                        .join("")
-                        .into(),
-                )
-            }
+                    .join("")
+                    .into(),
+            ),
             Referenceable::Tag(_, _) => None,
             Referenceable::UnresovledFile(_, _) => None,
+            Referenceable::Attachment(..) => None,
             Referenceable::UnresolvedHeading(_, _, _) => None,
             Referenceable::UnresovledIndexedBlock(_, _, _) => None,
         }
     }
 
+    /// The lines of the file that make up `referenceable`: the section of a heading, up to the next heading of the same
+    /// or a higher level; the paragraph or list item of a block; or the whole file
+    pub fn select_referenceable_lines(
+        &self,
+        referenceable: &Referenceable,
+    ) -> Option<Range<usize>> {
+        let path = referenceable.get_path();
+        let rope = self.ropes.get(path)?;
+
+        match referenceable {
+            Referenceable::File(..) => Some(0..rope.len_lines()),
+            Referenceable::IndexedBlock(_, block) => {
+                Some(block_lines(rope, block.range.start.line as usize))
+            }
+            Referenceable::Heading(_, heading) => {
+                let start = heading.range.start.line as usize;
+                let end = self
+                    .select_headings(path)?
+                    .iter()
+                    .filter(|other| {
+                        other.range.start.line as usize > start && other.level <= heading.level
+                    })
+                    .map(|other| other.range.start.line as usize)
+                    .min()
+                    .unwrap_or(rope.len_lines());
+
+                Some(start..end)
+            }
+            _ => None,
+        }
+    }
+
     pub fn select_blocks(&self) -> Vec<Block<'_>> {
         self.ropes
             .par_iter()
@@ -549,28 +932,55 @@ pub struct MDFile {
     pub link_reference_definitions: Vec<MDLinkReferenceDefinition>,
     pub metadata: Option<MDMetadata>,
     pub codeblocks: Vec<MDCodeBlock>,
+    pub callouts: Vec<MDCallout>,
+    /// Hash of the text this file was parsed from
+    pub content_hash: u64,
 }
 
 impl MDFile {
-    fn new(context: &Settings, text: &str, path: PathBuf) -> MDFile {
+    fn new(context: &Settings, root_dir: &Path, text: &str, path: PathBuf) -> MDFile {
         let code_blocks = MDCodeBlock::new(text).collect_vec();
-        let file_name = path.file_stem().expect("file should have file stem").to_str().unwrap_or_default();
+        // A path without a file name, such as one of an untitled buffer, is parsed like any other
+        let file_name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let folder = path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(root_dir).ok())
+            .unwrap_or(Path::new(""));
+        let html_links = context
+            .html_links
+            .then(|| Reference::new_html(text, file_name, folder))
+            .into_iter()
+            .flatten();
+        let references = Reference::new(text, file_name).chain(html_links);
         let links = match context {
             Settings {
                 references_in_codeblocks: false,
                 ..
-            } => Reference::new(text, file_name)
+            } => references
                 .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)))
                 .collect_vec(),
-            _ => Reference::new(text, file_name).collect_vec(),
+            _ => references.collect_vec(),
         };
         let headings = MDHeading::new(text)
-            .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)));
+            .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)))
+            .map(|heading| match context.attribute_anchors {
+                true => heading.without_attribute_anchor(),
+                false => heading,
+            });
         let footnotes = MDFootnote::new(text)
             .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)));
         let link_refs = MDLinkReferenceDefinition::new(text)
             .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)));
+        let attribute_anchors = context
+            .attribute_anchors
+            .then(|| MDIndexedBlock::new_attribute_anchors(text))
+            .into_iter()
+            .flatten();
         let indexed_blocks = MDIndexedBlock::new(text)
+            .chain(attribute_anchors)
             .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)));
         let tags = match context {
             Settings {
@@ -581,7 +991,14 @@ impl MDFile {
                 .collect_vec(),
             _ => MDTag::new(text).collect_vec(),
         };
+        let tags = MDTag::new_frontmatter(text)
+            .into_iter()
+            .chain(tags)
+            .collect_vec();
         let metadata = MDMetadata::new(text);
+        let callouts = MDCallout::new(text)
+            .filter(|it| !code_blocks.iter().any(|codeblock| codeblock.includes(it)))
+            .collect();
 
         MDFile {
             references: links,
@@ -593,12 +1010,27 @@ impl MDFile {
             link_reference_definitions: link_refs.collect(),
             metadata,
             codeblocks: code_blocks,
+            callouts,
+            content_hash: MDFile::content_hash(text),
         }
     }
 
+    fn content_hash(text: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
     pub fn file_name(&self) -> Option<&str> {
         self.path.file_stem()?.to_str()
     }
+
+    /// The title of the note: its first heading
+    pub fn title(&self) -> Option<&str> {
+        self.headings
+            .first()
+            .map(|heading| heading.heading_text.as_str())
+    }
 }
 
 impl MDFile {
@@ -613,6 +1045,8 @@ impl MDFile {
             link_reference_definitions,
             metadata: _,
             codeblocks: _,
+            callouts: _,
+            content_hash: _,
         } = self;
 
         iter::once(Referenceable::File(&self.path, self))
@@ -686,8 +1120,9 @@ impl Default for Reference {
 
 use Reference::*;
 
-use crate::config::Settings;
+use crate::config::{HeadingSlugStyle, Settings};
 
+pub use self::parsing::MDCallout;
 use self::{metadata::MDMetadata, parsing::MDCodeBlock};
 
 impl Reference {
@@ -705,6 +1140,16 @@ impl Reference {
         }
     }
 
+    /// Whether the display text of a wiki link only repeats its target, as in `[[Note|Note]]`
+    pub fn has_redundant_display_text(&self) -> bool {
+        match self {
+            WikiFileLink(data) | WikiHeadingLink(data, ..) | WikiIndexedBlockLink(data, ..) => {
+                data.display_text.as_ref() == Some(&data.reference_text)
+            }
+            _ => false,
+        }
+    }
+
     pub fn matches_type(&self, other: &Reference) -> bool {
         match &other {
             Tag(..) => matches!(self, Tag(..)),
@@ -721,37 +1166,46 @@ impl Reference {
 
     pub fn new<'a>(text: &'a str, file_name: &'a str) -> impl Iterator<Item = Reference> + 'a {
         static WIKI_LINK_RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\[\[(?<filepath>[^\[\]\|\.\#]+)?(\#(?<infileref>[^\[\]\.\|]+))?(?<ending>\.[^\# <>]+)?(\|(?<display>[^\[\]\.\|]+))?\]\]")
-
-                .unwrap()
-        }); // A [[link]] that does not have any [ or ] in it
+            // Names of notes may contain dots, as in `[[v1.2 release notes]]`; only the extensions of notes and
+            // attachments end them
+            Regex::new(&format!(
+                r"\[\[(?<filepath>[^\[\]\|\#\r\n]+?)?(?<ending>\.(?i:md|{}))?(\#(?<infileref>[^\[\]\.\|\r\n]+))?(\|(?<display>[^\[\]\.\|\r\n]+))?\]\]",
+                ATTACHMENT_EXTENSIONS.join("|")
+            ))
+            .unwrap()
+        }); // A [[link]] that does not have any [ or ] in it and is on one line
 
         let wiki_links = WIKI_LINK_RE
             .captures_iter(text)
-            .filter(
-                |captures| match captures.name("ending").map(|ending| ending.as_str()) {
-                    Some(".md") | None => true,
-                    _ => false,
-                },
-            )
+            .filter(|captures| is_link_ending(captures.name("ending")))
             .flat_map(RegexTuple::new)
             .flat_map(|regextuple| {
                 generic_link_constructor::<WikiReferenceConstructor>(text, file_name, regextuple)
             });
 
+        static MD_LINK_START_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"\[(?<display>[^\[\]\.]*)\]\(").unwrap());
         static MD_LINK_RE: Lazy<Regex> = Lazy::new(|| {
-            Regex::new(r"\[(?<display>[^\[\]\.]*)\]\(<?(?<filepath>(\.?\/)?[^\[\]\|\.\#<>]+)?(?<ending>\.[^\# <>]+)?(\#(?<infileref>[^\[\]\.\|<>]+))?>?\)")
+            Regex::new(r"\[(?<display>[^\[\]\.]*)\]\(<?(?<filepath>(\.?\/)?[^\[\]\|\.\#<>\r\n]+)?(?<ending>\.[^\# <>\r\n]+)?(\#(?<infileref>[^\[\]\.\|<>\r\n]+))?>?\)")
                 .expect("MD Link Not Constructing")
-        }); // [display](relativePath)
-
-        let md_links = MD_LINK_RE
-            .captures_iter(text)
-            .filter(
-                |captures| match captures.name("ending").map(|ending| ending.as_str()) {
-                    Some(".md") | None => true,
-                    _ => false,
-                },
-            )
+        }); // [display](relativePath); as in CommonMark, the destination cannot span lines
+
+        // The regex cannot balance parentheses, so it is matched only up to the end of the destination, as in
+        // `[Foo](Foo_(bar).md)`, which is found by scanning
+        let md_links = MD_LINK_START_RE
+            .find_iter(text)
+            .flat_map(|start| {
+                let link = &text[..=md_link_destination_end(text, start.end())?];
+
+                MD_LINK_RE
+                    .captures_at(link, start.start())
+                    .filter(|captures| {
+                        captures.get(0).is_some_and(|full| {
+                            full.start() == start.start() && full.end() == link.len()
+                        })
+                    })
+            })
+            .filter(|captures| is_link_ending(captures.name("ending")))
             .flat_map(RegexTuple::new)
             .flat_map(|regextuple| {
                 generic_link_constructor::<MDReferenceConstructor>(text, file_name, regextuple)
@@ -783,29 +1237,42 @@ impl Reference {
                 })
             });
 
-        let link_ref_references: Vec<Reference> = if MDLinkReferenceDefinition::new(text)
-            .collect_vec()
-            .is_empty()
-            .not()
-        {
+        // Reference links are only links when their label is defined, as in CommonMark, so that other bracketed text,
+        // such as `[sic]`, is not taken for one
+        let labels: HashSet<String> = MDLinkReferenceDefinition::new(text)
+            .map(|definition| definition.link_ref_name.to_lowercase())
+            .collect();
+
+        let link_ref_references: Vec<Reference> = if labels.is_empty().not() {
+            // Full `[text][label]`, collapsed `[label][]` and shortcut `[label]` reference links
             static LINK_REF_RE: Lazy<Regex> = Lazy::new(|| {
-                Regex::new(r"([^\[]|^)(?<full>\[(?<index>[^\^][^\[\] ]+)\])([^\]\(\:]|$)").unwrap()
+                Regex::new(r"(?<full>\[(?<text>[^\^\[\]][^\[\]]*)\](?:\[(?<index>[^\[\] ]*)\])?)")
+                    .unwrap()
             });
 
+            // The characters around the link are checked here rather than matched by the regex so that adjacent links,
+            // such as `[a] [a]`, do not overlap
             let link_ref_references: Vec<Reference> = LINK_REF_RE
                 .captures_iter(text)
                 .par_bridge()
-                .flat_map(
-                    |capture| match (capture.name("full"), capture.name("index")) {
-                        (Some(full), Some(index)) => Some((full, index)),
-                        _ => None,
-                    },
-                )
-                .map(|(outer, index)| {
+                .flat_map(|capture| {
+                    let (full, text) = (capture.name("full")?, capture.name("text")?);
+
+                    match capture.name("index").map(|index| index.as_str()) {
+                        Some("") | None => Some((full, text.as_str(), None)),
+                        Some(index) => Some((full, index, Some(text.as_str()))),
+                    }
+                })
+                .filter(|(full, label, _)| {
+                    !text[..full.start()].ends_with('[')
+                        && !text[full.end()..].starts_with([']', '(', ':'])
+                        && labels.contains(&label.to_lowercase())
+                })
+                .map(|(outer, label, display)| {
                     LinkRef(ReferenceData {
-                        reference_text: index.as_str().into(),
+                        reference_text: label.into(),
                         range: MyRange::from_range(&Rope::from_str(text), outer.range()),
-                        display_text: None,
+                        display_text: display.map(String::from),
                     })
                 })
                 .collect::<Vec<_>>();
@@ -823,6 +1290,60 @@ impl Reference {
             .chain(link_ref_references)
     }
 
+    /// Links to local notes in raw html anchors: `<a href="note.md">display</a>`. These are treated as markdown links.
+    /// As in a browser, the href is relative to `folder`, the folder of the note within the vault, unless it starts
+    /// with `/`; the links name the notes by their path from the vault root.
+    pub fn new_html<'a>(
+        text: &'a str,
+        file_name: &'a str,
+        folder: &'a Path,
+    ) -> impl Iterator<Item = Reference> + 'a {
+        static HTML_LINK_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r#"<a\s[^>]*?href="(?<filepath>(\.{1,2}\/)*\/?[^"\#\.<>]+)?(?<ending>\.[^"\#<>]+)?(\#(?<infileref>[^"\.<>]+))?"[^>]*>(?<display>[^<]*)</a>"#)
+                .expect("HTML Link Not Constructing")
+        });
+
+        HTML_LINK_RE
+            .captures_iter(text)
+            .filter(|captures| match captures.name("ending") {
+                Some(ending) => is_md_ending(ending.as_str()),
+                None => true,
+            })
+            .flat_map(RegexTuple::new)
+            .flat_map(|regextuple| {
+                generic_link_constructor::<MDReferenceConstructor>(text, file_name, regextuple)
+            })
+            .map(|reference| match reference {
+                MDFileLink(data) => MDFileLink(ReferenceData {
+                    reference_text: vault_link_path(folder, &data.reference_text),
+                    ..data
+                }),
+                MDHeadingLink(data, file, heading) => {
+                    let file = vault_link_path(folder, &file);
+                    MDHeadingLink(
+                        ReferenceData {
+                            reference_text: format!("{file}#{heading}"),
+                            ..data
+                        },
+                        file,
+                        heading,
+                    )
+                }
+                MDIndexedBlockLink(data, file, index) => {
+                    let file = vault_link_path(folder, &file);
+                    MDIndexedBlockLink(
+                        ReferenceData {
+                            reference_text: format!("{file}#^{index}"),
+                            ..data
+                        },
+                        file,
+                        index,
+                    )
+                }
+                reference => reference,
+            })
+    }
+
     pub fn references(
         &self,
         root_dir: &Path,
@@ -864,7 +1385,9 @@ impl Reference {
                 MDIndexedBlockLink(_, _, _) => false,
                 LinkRef(_) => false,
             },
-            &Referenceable::File(..) | &Referenceable::UnresovledFile(..) => match self {
+            &Referenceable::File(..)
+            | &Referenceable::UnresovledFile(..)
+            | &Referenceable::Attachment(..) => match self {
                 MDFileLink(ReferenceData {
                     reference_text: file_ref_text,
                     ..
@@ -872,7 +1395,19 @@ impl Reference {
                 | WikiFileLink(ReferenceData {
                     reference_text: file_ref_text,
                     ..
-                }) => matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir)),
+                }) => {
+                    matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir))
+                        || matches!(self, MDFileLink(..))
+                            && matches_note_relative_path(
+                                root_dir,
+                                file_path,
+                                file_ref_text,
+                                referenceable.get_refname(root_dir),
+                            )
+                        || matches!(referenceable, Referenceable::Attachment(path, folder)
+                            if path.starts_with(folder)
+                                && matches_path_or_file(file_ref_text, referenceable.get_refname(folder)))
+                }
                 Tag(_) => false,
                 WikiHeadingLink(_, _, _) => false,
                 WikiIndexedBlockLink(_, _, _) => false,
@@ -900,8 +1435,47 @@ impl Reference {
                 | WikiIndexedBlockLink(.., file_ref_text, link_infile_ref)
                 | MDHeadingLink(.., file_ref_text, link_infile_ref)
                 | MDIndexedBlockLink(.., file_ref_text, link_infile_ref) => {
-                    matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir))
-                        && link_infile_ref.to_lowercase() == infile_ref.to_lowercase()
+                    let link_infile_ref = link_key(&decode_link_path(link_infile_ref));
+                    let infile_ref = link_key(infile_ref);
+
+                    // The target of a chained heading link such as `[[Note#H1#H2]]` is its last heading; the
+                    // parents are checked by the vault, which knows the other headings of the file
+                    // Markdown links may also name headings by their slug, as in `[setup](Note#initial-setup)`
+                    let is_slug = matches!(self, MDHeadingLink(..))
+                        && matches!(referenceable, Referenceable::Heading(..))
+                        && link_infile_ref == heading_slug(&infile_ref);
+
+                    // Links naming the file they are in, as `[[#Heading]]` and `[[#^block]]` do, are to its headings and
+                    // blocks even when other files have the same name
+                    let in_linked_file = || match referenceable {
+                        Referenceable::Heading(path, _) | Referenceable::IndexedBlock(path, _) => {
+                            path.as_path() == file_path
+                                || !file_path
+                                    .file_stem()
+                                    .and_then(|stem| stem.to_str())
+                                    .is_some_and(|stem| {
+                                        stem.eq_ignore_ascii_case(&decode_link_path(file_ref_text))
+                                    })
+                        }
+                        _ => true,
+                    };
+
+                    let matches_file = || {
+                        matches_path_or_file(file_ref_text, referenceable.get_refname(root_dir))
+                            || matches!(self, MDHeadingLink(..) | MDIndexedBlockLink(..))
+                                && matches_note_relative_path(
+                                    root_dir,
+                                    file_path,
+                                    file_ref_text,
+                                    referenceable.get_refname(root_dir),
+                                )
+                    };
+
+                    matches_file()
+                        && (link_infile_ref == infile_ref
+                            || link_infile_ref.rsplit('#').next() == Some(infile_ref.as_str())
+                            || is_slug)
+                        && in_linked_file()
                 }
                 Tag(_) => false,
                 WikiFileLink(_) => false,
@@ -934,6 +1508,7 @@ impl Reference {
 struct RegexTuple<'a> {
     range: Match<'a>,
     file_path: Option<Match<'a>>,
+    ending: Option<Match<'a>>,
     infile_ref: Option<Match<'a>>,
     display_text: Option<Match<'a>>,
 }
@@ -943,12 +1518,14 @@ impl RegexTuple<'_> {
         match (
             capture.get(0),
             capture.name("filepath"),
+            capture.name("ending"),
             capture.name("infileref"),
             capture.name("display"),
         ) {
-            (Some(range), file_path, infile_ref, display_text) => Some(RegexTuple {
+            (Some(range), file_path, ending, infile_ref, display_text) => Some(RegexTuple {
                 range,
                 file_path,
+                ending,
                 infile_ref,
                 display_text,
             }),
@@ -996,6 +1573,7 @@ fn generic_link_constructor<T: ParseableReferenceConstructor>(
     RegexTuple {
         range,
         file_path,
+        ending,
         infile_ref,
         display_text,
     }: RegexTuple,
@@ -1007,6 +1585,15 @@ fn generic_link_constructor<T: ParseableReferenceConstructor>(
         return None;
     }
 
+    // Links to attachments keep their extension and point to the whole file, even with a fragment such as `#page=3`
+    if let Some(ending) = ending.filter(|ending| !is_md_ending(ending.as_str())) {
+        return Some(T::new_file_link(ReferenceData {
+            reference_text: format!("{}{}", file_path?.as_str(), ending.as_str()),
+            range: MyRange::from_range(&Rope::from_str(text), range.range()),
+            display_text: display_text.map(|d| d.as_str().into()),
+        }));
+    }
+
     match (range, file_path.map(|it| it.as_str()).unwrap_or(file_name), infile_ref, display_text) {
         // Pure file reference as there is no infileref such as #... for headings or #^... for indexed blocks
         (full, filepath, None, display) => Some(T::new_file_link(ReferenceData {
@@ -1112,7 +1699,7 @@ impl From<tower_lsp::lsp_types::Range> for MyRange {
 }
 
 impl MDHeading {
-    fn new(text: &str) -> impl Iterator<Item = MDHeading> + '_ {
+    pub fn new(text: &str) -> impl Iterator<Item = MDHeading> + '_ {
         static HEADING_RE: Lazy<Regex> =
             Lazy::new(|| Regex::new(r"(?<starter>#+) (?<heading_text>.+)").unwrap());
 
@@ -1134,6 +1721,74 @@ impl MDHeading {
 
         headings
     }
+
+    /// The heading without a trailing `{#id}` attribute, which is an anchor rather than part of its text
+    fn without_attribute_anchor(self) -> MDHeading {
+        static ATTRIBUTE_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"\s*\{#[\w\-]+[^}\n]*\}$").unwrap());
+
+        MDHeading {
+            heading_text: ATTRIBUTE_RE.replace(&self.heading_text, "").into(),
+            ..self
+        }
+    }
+}
+
+/// Whether the headings containing `heading` in `headings` include `parents`, outermost first; parents need not be
+/// direct, as in Obsidian
+fn heading_has_parents(headings: &[MDHeading], heading: &MDHeading, parents: &[&str]) -> bool {
+    let Some(ancestors) = heading_parents(headings, heading) else {
+        return false;
+    };
+
+    let mut ancestors = ancestors.into_iter().rev();
+
+    parents.iter().rev().all(|parent| {
+        ancestors.any(|ancestor| link_key(&ancestor.heading_text) == link_key(parent))
+    })
+}
+
+/// Whether `heading` has `parents` and no other heading with the same text has them
+fn is_only_heading_with_parents(
+    headings: &[MDHeading],
+    heading: &MDHeading,
+    parents: &[&str],
+) -> bool {
+    heading_has_parents(headings, heading, parents)
+        && headings
+            .iter()
+            .filter(|other| {
+                link_key(&other.heading_text) == link_key(&heading.heading_text)
+                    && heading_has_parents(headings, other, parents)
+            })
+            .count()
+            == 1
+}
+
+/// The headings containing `heading` in `headings`, outermost first; none if `heading` is not one of `headings`
+pub fn heading_parents<'a>(
+    headings: &'a [MDHeading],
+    heading: &MDHeading,
+) -> Option<Vec<&'a MDHeading>> {
+    let position = headings.iter().position(|other| other == heading)?;
+
+    // Walking back from the heading, each heading of a lower level than the last is the next parent out
+    let mut level = heading.level.0;
+    let mut parents = headings[..position]
+        .iter()
+        .rev()
+        .filter(|other| {
+            let is_parent = other.level.0 < level;
+            if is_parent {
+                level = other.level.0;
+            }
+
+            is_parent
+        })
+        .collect_vec();
+    parents.reverse();
+
+    Some(parents)
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -1167,6 +1822,25 @@ impl MDIndexedBlock {
 
         indexed_blocks
     } // Make this better identify the full blocks
+
+    /// Anchors set with attributes at the end of a heading or block, as in `# Heading {#custom-id}`. Links to the
+    /// anchor, such as `[[Note#custom-id]]`, resolve like links to an indexed block.
+    fn new_attribute_anchors(text: &str) -> impl Iterator<Item = MDIndexedBlock> + '_ {
+        static ATTRIBUTE_ANCHOR_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"(?m)\S.*? (?<full>\{#(?<index>[\w\-]+)[^}\n]*\})[ \t]*$").unwrap()
+        });
+
+        ATTRIBUTE_ANCHOR_RE
+            .captures_iter(text)
+            .flat_map(|c| match (c.name("full"), c.name("index")) {
+                (Some(full), Some(index)) => Some((full, index)),
+                _ => None,
+            })
+            .map(|(full, index)| MDIndexedBlock {
+                index: index.as_str().into(),
+                range: MyRange::from_range(&Rope::from_str(text), full.range()),
+            })
+    }
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -1239,6 +1913,60 @@ impl MDTag {
 
         tagged_blocks
     }
+
+    /// The tags of the `tags` property of the frontmatter, whether a block list, a flow list, or a string of comma or
+    /// space separated tags. The `#` and quotes around a tag are not part of its range.
+    fn new_frontmatter(text: &str) -> Vec<MDTag> {
+        static FRONTMATTER_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"\A---\r?\n(?<metadata>(?s:.*?))\r?\n---").unwrap());
+        // The property continues on the following lines as long as they are indented, list items, or blank
+        static TAGS_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"(?m)^tags?:(?<tags>.*(?:\n(?:[ \t-].*)?)*)").unwrap());
+        static TOKEN_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"[^\s\[\],'"]+"#).unwrap());
+        static TAG_RE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"^#?(?<tag>[a-zA-Z_\-\/][0-9a-zA-Z_\-\/]*)$").unwrap());
+
+        let Some(tags) = FRONTMATTER_RE
+            .captures(text)
+            .and_then(|captures| captures.name("metadata"))
+            .and_then(|metadata| {
+                let tags = TAGS_RE.captures(metadata.as_str())?.name("tags")?;
+                Some((metadata.start() + tags.start(), tags.as_str()))
+            })
+        else {
+            return vec![];
+        };
+
+        let rope = Rope::from_str(text);
+        let (offset, tags) = tags;
+        let mut comment_end = 0;
+
+        TOKEN_RE
+            .find_iter(tags)
+            .filter_map(|token| {
+                // A lone `#` starts a yaml comment, which runs to the end of the line
+                if token.as_str() == "#" {
+                    comment_end = tags[token.start()..]
+                        .find('\n')
+                        .map_or(tags.len(), |end| token.start() + end);
+                }
+                if token.start() < comment_end {
+                    return None;
+                }
+
+                let tag = TAG_RE.captures(token.as_str())?.name("tag")?;
+                let start = offset + token.start() + tag.start();
+
+                tag.as_str()
+                    .chars()
+                    .any(|c| c.is_alphabetic())
+                    .then(|| MDTag {
+                        tag_ref: tag.as_str().into(),
+                        range: MyRange::from_range(&rope, start..start + tag.len()),
+                    })
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
@@ -1260,12 +1988,14 @@ impl MDLinkReferenceDefinition {
                 (Some(full), Some(index), Some(text)) => Some((full, index, text)),
                 _ => None,
             })
-            .flat_map(|(full, index, url)| {
+            .flat_map(|(full, index, destination)| {
+                let (url, title) = link_ref_url_and_title(destination.as_str());
+
                 Some(MDLinkReferenceDefinition {
                     link_ref_name: index.as_str().to_string(),
                     range: MyRange::from_range(&Rope::from_str(text), full.range()),
-                    url: url.as_str().trim().to_string(),
-                    title: None,
+                    url,
+                    title,
                 })
             });
 
@@ -1273,6 +2003,28 @@ impl MDLinkReferenceDefinition {
     }
 }
 
+/// The url of a definition, such as `<my note.md>` or `https://example.com`, and its optional title in `"`, `'` or
+/// `()`, as in `[id]: https://example.com "Example"`
+fn link_ref_url_and_title(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+
+    let (url, rest) = match text
+        .strip_prefix('<')
+        .and_then(|bracketed| bracketed.split_once('>'))
+    {
+        Some((url, rest)) => (url, rest),
+        None => text.split_at(text.find(char::is_whitespace).unwrap_or(text.len())),
+    };
+
+    let rest = rest.trim();
+    let title = [('"', '"'), ('\'', '\''), ('(', ')')]
+        .into_iter()
+        .find_map(|(open, close)| rest.strip_prefix(open)?.strip_suffix(close))
+        .map(String::from);
+
+    (url.to_string(), title)
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 /**
 An Algebreic type for methods for all referenceables, which are anything able to be referenced through obsidian link or tag. These include
@@ -1294,6 +2046,8 @@ pub enum Referenceable<'a> {
     /// full path, link path, index (without ^)
     UnresovledIndexedBlock(PathBuf, &'a String, &'a String),
     LinkRefDef(&'a PathBuf, &'a MDLinkReferenceDefinition),
+    /// A file other than a note, such as an image, that exists in the vault, and the attachments folder
+    Attachment(&'a PathBuf, &'a Path),
 }
 
 /// Utility function
@@ -1411,6 +2165,14 @@ impl Referenceable<'_> {
                 infile_ref: None,
                 path: None,
             }),
+            // Links to attachments always include the extension
+            Referenceable::Attachment(path, _) => diff_paths(path, root_dir)
+                .and_then(|diff| diff.to_str().map(String::from))
+                .map(|string| Refname {
+                    full_refname: string.clone(),
+                    path: Some(string),
+                    ..Default::default()
+                }),
         }
     }
 
@@ -1469,6 +2231,30 @@ impl Referenceable<'_> {
         }
     }
 
+    /// The label of the referenceable in the UI: the note name for files, `note#heading` for headings, `note#^id` for
+    /// blocks, and `#tag` for tags. Unlike the refname, this does not include the folder of the note.
+    pub fn display_name(&self) -> Option<String> {
+        let stem = || Some(self.get_path().file_stem()?.to_str()?.to_string());
+
+        match self {
+            Referenceable::File(..) => stem(),
+            Referenceable::Heading(_, heading) => {
+                Some(format!("{}#{}", stem()?, heading.heading_text))
+            }
+            Referenceable::IndexedBlock(_, block) => Some(format!("{}#^{}", stem()?, block.index)),
+            Referenceable::Tag(_, tag) => Some(format!("#{}", tag.tag_ref)),
+            Referenceable::Footnote(_, footnote) => Some(footnote.index.clone()),
+            Referenceable::LinkRefDef(_, refdef) => Some(refdef.link_ref_name.clone()),
+            Referenceable::Attachment(path, _) => Some(path.file_name()?.to_str()?.to_string()),
+            // Unresolved referenceables are labeled as they are linked
+            Referenceable::UnresovledFile(..)
+            | Referenceable::UnresolvedHeading(..)
+            | Referenceable::UnresovledIndexedBlock(..) => self
+                .get_refname(Path::new(""))
+                .map(|refname| refname.full_refname),
+        }
+    }
+
     pub fn get_path(&self) -> &Path {
         match self {
             Referenceable::File(path, _) => path,
@@ -1480,12 +2266,13 @@ impl Referenceable<'_> {
             Referenceable::UnresovledFile(path, ..) => path,
             Referenceable::UnresolvedHeading(path, ..) => path,
             Referenceable::LinkRefDef(path, ..) => path,
+            Referenceable::Attachment(path, _) => path,
         }
     }
 
     pub fn get_range(&self) -> Option<MyRange> {
         match self {
-            Referenceable::File(_, _) => None,
+            Referenceable::File(_, _) | Referenceable::Attachment(..) => None,
             Referenceable::Heading(_, heading) => Some(heading.range),
             Referenceable::IndexedBlock(_, indexed_block) => Some(indexed_block.range),
             Referenceable::Tag(_, tag) => Some(tag.range),
@@ -1512,39 +2299,268 @@ fn matches_path_or_file(file_ref_text: &str, refname: Option<Refname>) -> bool {
         let refname = refname?;
         let refname_path = refname.path.clone()?; // this function should not be used for tags, ... only for heading, files, indexed blocks
 
-        if file_ref_text.contains('/') {
-            let file_ref_text = file_ref_text.replace(r"%20", " ");
-            let file_ref_text = file_ref_text.replace(r"\ ", " ");
+        let file_ref_text = decode_link_path(file_ref_text);
 
+        if file_ref_text.contains('/') {
             let chars: Vec<char> = file_ref_text.chars().collect();
             match chars.as_slice() {
                 &['.', '/', ref path @ ..] | &['/', ref path @ ..] => {
-                    Some(String::from_iter(path) == refname_path)
+                    Some(link_key(&String::from_iter(path)) == link_key(&refname_path))
                 }
-                path => Some(String::from_iter(path) == refname_path),
+                path => Some(link_key(&String::from_iter(path)) == link_key(&refname_path)),
             }
         } else {
             let last_segment = refname.link_file_key()?;
 
-            Some(file_ref_text.to_lowercase() == last_segment.to_lowercase())
+            Some(link_key(&file_ref_text) == link_key(&last_segment))
         }
     })()
     .is_some_and(|b| b)
 }
 
+/// Whether a markdown link path such as `sub/note` names `refname` relative to the folder of the note at `file_path`,
+/// as in CommonMark; paths are otherwise from the vault root
+fn matches_note_relative_path(
+    root_dir: &Path,
+    file_path: &Path,
+    file_ref_text: &str,
+    refname: Option<Refname>,
+) -> bool {
+    let file_ref_text = decode_link_path(file_ref_text);
+    if !file_ref_text.contains('/') || file_ref_text.starts_with('/') {
+        return false;
+    }
+
+    let Some(folder) = file_path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root_dir).ok())
+    else {
+        return false;
+    };
+
+    refname
+        .and_then(|refname| refname.path)
+        .is_some_and(|path| link_key(&vault_link_path(folder, &file_ref_text)) == link_key(&path))
+}
+
+/// The path from the vault root of a link written relative to `folder`, a folder of the vault; a link starting with `/`
+/// is relative to the vault root
+fn vault_link_path(folder: &Path, link_path: &str) -> String {
+    let (mut segments, link_path) = match link_path.strip_prefix('/') {
+        Some(link_path) => (vec![], link_path),
+        None => (
+            folder
+                .iter()
+                .filter_map(|segment| segment.to_str())
+                .collect_vec(),
+            link_path,
+        ),
+    };
+
+    for segment in link_path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+/// The text links and the names of notes and headings are compared by: lowercase and NFC-normalized, so that `Café`
+/// written with a combining accent matches `Café` written with a precomposed one. The vault checks the case and
+/// normalization of links when those are not to be ignored.
+fn link_key(text: &str) -> String {
+    text.to_lowercase().nfc().collect()
+}
+
+/// Whether the file, and the heading if any, that a link names are the same as those of `referenceable` once `key` is
+/// applied to both; links are otherwise matched case-insensitively and normalized. Files linked by a path may be named
+/// relative to a folder, and markdown links may name headings by their slug if `slug_anchors` is set.
+fn matches_exact_case(
+    root_dir: &Path,
+    reference: &Reference,
+    referenceable: &Referenceable,
+    slug_anchors: bool,
+    key: impl Fn(&str) -> String,
+) -> bool {
+    let (file, heading) = match reference {
+        WikiFileLink(data) | MDFileLink(data) => (&data.reference_text, None),
+        WikiHeadingLink(_, file, heading) | MDHeadingLink(_, file, heading) => {
+            (file, Some(heading))
+        }
+        WikiIndexedBlockLink(_, file, _) | MDIndexedBlockLink(_, file, _) => (file, None),
+        Tag(_) | Footnote(_) | LinkRef(_) => return true,
+    };
+
+    let Some(refname) = referenceable.get_refname(root_dir) else {
+        return true;
+    };
+
+    let file = decode_link_path(file);
+    let file = key(file.trim_start_matches("./").trim_start_matches('/'));
+    let file_matches = match (&refname.path, file.contains('/')) {
+        // links to the current file, such as `[[#Heading]]`, do not name it
+        _ if file.is_empty() => true,
+        (Some(path), true) => {
+            let path = key(path);
+            path == file || path.ends_with(&format!("/{file}"))
+        }
+        (_, false) => refname
+            .link_file_key()
+            .is_some_and(|file_key| key(&file_key) == file),
+        (None, true) => true,
+    };
+
+    let heading_matches = match (referenceable, heading) {
+        (Referenceable::Heading(_, md_heading), Some(heading)) => {
+            let heading = decode_link_path(heading);
+            let names_heading = |heading: &str| {
+                key(heading) == key(&md_heading.heading_text)
+                    || matches!(reference, MDHeadingLink(..))
+                        && slug_anchors
+                        && key(heading) == key(&heading_slug(&md_heading.heading_text))
+            };
+
+            // The heading may be the last of a chain such as `[[Note#H1#H2]]`, or have a `#` of its own
+            names_heading(&heading) || names_heading(heading.rsplit('#').next().unwrap_or_default())
+        }
+        _ => true,
+    };
+
+    file_matches && heading_matches
+}
+
+/// Percent-encode the characters of a link path that are not allowed in a markdown link destination, such as spaces.
+pub fn encode_link_path(path: &str) -> String {
+    path.chars()
+        .map(|c| match c {
+            ' ' | '<' | '>' | '(' | ')' | '[' | ']' | '%' | '|' | '"' | '`' => {
+                format!("%{:02X}", c as u32)
+            }
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// The anchor of a heading as markdown renderers such as GitHub's generate it: lowercase, with spaces as dashes and
+/// without punctuation, so that `## Initial Setup!` is `initial-setup`
+pub fn heading_slug(heading_text: &str) -> String {
+    heading_text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Decode the percent-encoded (`%20`) and backslash-escaped (`\ `) characters of a link path
+pub fn decode_link_path(path: &str) -> String {
+    let path = path.replace(r"\ ", " ");
+
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).unwrap_or(path)
+}
+
+/// The folder of the test vault and its settings, without the global settings file of the machine, for test vaults
+/// built with [`Vault::of_notes`]
+#[cfg(test)]
+pub(crate) fn test_settings() -> (PathBuf, Settings) {
+    let root_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("TestFiles");
+    let settings = Settings::without_global_config(
+        &root_dir,
+        &tower_lsp::lsp_types::ClientCapabilities::default(),
+    )
+    .unwrap();
+
+    (root_dir, settings)
+}
+
+#[cfg(test)]
+impl Vault {
+    /// A vault of the notes with the given texts, without reading the files
+    pub(crate) fn of_notes<'a>(
+        settings: &Settings,
+        root_dir: &Path,
+        notes: impl IntoIterator<Item = (PathBuf, &'a str)>,
+    ) -> Vault {
+        let (md_files, ropes): (HashMap<PathBuf, MDFile>, HashMap<PathBuf, Rope>) = notes
+            .into_iter()
+            .map(|(path, text)| {
+                (
+                    (
+                        path.clone(),
+                        MDFile::new(settings, root_dir, text, path.clone()),
+                    ),
+                    (path, Rope::from_str(text)),
+                )
+            })
+            .unzip();
+
+        Vault {
+            md_files: md_files.into(),
+            ropes: ropes.into(),
+            attachments: Default::default(),
+            attachments_folder: root_dir.to_path_buf(),
+            case_insensitive_links: settings.case_insensitive_links,
+            unicode_normalize_links: settings.unicode_normalize_links,
+            heading_slug_style: settings.heading_slug_style,
+            preview_max_lines: settings.preview_max_lines,
+            root_dir: root_dir.to_path_buf(),
+        }
+    }
+}
+
 // tests
 #[cfg(test)]
 mod vault_tests {
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use itertools::Itertools;
-    use tower_lsp::lsp_types::{Position, Range};
-
+    use tower_lsp::lsp_types::{ClientCapabilities, Position, Range};
+    use unicode_normalization::UnicodeNormalization;
+
+    use crate::config::{HeadingSlugStyle, Settings};
+    use crate::vault::{
+        block_lines, decode_link_path, decode_note_text, encode_link_path, heading_has_parents,
+        heading_parents, heading_slug, is_only_heading_with_parents, matches_exact_case,
+        matches_path_or_file,
+    };
     use crate::vault::{HeadingLevel, MyRange, ReferenceData};
     use crate::vault::{MDLinkReferenceDefinition, Refname};
 
     use super::Reference::*;
-    use super::{MDFile, MDFootnote, MDHeading, MDIndexedBlock, MDTag, Reference, Referenceable};
+    use super::{
+        test_settings, MDFile, MDFootnote, MDHeading, MDIndexedBlock, MDTag, Reference,
+        Referenceable, Vault,
+    };
 
     #[test]
     fn wiki_link_parsing() {
@@ -1630,6 +2646,123 @@ mod vault_tests {
         assert_eq!(parsed, expected)
     }
 
+    #[test]
+    fn redundant_display_text() {
+        let text = "[[Note|Note]] [[Note#Heading|Note#Heading]] [[Note|A note]] [Note](Note)";
+        let redundant = Reference::new(text, "test.md")
+            .map(|reference| reference.has_redundant_display_text())
+            .collect_vec();
+
+        assert_eq!(redundant, vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn wiki_link_names_with_dots() {
+        let text = "[[file.with.dots]] [[file.with.dots#Heading]] [[v1.2 release notes]] [[Note.md]] [[image.PNG]]";
+        let parsed = Reference::new(text, "test.md")
+            .map(|reference| match reference {
+                WikiHeadingLink(_, file, heading) => format!("{file} > {heading}"),
+                reference => reference.data().reference_text.clone(),
+            })
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                "file.with.dots",
+                "file.with.dots > Heading",
+                "v1.2 release notes",
+                "Note",
+                "image.PNG"
+            ]
+        );
+    }
+
+    #[test]
+    fn wiki_link_chained_heading_parsing() {
+        let text = "This is a [[link#parent#heading]]";
+        let parsed = Reference::new(text, "test.md").collect_vec();
+
+        let expected = vec![WikiHeadingLink(
+            ReferenceData {
+                reference_text: "link#parent#heading".into(),
+                range: tower_lsp::lsp_types::Range {
+                    start: tower_lsp::lsp_types::Position {
+                        line: 0,
+                        character: 10,
+                    },
+                    end: tower_lsp::lsp_types::Position {
+                        line: 0,
+                        character: 33,
+                    },
+                }
+                .into(),
+                ..ReferenceData::default()
+            },
+            "link".into(),
+            "parent#heading".into(),
+        )];
+
+        assert_eq!(parsed, expected)
+    }
+
+    #[test]
+    fn block_lines_of_paragraphs_and_list_items() {
+        let rope = ropey::Rope::from_str(
+            "# Heading\nFirst line\nof the paragraph ^para\n\n- item\n- another item ^item\n\nOne line ^line",
+        );
+
+        assert_eq!(block_lines(&rope, 2), 1..3);
+        assert_eq!(block_lines(&rope, 5), 5..6);
+        assert_eq!(block_lines(&rope, 7), 7..8);
+    }
+
+    #[test]
+    fn chained_heading_parents() {
+        let text = "# One\n## Notes\n# Two\n## Section\n### Notes";
+        let headings = MDHeading::new(text).collect_vec();
+        let (first_notes, second_notes) = (&headings[1], &headings[4]);
+
+        assert!(heading_has_parents(&headings, first_notes, &["One"]));
+        assert!(!heading_has_parents(&headings, first_notes, &["Two"]));
+        assert!(heading_has_parents(
+            &headings,
+            second_notes,
+            &["two", "Section"]
+        ));
+        // Intermediate parents may be skipped, but the order must hold
+        assert!(heading_has_parents(&headings, second_notes, &["Two"]));
+        assert!(!heading_has_parents(
+            &headings,
+            second_notes,
+            &["Section", "Two"]
+        ));
+
+        let parent_texts = |heading| {
+            heading_parents(&headings, heading)
+                .unwrap()
+                .iter()
+                .map(|parent| parent.heading_text.as_str())
+                .collect_vec()
+        };
+        assert_eq!(parent_texts(second_notes), vec!["Two", "Section"]);
+        assert!(parent_texts(&headings[0]).is_empty());
+
+        // Chains that more than one heading has do not name either
+        let text = "# One\n## Notes\n# One\n## Notes\n# Two\n## Notes";
+        let headings = MDHeading::new(text).collect_vec();
+        assert!(!is_only_heading_with_parents(
+            &headings,
+            &headings[1],
+            &["One"]
+        ));
+        assert!(is_only_heading_with_parents(
+            &headings,
+            &headings[5],
+            &["Two"]
+        ));
+    }
+
     #[test]
     fn wiki_link_indexedblock_parsing() {
         let text = "This is a [[link#^index1]]";
@@ -1783,6 +2916,137 @@ mod vault_tests {
         assert_eq!(parsed, expected)
     }
 
+    #[test]
+    fn md_link_does_not_span_lines() {
+        let text = "[file1](file1_ref.md)\n(this causes bug)";
+
+        let parsed = Reference::new(text, "test.md").collect_vec();
+
+        let expected = vec![Reference::MDFileLink(ReferenceData {
+            reference_text: "file1_ref".into(),
+            display_text: Some("file1".into()),
+            range: Range {
+                start: Position {
+                    line: 0,
+                    character: 0,
+                },
+                end: Position {
+                    line: 0,
+                    character: 21,
+                },
+            }
+            .into(),
+        })];
+
+        assert_eq!(parsed, expected);
+
+        let text = "[file1](file1_ref\n.md) [[file1\n]]";
+
+        assert_eq!(Reference::new(text, "test.md").collect_vec(), vec![]);
+    }
+
+    #[test]
+    fn md_link_destinations_with_parentheses_and_brackets() {
+        let links = |text: &str| {
+            Reference::new(text, "test.md")
+                .map(|reference| {
+                    (
+                        reference.data().reference_text.clone(),
+                        reference.data().range.end.character,
+                    )
+                })
+                .collect_vec()
+        };
+
+        assert_eq!(
+            links("[Foo](Foo_(bar).md) and (aside)"),
+            vec![("Foo_(bar)".to_string(), 19)]
+        );
+        assert_eq!(
+            links("[Foo](https://en.wikipedia.org/wiki/Foo_(bar)) and [note](note)"),
+            vec![("note".to_string(), 63)]
+        );
+        assert_eq!(
+            links("[a](<my note.md>) [b](<Foo (bar).md>)"),
+            vec![("my note".to_string(), 17), ("Foo (bar)".to_string(), 37)]
+        );
+        assert_eq!(links("[a](Foo_(bar).md [b](<note.md)"), vec![]);
+    }
+
+    #[test]
+    fn non_utf8_note_decoding() {
+        assert_eq!(decode_note_text("# Café".as_bytes().to_vec()), "# Café");
+
+        // "# Café" encoded as latin-1
+        let latin1 = vec![b'#', b' ', b'C', b'a', b'f', 0xE9];
+        assert_eq!(decode_note_text(latin1), "# Caf\u{FFFD}");
+    }
+
+    #[test]
+    fn html_link_parsing() {
+        let text = r#"Test text <a href="path/to/note.md">Note</a> <a class="x" href="note#Heading">Heading</a> <a href="https://example.com">Web</a>"#;
+
+        let parsed = Reference::new_html(text, "test", Path::new("")).collect_vec();
+
+        let expected = vec![
+            Reference::MDFileLink(ReferenceData {
+                reference_text: "path/to/note".into(),
+                display_text: Some("Note".into()),
+                range: Range {
+                    start: Position {
+                        line: 0,
+                        character: 10,
+                    },
+                    end: Position {
+                        line: 0,
+                        character: 44,
+                    },
+                }
+                .into(),
+            }),
+            Reference::MDHeadingLink(
+                ReferenceData {
+                    reference_text: "note#Heading".into(),
+                    display_text: Some("Heading".into()),
+                    range: Range {
+                        start: Position {
+                            line: 0,
+                            character: 45,
+                        },
+                        end: Position {
+                            line: 0,
+                            character: 89,
+                        },
+                    }
+                    .into(),
+                },
+                "note".into(),
+                "Heading".into(),
+            ),
+        ];
+
+        assert_eq!(parsed, expected)
+    }
+
+    #[test]
+    fn html_links_relative_to_note() {
+        let text = r##"<a href="sub/note.md">a</a> <a href="../other.md#Heading">b</a> <a href="/top.md">c</a> <a href="#^block">d</a>"##;
+
+        let parsed = Reference::new_html(text, "test", Path::new("folder/inner"))
+            .map(|reference| reference.reference_text.clone())
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                "folder/inner/sub/note",
+                "folder/other#Heading",
+                "top",
+                "folder/inner/test#^block"
+            ]
+        );
+    }
+
     #[test]
     fn advanced_md_link_parsing() {
         let text = "Test text test text [link](<path to/link>)";
@@ -1974,7 +3238,319 @@ mod vault_tests {
         let text = "This is a png [[link.png]] [[link|display.png]]";
         let parsed = Reference::new(text, "test.md").collect_vec();
 
-        assert_eq!(parsed, vec![])
+        let expected = vec![Reference::WikiFileLink(ReferenceData {
+            reference_text: "link.png".into(),
+            range: tower_lsp::lsp_types::Range {
+                start: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 14,
+                },
+                end: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 26,
+                },
+            }
+            .into(),
+            ..ReferenceData::default()
+        })];
+
+        assert_eq!(parsed, expected)
+    }
+
+    #[test]
+    fn uppercase_md_extension() {
+        let text = "[link](note.MD#Heading) [[Other.Md]]";
+        let parsed = Reference::new(text, "test.md")
+            .map(|reference| reference.data().reference_text.clone())
+            .collect_vec();
+
+        assert_eq!(parsed, vec!["Other", "note#Heading"]);
+    }
+
+    #[test]
+    fn referenceable_display_names() {
+        let path = PathBuf::from("/home/vault/folder/Note.md");
+        let md_file = MDFile::new(
+            &test_settings().1,
+            Path::new("/home/vault"),
+            "# Heading\n\ntext ^block #tag",
+            path.clone(),
+        );
+
+        let names = md_file
+            .get_referenceables()
+            .iter()
+            .map(|referenceable| referenceable.display_name())
+            .collect_vec();
+
+        assert_eq!(
+            names,
+            vec![
+                Some("Note".to_string()),
+                Some("Note#Heading".to_string()),
+                Some("Note#^block".to_string()),
+                Some("#tag".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn case_insensitive_links() {
+        let (root_dir, settings) = test_settings();
+
+        let path = root_dir.join("Folder/My Note.md");
+        let md_file = MDFile::new(&settings, &root_dir, "## Setup", path.clone());
+        let file = Referenceable::File(&path, &md_file);
+        let heading = Referenceable::Heading(&path, &md_file.headings[0]);
+
+        let link = |text| Reference::new(text, "test.md").next().unwrap();
+        let resolves = |text, referenceable: &Referenceable| {
+            let link = link(text);
+            (
+                link.references(&root_dir, &root_dir.join("test.md"), referenceable),
+                matches_exact_case(&root_dir, &link, referenceable, true, str::to_string),
+            )
+        };
+
+        assert_eq!(resolves("[[folder/my note]]", &file), (true, false));
+        assert_eq!(resolves("[[Folder/My Note]]", &file), (true, true));
+        assert_eq!(resolves("[[my note]]", &file), (true, false));
+        assert_eq!(resolves("[[My Note#setup]]", &heading), (true, false));
+        assert_eq!(resolves("[[My Note#Setup]]", &heading), (true, true));
+    }
+
+    #[test]
+    fn exact_paths_preferred_over_names() {
+        let (root_dir, settings) = test_settings();
+
+        let paths = ["note.md", "a/note.md", "b/note.md"].map(|path| root_dir.join(path));
+        let vault = Vault::of_notes(&settings, &root_dir, paths.clone().map(|path| (path, "")));
+
+        let resolved = |text| {
+            let link = Reference::new(text, "test.md").next().unwrap();
+            vault
+                .select_referenceables_for_reference(&link, &root_dir.join("test.md"))
+                .iter()
+                .map(|referenceable| referenceable.get_path().to_path_buf())
+                .sorted()
+                .collect_vec()
+        };
+
+        assert_eq!(resolved("[[note]]"), vec![paths[0].clone()]);
+        assert_eq!(resolved("[[a/note]]"), vec![paths[1].clone()]);
+        assert_eq!(vault.select_name_conflicts().len(), 3);
+    }
+
+    #[test]
+    fn unicode_normalized_links() {
+        let (root_dir, settings) = test_settings();
+
+        // `Cafe\u{301}` is decomposed: an `e` and a combining acute accent
+        let path = root_dir.join("Cafe\u{301}.md");
+        let md_file = MDFile::new(&settings, &root_dir, "## Crème brûlée", path.clone());
+        let file = Referenceable::File(&path, &md_file);
+        let heading = Referenceable::Heading(&path, &md_file.headings[0]);
+
+        let resolves = |text, referenceable: &Referenceable| {
+            let link = Reference::new(text, "test.md").next().unwrap();
+            (
+                link.references(&root_dir, &root_dir.join("test.md"), referenceable),
+                matches_exact_case(&root_dir, &link, referenceable, true, str::to_string),
+                matches_exact_case(&root_dir, &link, referenceable, true, |text| {
+                    text.nfc().collect()
+                }),
+            )
+        };
+
+        assert_eq!(resolves("[[Caf\u{e9}]]", &file), (true, false, true));
+        assert_eq!(resolves("[[Cafe\u{301}]]", &file), (true, true, true));
+        assert_eq!(resolves("[[caf\u{e9}]]", &file), (true, false, false));
+        assert_eq!(
+            resolves("[[Caf\u{e9}#Cre\u{300}me brûlée]]", &heading),
+            (true, false, true)
+        );
+    }
+
+    #[test]
+    fn pathological_markdown_does_not_panic() {
+        let (root_dir, mut settings) = test_settings();
+        settings.html_links = true;
+        settings.attribute_anchors = true;
+
+        let texts = [
+            "",
+            "---",
+            "---\ntags:\n  - [",
+            "[[",
+            "]]",
+            "[[#]] [[#^]] [[|]] [[^]] [](#) [](<>) []()",
+            "# \n#\n####### too deep\n# {#}",
+            "[^] [^1]: [^]: ^ ^^ a ^",
+            "```\n[[unterminated code",
+            "`` ` ```` `",
+            "<a href=\"\">x</a> <a href=\"#\"></a> <a href",
+            "[[日本語#見出し|表示]] [表示](日本語.md#見出し) #タグ 😀 ^😀",
+            "\r\n# Heading\r\n[[Note]]\r\n",
+            "[[a]][[b]][x](y)[z](w)#t#u^v",
+        ];
+
+        for text in texts {
+            let _ = MDFile::new(&settings, &root_dir, text, root_dir.join("Note.md"));
+            let _ = MDFile::new(&settings, &root_dir, text, PathBuf::from("/"));
+        }
+    }
+
+    #[test]
+    fn slug_anchors_resolve() {
+        let (root_dir, settings) = test_settings();
+
+        let path = root_dir.join("Note.md");
+        let md_file = MDFile::new(&settings, &root_dir, "## Initial Setup (v2)!", path.clone());
+        let heading = Referenceable::Heading(&path, &md_file.headings[0]);
+
+        let resolves = |text| {
+            Reference::new(text, "test.md").next().unwrap().references(
+                &root_dir,
+                &root_dir.join("test.md"),
+                &heading,
+            )
+        };
+
+        assert!(resolves("[setup](Note#initial-setup-v2)"));
+        assert!(resolves("[setup](Note#Initial%20Setup%20(v2)!)"));
+        assert!(!resolves("[[Note#initial-setup-v2]]"));
+    }
+
+    #[test]
+    fn heading_slug_styles() {
+        assert_eq!(heading_slug("Initial Setup (v2)!"), "initial-setup-v2");
+        assert_eq!(heading_slug("  What's new?  "), "whats-new");
+        assert_eq!(heading_slug("C++ & Rust: a_b"), "c--rust-a_b");
+        assert_eq!(heading_slug("Émile's café"), "émiles-café");
+
+        let (root_dir, mut settings) = test_settings();
+
+        for (style, slug_resolves) in [
+            (HeadingSlugStyle::Github, true),
+            (HeadingSlugStyle::Obsidian, false),
+        ] {
+            settings.heading_slug_style = style;
+            let path = root_dir.join("Note.md");
+            let vault = Vault::of_notes(
+                &settings,
+                &root_dir,
+                [(path.clone(), "## What's New?\n\n## C# Tips")],
+            );
+
+            let resolves = |text| {
+                let link = Reference::new(text, "test.md").next().unwrap();
+                vault
+                    .select_referenceables_for_reference(&link, &root_dir.join("test.md"))
+                    .iter()
+                    .any(|referenceable| matches!(referenceable, Referenceable::Heading(..)))
+            };
+
+            assert_eq!(resolves("[new](Note.md#whats-new)"), slug_resolves);
+            assert!(resolves("[new](Note.md#What's%20New?)"));
+            // Wiki links name headings by their text whatever the style, even text with a `#`
+            assert!(resolves("[[Note#What's New?]]"));
+            assert!(resolves("[[Note#C# Tips]]"));
+            assert!(!resolves("[[Note#whats-new]]"));
+        }
+    }
+
+    #[test]
+    fn same_file_anchor_links_resolve() {
+        let (root_dir, settings) = test_settings();
+
+        // Two notes with the same name, each with the linked heading and block
+        let (path, other_path) = (root_dir.join("a/Note.md"), root_dir.join("b/Note.md"));
+        let text = "# Heading\n\nA paragraph ^block";
+        let (md_file, other_md_file) = (
+            MDFile::new(&settings, &root_dir, text, path.clone()),
+            MDFile::new(&settings, &root_dir, text, other_path.clone()),
+        );
+
+        let resolves = |text, referenceable: &Referenceable| {
+            Reference::new(text, "Note")
+                .next()
+                .unwrap()
+                .references(&root_dir, &path, referenceable)
+        };
+
+        for (heading_link, block_link) in [
+            ("[[#Heading]]", "[[#^block]]"),
+            ("[text](#Heading)", "[text](#^block)"),
+        ] {
+            assert!(resolves(
+                heading_link,
+                &Referenceable::Heading(&path, &md_file.headings[0])
+            ));
+            assert!(!resolves(
+                heading_link,
+                &Referenceable::Heading(&other_path, &other_md_file.headings[0])
+            ));
+            assert!(resolves(
+                block_link,
+                &Referenceable::IndexedBlock(&path, &md_file.indexed_blocks[0])
+            ));
+            assert!(!resolves(
+                block_link,
+                &Referenceable::IndexedBlock(&other_path, &other_md_file.indexed_blocks[0])
+            ));
+        }
+    }
+
+    #[test]
+    fn attribute_anchors_resolve() {
+        let (root_dir, mut settings) = test_settings();
+        settings.attribute_anchors = true;
+
+        let path = root_dir.join("Note.md");
+        let text =
+            "# Heading {#custom-id}\n\nA paragraph {#para-id .note}\n\nNot an {#anchor} here";
+        let md_file = MDFile::new(&settings, &root_dir, text, path.clone());
+
+        assert_eq!(md_file.headings[0].heading_text, "Heading");
+        assert_eq!(
+            md_file
+                .indexed_blocks
+                .iter()
+                .map(|block| block.index.as_str())
+                .collect_vec(),
+            vec!["custom-id", "para-id"]
+        );
+
+        let link = Reference::new("[[Note#custom-id]]", "test.md")
+            .next()
+            .unwrap();
+        let anchor = Referenceable::IndexedBlock(&path, &md_file.indexed_blocks[0]);
+        assert!(link.references(&root_dir, &root_dir.join("test.md"), &anchor));
+    }
+
+    #[test]
+    fn attachment_links_resolve() {
+        let root_dir = PathBuf::from("/home/vault");
+        let path = PathBuf::from("/home/vault/assets/screenshots/diagram.png");
+        let attachments_folder = root_dir.join("assets");
+        let attachment = Referenceable::Attachment(&path, &attachments_folder);
+
+        let text = "![[diagram.png|300]] [[diagram]] [[other.png]] ![[screenshots/diagram.png]] ![[other/diagram.png]] [image](assets/screenshots/diagram.png) [site](https://example.com)";
+        let parsed = Reference::new(text, "test.md").collect_vec();
+
+        assert_eq!(parsed.len(), 6);
+        assert_eq!(parsed[0].data().display_text.as_deref(), Some("300"));
+        assert_eq!(
+            parsed
+                .iter()
+                .map(|reference| reference.references(
+                    &root_dir,
+                    &root_dir.join("test.md"),
+                    &attachment
+                ))
+                .collect_vec(),
+            vec![true, false, false, true, false, true]
+        );
     }
 
     #[test]
@@ -2117,6 +3693,27 @@ more text
         )
     }
 
+    #[test]
+    fn encoded_link_path_round_trip() {
+        let root_dir = Path::new("/home/vault");
+        let md_file = MDFile::default();
+
+        let path_buf = Path::new("/home/vault/My Note.md").to_path_buf();
+        let linkable = Referenceable::File(&path_buf, &md_file);
+        let encoded = encode_link_path("My Note");
+        assert_eq!(encoded, "My%20Note");
+        assert!(matches_path_or_file(&encoded, linkable.get_refname(root_dir)));
+
+        let path_buf = Path::new("/home/vault/folder/My (Other) Note.md").to_path_buf();
+        let linkable = Referenceable::File(&path_buf, &md_file);
+        let encoded = encode_link_path("folder/My (Other) Note");
+        assert_eq!(encoded, "folder/My%20%28Other%29%20Note");
+        assert!(matches_path_or_file(&encoded, linkable.get_refname(root_dir)));
+
+        assert_eq!(decode_link_path(r"My\ Note"), "My Note");
+        assert_eq!(decode_link_path("100%"), "100%");
+    }
+
     #[test]
     fn parsing_special_text() {
         let text = "’’’󰌶 is a [[link]] [[link 2]]\n[[link 3]]";
@@ -2346,6 +3943,25 @@ Continued
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn parse_link_ref_def_titles() {
+        let text = "[id1]: http://x \"My Title\"\n[id2]: <my note.md> 'Note'\n[id3]: http://y (Paren)\n[id4]: http://z";
+
+        let parsed = MDLinkReferenceDefinition::new(text)
+            .map(|definition| (definition.url, definition.title))
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("http://x".to_string(), Some("My Title".to_string())),
+                ("my note.md".to_string(), Some("Note".to_string())),
+                ("http://y".to_string(), Some("Paren".to_string())),
+                ("http://z".to_string(), None),
+            ]
+        );
+    }
+
     #[test]
     fn parse_link_ref() {
         let text = "This is a [link]j\n\n[link]: linktext";
@@ -2371,6 +3987,65 @@ Continued
         assert_eq!(parsed, expected);
     }
 
+    #[test]
+    fn link_ref_forms() {
+        let text =
+            "[Full text][id] [id][] [id] [ID] [sic] [text](note)\n\n[id]: https://example.com";
+
+        let parsed = Reference::new(text, "test.md")
+            .map(|reference| {
+                let data = reference.data();
+                (
+                    data.reference_text.clone(),
+                    data.display_text.clone(),
+                    data.range.start.character,
+                    data.range.end.character,
+                )
+            })
+            .filter(|(reference_text, ..)| reference_text != "note")
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("id".to_string(), Some("Full text".to_string()), 0, 15),
+                ("id".to_string(), None, 16, 22),
+                ("id".to_string(), None, 23, 27),
+                ("ID".to_string(), None, 28, 32),
+            ]
+        );
+
+        let path = PathBuf::from("/home/vault/test.md");
+        let definitions = MDLinkReferenceDefinition::new(text).collect_vec();
+        let definition = Referenceable::LinkRefDef(&path, &definitions[0]);
+
+        assert!(Reference::new(text, "test")
+            .filter(|reference| matches!(reference, Reference::LinkRef(..)))
+            .all(|reference| definition.matches_reference(
+                Path::new("/home/vault"),
+                &reference,
+                &path
+            )));
+    }
+
+    #[test]
+    fn link_ref_definition_references() {
+        let text = "See [link], [link] [link]\nand [other]\n\n[link]: https://example.com";
+        let path = PathBuf::from("/home/vault/test.md");
+
+        let definitions = MDLinkReferenceDefinition::new(text).collect_vec();
+        let definition = Referenceable::LinkRefDef(&path, &definitions[0]);
+
+        let references = Reference::new(text, "test")
+            .filter(|reference| {
+                definition.matches_reference(Path::new("/home/vault"), reference, &path)
+            })
+            .map(|reference| reference.data().range.start.character)
+            .collect_vec();
+
+        assert_eq!(references, vec![4, 12, 19]);
+    }
+
     #[test]
     fn tag_in_md_link_display() {
         let text = "This [Issue #seven](https://github.com/users/Feel-ix-343/projects/3/views/1?pane=issue&itemId=63386256)";
@@ -2436,4 +4111,66 @@ Continued
 
         assert_eq!(expected, parsed)
     }
+
+    #[test]
+    fn frontmatter_tags() {
+        let text =
+            "---\ntitle: Note\ntags:\n  - a/b\n  - \"c\" # d\n- '#e'\n\naliases: [f]\n---\n#g";
+
+        let parsed = MDTag::new_frontmatter(text)
+            .into_iter()
+            .map(|tag| (tag.tag_ref, tag.range.start))
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                ("a/b".to_string(), Position::new(3, 4)),
+                ("c".to_string(), Position::new(4, 5)),
+                ("e".to_string(), Position::new(5, 4)),
+            ]
+        );
+
+        let inline = |text| {
+            MDTag::new_frontmatter(text)
+                .into_iter()
+                .map(|tag| tag.tag_ref)
+                .collect_vec()
+        };
+
+        assert_eq!(inline("---\ntags: [a/b, \"c\"]\n---"), vec!["a/b", "c"]);
+        assert_eq!(inline("---\ntags: a/b, c d\n---"), vec!["a/b", "c", "d"]);
+        assert_eq!(inline("tags: [a]\n"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reconstruction_reuses_unchanged_files() {
+        let root_dir = std::env::temp_dir().join(format!("moxide-{}", nanoid::nanoid!()));
+        std::fs::create_dir_all(&root_dir).unwrap();
+        let (a, b) = (root_dir.join("a.md"), root_dir.join("b.md"));
+        std::fs::write(&a, "# A\n[[b]]").unwrap();
+        std::fs::write(&b, "# B").unwrap();
+
+        let settings =
+            Settings::without_global_config(&root_dir, &ClientCapabilities::default()).unwrap();
+        let mut old = Vault::construct_vault(&settings, &root_dir, None).unwrap();
+
+        // A reused parse is the one of the old vault, so a marker in it survives reconstruction
+        old.md_files.get_mut(&a).unwrap().references.clear();
+        std::fs::write(&b, "# B\n## New").unwrap();
+
+        let new = Vault::construct_vault(&settings, &root_dir, Some(&old)).unwrap();
+        std::fs::remove_dir_all(&root_dir).unwrap();
+
+        assert_eq!(new.md_files[&a], old.md_files[&a]);
+        assert_eq!(
+            new.md_files[&b]
+                .headings
+                .iter()
+                .map(|heading| heading.heading_text.as_str())
+                .collect_vec(),
+            vec!["B", "New"]
+        );
+        assert_eq!(new.ropes[&b].to_string(), "# B\n## New");
+    }
 }
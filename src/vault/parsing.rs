@@ -1,6 +1,7 @@
 use once_cell::sync::Lazy;
 use regex::Regex;
 use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range};
 
 use super::{MyRange, Rangeable};
 
@@ -42,12 +43,81 @@ impl Rangeable for MDCodeBlock {
     }
 }
 
+/// An Obsidian callout, as in `> [!warning] Title`, with the quoted lines continuing it. Callouts within it, quoted once
+/// more, are callouts of their own and part of its body.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MDCallout {
+    /// The type of the callout, as `warning`
+    pub kind: String,
+    pub title: Option<String>,
+    /// The title, or the end of the first line if there is none
+    pub title_range: MyRange,
+    /// From the first line of the callout to the end of its last quoted line
+    pub range: MyRange,
+}
+
+impl MDCallout {
+    pub fn new(text: &str) -> impl Iterator<Item = MDCallout> + '_ {
+        static CALLOUT_RE: Lazy<Regex> = Lazy::new(|| {
+            Regex::new(r"^\s*(?:>\s*)+\[!(?<kind>[^\]\s]+)\][+-]?(?<title>.*)$")
+                .expect("Callout Regex Not Constructing")
+        });
+
+        let lines = text.lines().collect::<Vec<_>>();
+
+        (0..lines.len()).flat_map(move |line| {
+            let captures = CALLOUT_RE.captures(lines[line])?;
+            let depth = quote_depth(lines[line]);
+
+            let last = line
+                + lines[line + 1..]
+                    .iter()
+                    .take_while(|next| quote_depth(next) >= depth)
+                    .count();
+
+            let title = captures.name("title")?;
+            let title_text = title.as_str().trim();
+            let character = |byte: usize| lines[line][..byte].chars().count() as u32;
+            let title_start = title.start() + (title.len() - title.as_str().trim_start().len());
+
+            Some(MDCallout {
+                kind: captures.name("kind")?.as_str().to_lowercase(),
+                title: (!title_text.is_empty()).then(|| title_text.to_string()),
+                title_range: Range {
+                    start: Position::new(line as u32, character(title_start)),
+                    end: Position::new(line as u32, character(title_start + title_text.len())),
+                }
+                .into(),
+                range: Range {
+                    start: Position::new(line as u32, 0),
+                    end: Position::new(last as u32, lines[last].chars().count() as u32),
+                }
+                .into(),
+            })
+        })
+    }
+}
+
+/// The number of `>` markers a line is quoted with, as 2 for `> > text`
+fn quote_depth(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| *c == '>' || c.is_whitespace())
+        .filter(|c| *c == '>')
+        .count()
+}
+
+impl Rangeable for MDCallout {
+    fn range(&self) -> &MyRange {
+        &self.range
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
     use tower_lsp::lsp_types::{Position, Range};
 
-    use super::MDCodeBlock;
+    use super::{MDCallout, MDCodeBlock};
 
     #[test]
     fn test_code_block_parsing() {
@@ -290,4 +360,43 @@ fj aklfjd
 
         assert_eq!(parsed, expected)
     }
+
+    #[test]
+    fn nested_and_multiline_callouts() {
+        let test = "Text\n> [!Warning] Careful here\n> First line\n>\n> > [!tip]\n> > Nested\n> Last line\n\n> [!note]\n";
+
+        let parsed = MDCallout::new(test)
+            .map(|callout| {
+                (
+                    callout.kind,
+                    callout.title,
+                    callout.range.start.line,
+                    callout.range.end.line,
+                )
+            })
+            .collect_vec();
+
+        assert_eq!(
+            parsed,
+            vec![
+                (
+                    "warning".to_string(),
+                    Some("Careful here".to_string()),
+                    1,
+                    6
+                ),
+                ("tip".to_string(), None, 4, 5),
+                ("note".to_string(), None, 8, 8),
+            ]
+        );
+
+        let callout = MDCallout::new(test).next().unwrap();
+        assert_eq!(
+            *callout.title_range,
+            Range {
+                start: Position::new(1, 13),
+                end: Position::new(1, 25),
+            }
+        );
+    }
 }